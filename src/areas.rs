@@ -0,0 +1,476 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::FIELD_SIZE;
+
+#[derive(Reflect, Clone, Debug, PartialEq)]
+pub enum AreaShape {
+    Rect(Rect),
+    Circle { center: Vec2, radius: f32 },
+    // Outline in order, either winding. May be concave but must not cross itself.
+    Polygon(Vec<Vec2>),
+}
+impl Default for AreaShape {
+    fn default() -> Self {
+        AreaShape::Rect(Rect::default())
+    }
+}
+
+impl AreaShape {
+    pub fn contains(&self, p: Vec2) -> bool {
+        match self {
+            AreaShape::Rect(r) => r.contains(p),
+            AreaShape::Circle { center, radius } => center.distance_squared(p) <= radius * radius,
+            AreaShape::Polygon(points) => {
+                // even-odd ray cast towards +x
+                let mut inside = false;
+                for (a, b) in edges(points) {
+                    if (a.y > p.y) != (b.y > p.y) {
+                        let x = a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x);
+                        if p.x < x {
+                            inside = !inside;
+                        }
+                    }
+                }
+                inside
+            }
+        }
+    }
+    pub fn bounds(&self) -> Rect {
+        match self {
+            AreaShape::Rect(r) => *r,
+            AreaShape::Circle { center, radius } => {
+                Rect::from_center_half_size(*center, Vec2::splat(*radius))
+            }
+            // An empty outline has no extent, a zero rect at the origin stands in.
+            AreaShape::Polygon(points) => match points.split_first() {
+                Some((first, rest)) => rest
+                    .iter()
+                    .fold(Rect::from_corners(*first, *first), |r, p| r.union_point(*p)),
+                None => Rect::default(),
+            },
+        }
+    }
+    // The point of the shape nearest to `p`, `p` itself when inside.
+    pub fn closest(&self, p: Vec2) -> Vec2 {
+        if self.contains(p) {
+            return p;
+        }
+        match self {
+            AreaShape::Rect(r) => p.clamp(r.min, r.max),
+            AreaShape::Circle { center, radius } => {
+                *center + (p - *center).normalize_or_zero() * *radius
+            }
+            AreaShape::Polygon(points) => edges(points)
+                .map(|(a, b)| {
+                    let t = (p - a).dot(b - a) / (b - a).length_squared().max(f32::EPSILON);
+                    a + (b - a) * t.clamp(0.0, 1.0)
+                })
+                .min_by(|a, b| a.distance_squared(p).total_cmp(&b.distance_squared(p)))
+                .unwrap_or(p),
+        }
+    }
+    pub fn area(&self) -> f32 {
+        match self {
+            AreaShape::Rect(r) => r.width() * r.height(),
+            AreaShape::Circle { radius, .. } => std::f32::consts::PI * radius * radius,
+            AreaShape::Polygon(points) => signed_area(points).abs(),
+        }
+    }
+    // Uniform over the inside of the shape.
+    pub fn sample(&self, rng: &mut impl Rng) -> Vec2 {
+        match self {
+            AreaShape::Rect(r) => Vec2::new(
+                rng.gen_range(r.min.x..=r.max.x),
+                rng.gen_range(r.min.y..=r.max.y),
+            ),
+            AreaShape::Circle { center, radius } => {
+                let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                let distance = radius * rng.gen::<f32>().sqrt();
+                *center + Vec2::from_angle(angle) * distance
+            }
+            AreaShape::Polygon(points) => {
+                let triangles = triangulate(points);
+                let total: f32 = triangles.iter().map(triangle_area).sum();
+                let mut pick = rng.gen_range(0.0..=total);
+                let picked = triangles
+                    .iter()
+                    .find(|t| {
+                        pick -= triangle_area(t);
+                        pick <= 0.0
+                    })
+                    .or(triangles.last());
+                // Fewer than three points has no inside to pick from.
+                let Some(&[a, b, c]) = picked else {
+                    return points.first().copied().unwrap_or_default();
+                };
+                let (mut u, mut v) = (rng.gen::<f32>(), rng.gen::<f32>());
+                if u + v > 1.0 {
+                    (u, v) = (1.0 - u, 1.0 - v);
+                }
+                a + (b - a) * u + (c - a) * v
+            }
+        }
+    }
+    pub fn draw<T: GizmoConfigGroup>(&self, gizmos: &mut Gizmos<T>, color: Color) {
+        match self {
+            AreaShape::Rect(r) => gizmos.rect_2d(r.center(), 0., r.size(), color),
+            AreaShape::Circle { center, radius } => {
+                gizmos.circle_2d(*center, *radius, color);
+            }
+            AreaShape::Polygon(points) => {
+                gizmos.linestrip_2d(points.iter().chain(points.first()).copied(), color)
+            }
+        }
+    }
+}
+
+fn edges(points: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| (*a, *b))
+}
+fn signed_area(points: &[Vec2]) -> f32 {
+    edges(points).map(|(a, b)| a.perp_dot(b)).sum::<f32>() / 2.
+}
+fn triangle_area(t: &[Vec2; 3]) -> f32 {
+    ((t[1] - t[0]).perp_dot(t[2] - t[0]) / 2.).abs()
+}
+fn in_triangle(p: Vec2, [a, b, c]: [Vec2; 3]) -> bool {
+    let d1 = (b - a).perp_dot(p - a);
+    let d2 = (c - b).perp_dot(p - b);
+    let d3 = (a - c).perp_dot(p - c);
+    d1 >= 0. && d2 >= 0. && d3 >= 0.
+}
+// Ear clipping, good enough for the handful of vertices a level outline has.
+pub fn triangulate(points: &[Vec2]) -> Vec<[Vec2; 3]> {
+    let mut index: Vec<usize> = (0..points.len()).collect();
+    if signed_area(points) < 0. {
+        index.reverse();
+    }
+    let mut triangles = Vec::new();
+    while index.len() > 3 {
+        let n = index.len();
+        let ear = (0..n).find(|i| {
+            let t = [
+                points[index[(i + n - 1) % n]],
+                points[index[*i]],
+                points[index[(i + 1) % n]],
+            ];
+            let convex = (t[1] - t[0]).perp_dot(t[2] - t[1]) > 0.;
+            convex
+                && !index
+                    .iter()
+                    .map(|j| points[*j])
+                    .any(|p| !t.contains(&p) && in_triangle(p, t))
+        });
+        // Only happens for self-intersecting outlines, keep what was found so far.
+        let Some(i) = ear else {
+            break;
+        };
+        triangles.push([
+            points[index[(i + n - 1) % n]],
+            points[index[i]],
+            points[index[(i + 1) % n]],
+        ]);
+        index.remove(i);
+    }
+    if let [a, b, c] = index[..] {
+        triangles.push([points[a], points[b], points[c]]);
+    }
+    triangles
+}
+
+#[derive(Reflect, Clone, Debug, PartialEq)]
+pub struct Region {
+    pub shape: AreaShape,
+    // Relative chance of this region being picked when sampling.
+    pub weight: f32,
+}
+impl Region {
+    pub fn new(shape: AreaShape) -> Self {
+        Self { shape, weight: 1.0 }
+    }
+}
+impl Default for Region {
+    fn default() -> Self {
+        Self::new(AreaShape::default())
+    }
+}
+
+#[derive(Reflect, Clone, Debug, Default, PartialEq)]
+pub struct Regions(pub Vec<Region>);
+
+impl Regions {
+    pub fn single(shape: AreaShape) -> Self {
+        Self(vec![Region::new(shape)])
+    }
+    pub fn contains(&self, p: Vec2) -> bool {
+        self.0.iter().any(|r| r.shape.contains(p))
+    }
+    // Indices of every region `p` is inside of.
+    pub fn containing(&self, p: Vec2) -> impl Iterator<Item = usize> + '_ {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(move |(_, r)| r.shape.contains(p))
+            .map(|(i, _)| i)
+    }
+    pub fn bounds(&self) -> Option<Rect> {
+        self.0
+            .iter()
+            .map(|r| r.shape.bounds())
+            .reduce(|a, b| a.union(b))
+    }
+    pub fn choose(&self, rng: &mut impl Rng) -> Option<&Region> {
+        let total: f32 = self.0.iter().map(|r| r.weight.max(0.)).sum();
+        if total <= 0. {
+            return None;
+        }
+        let mut pick = rng.gen_range(0.0..total);
+        self.0
+            .iter()
+            .find(|r| {
+                pick -= r.weight.max(0.);
+                pick < 0.
+            })
+            .or(self.0.last())
+    }
+    pub fn sample(&self, rng: &mut impl Rng) -> Option<Vec2> {
+        self.choose(rng).map(|r| r.shape.sample(rng))
+    }
+    pub fn closest(&self, p: Vec2) -> Option<Vec2> {
+        self.0
+            .iter()
+            .map(|r| r.shape.closest(p))
+            .min_by(|a, b| a.distance_squared(p).total_cmp(&b.distance_squared(p)))
+    }
+    // Moves from `from` towards `to` without leaving the regions, sliding along
+    // an axis when the direct move would cross an edge. Starting outside, as
+    // after the area shrinks, pulls `to` onto the nearest region instead.
+    pub fn confine(&self, from: Vec2, to: Vec2) -> Vec2 {
+        if self.0.is_empty() || self.contains(to) {
+            return to;
+        }
+        if !self.contains(from) {
+            return self.closest(to).unwrap_or(to);
+        }
+        [Vec2::new(to.x, from.y), Vec2::new(from.x, to.y)]
+            .into_iter()
+            .find(|p| self.contains(*p))
+            .unwrap_or(from)
+    }
+    pub fn draw<T: GizmoConfigGroup>(&self, gizmos: &mut Gizmos<T>, color: Color) {
+        for r in self.0.iter() {
+            r.shape.draw(gizmos, color);
+        }
+    }
+}
+
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct SpawnArea {
+    pub(crate) regions: Regions,
+    #[cfg(debug)]
+    pub color: Color,
+}
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct SafeArea {
+    pub(crate) regions: Regions,
+    #[cfg(debug)]
+    pub color: Color,
+}
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct PlayArea {
+    pub(crate) regions: Regions,
+    #[cfg(debug)]
+    pub color: Color,
+}
+// Defaults are laid out around the visible extent of the camera, so they are
+// rebuilt by `view` whenever the window changes size.
+pub fn centered(extent: Vec2) -> Regions {
+    Regions::single(AreaShape::Rect(Rect::from_center_size(Vec2::ZERO, extent)))
+}
+impl SpawnArea {
+    pub fn for_extent(extent: Vec2) -> Self {
+        Self {
+            regions: centered(extent * 2.),
+            #[cfg(debug)]
+            color: Color::RED,
+        }
+    }
+}
+impl SafeArea {
+    pub fn for_extent(extent: Vec2) -> Self {
+        Self {
+            regions: centered(extent * 2. / 3.),
+            #[cfg(debug)]
+            color: Color::BLUE,
+        }
+    }
+}
+impl PlayArea {
+    pub fn for_extent(extent: Vec2) -> Self {
+        Self {
+            regions: centered(extent),
+            #[cfg(debug)]
+            color: Color::BLUE,
+        }
+    }
+}
+impl Default for SpawnArea {
+    fn default() -> Self {
+        Self::for_extent(FIELD_SIZE)
+    }
+}
+impl Default for SafeArea {
+    fn default() -> Self {
+        Self::for_extent(FIELD_SIZE)
+    }
+}
+impl Default for PlayArea {
+    fn default() -> Self {
+        Self::for_extent(FIELD_SIZE)
+    }
+}
+pub struct AreaPlugin;
+impl Plugin for AreaPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpawnArea>()
+            .init_resource::<SafeArea>()
+            .init_resource::<PlayArea>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    // An L, concave at (1, 1).
+    fn ell() -> AreaShape {
+        AreaShape::Polygon(vec![
+            Vec2::new(0., 0.),
+            Vec2::new(2., 0.),
+            Vec2::new(2., 1.),
+            Vec2::new(1., 1.),
+            Vec2::new(1., 2.),
+            Vec2::new(0., 2.),
+        ])
+    }
+
+    #[test]
+    fn contains_points_inside_each_shape() {
+        let rect = AreaShape::Rect(Rect::new(0., 0., 2., 1.));
+        assert!(rect.contains(Vec2::new(1., 0.5)));
+        assert!(!rect.contains(Vec2::new(1., 1.5)));
+        let circle = AreaShape::Circle {
+            center: Vec2::new(1., 1.),
+            radius: 1.,
+        };
+        assert!(circle.contains(Vec2::new(1.5, 1.5)));
+        assert!(!circle.contains(Vec2::new(1.9, 1.9)));
+        assert!(ell().contains(Vec2::new(0.5, 1.5)));
+        assert!(ell().contains(Vec2::new(1.5, 0.5)));
+        assert!(!ell().contains(Vec2::new(1.5, 1.5)));
+    }
+
+    #[test]
+    fn triangulates_concave_outlines_either_winding() {
+        let AreaShape::Polygon(mut points) = ell() else {
+            unreachable!()
+        };
+        for _ in 0..2 {
+            let triangles = triangulate(&points);
+            assert_eq!(triangles.len(), points.len() - 2);
+            let total: f32 = triangles.iter().map(triangle_area).sum();
+            assert!((total - 3.).abs() < 1e-5);
+            points.reverse();
+        }
+    }
+
+    #[test]
+    fn samples_stay_inside() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let center = Vec2::new(-5., 3.);
+        let circle = AreaShape::Circle { center, radius: 2. };
+        for _ in 0..500 {
+            assert!(circle.sample(&mut rng).distance(center) <= 2. + 1e-4);
+            let p = ell().sample(&mut rng);
+            // Inside the bounds and out of the notch, give or take float error.
+            assert!(
+                Rect::new(-1e-4, -1e-4, 2. + 1e-4, 2. + 1e-4).contains(p),
+                "{p}"
+            );
+            assert!(p.x < 1. + 1e-4 || p.y < 1. + 1e-4, "{p}");
+        }
+    }
+
+    #[test]
+    fn degenerate_polygons_do_not_panic() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let empty = AreaShape::Polygon(Vec::new());
+        assert_eq!(empty.bounds(), Rect::default());
+        assert_eq!(empty.area(), 0.);
+        assert!(!empty.contains(Vec2::ZERO));
+        assert_eq!(empty.sample(&mut rng), Vec2::ZERO);
+        let point = AreaShape::Polygon(vec![Vec2::new(3., 4.)]);
+        assert_eq!(point.sample(&mut rng), Vec2::new(3., 4.));
+    }
+
+    #[test]
+    fn choose_follows_the_weights() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut regions = Regions(vec![
+            Region::new(AreaShape::Rect(Rect::new(0., 0., 1., 1.))),
+            Region::new(AreaShape::Rect(Rect::new(5., 5., 6., 6.))),
+        ]);
+        regions.0[0].weight = 0.;
+        for _ in 0..100 {
+            let p = regions.sample(&mut rng).unwrap();
+            assert!(regions.0[1].shape.contains(p));
+        }
+        regions.0[1].weight = 0.;
+        assert!(regions.choose(&mut rng).is_none());
+        assert!(Regions::default().sample(&mut rng).is_none());
+    }
+
+    #[test]
+    fn confine_slides_along_edges() {
+        let area = Regions::single(AreaShape::Rect(Rect::new(-100., -100., 100., 100.)));
+        let from = Vec2::new(90., 0.);
+        assert_eq!(area.confine(from, Vec2::new(95., 5.)), Vec2::new(95., 5.));
+        assert_eq!(area.confine(from, Vec2::new(110., 5.)), Vec2::new(90., 5.));
+        assert_eq!(
+            Regions::default().confine(from, Vec2::splat(1e6)),
+            Vec2::splat(1e6)
+        );
+    }
+
+    #[test]
+    fn confine_pulls_points_from_outside_back_in() {
+        let area = Regions(vec![
+            Region::new(AreaShape::Rect(Rect::new(-100., -100., 100., 100.))),
+            Region::new(AreaShape::Circle {
+                center: Vec2::new(400., 0.),
+                radius: 50.,
+            }),
+        ]);
+        // Left outside when the area shrank, still trying to fly away.
+        let from = Vec2::new(-300., 20.);
+        assert_eq!(
+            area.confine(from, Vec2::new(-310., 20.)),
+            Vec2::new(-100., 20.)
+        );
+        // Nearer the circle than the rect.
+        let p = area.confine(Vec2::new(500., 0.), Vec2::new(510., 0.));
+        assert!(p.distance(Vec2::new(450., 0.)) < 1e-3, "{p}");
+        let ell = Regions::single(ell());
+        let p = ell.confine(Vec2::new(1.6, 1.5), Vec2::new(1.6, 1.3));
+        assert!(p.distance(Vec2::new(1.6, 1.)) < 1e-5, "{p}");
+    }
+}
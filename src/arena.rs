@@ -0,0 +1,915 @@
+use std::{
+    fmt::{self, Write},
+    fs,
+    path::PathBuf,
+};
+
+use bevy::{
+    asset::io::file::FileAssetReader,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+    },
+};
+use bevy_ecs_tilemap::{helpers::geometry::get_tilemap_center_transform, prelude::*};
+use bevy_sepax2d::prelude::{
+    sepax2d::{sat_collision, sat_overlap, Polygon},
+    *,
+};
+
+use crate::{
+    areas::{AreaShape, PlayArea, Region, Regions, SafeArea, SpawnArea},
+    pool::GameAssets,
+    powerups::{self, PowerUpKind, TableEntry},
+    Player, Rock, Ship, MAX_ROCK_SIZE, ROCK_SIZES, SPAWNER_TIMER,
+};
+
+pub const DEFAULT_ARENA: &str = "open";
+// Characters understood inside a `layer` block, in tileset order.
+const TILE_CHARS: [char; 4] = ['#', '%', ',', '*'];
+const TILE_COLORS: [[u8; 4]; 4] = [
+    [90, 90, 110, 255],
+    [70, 60, 80, 255],
+    [40, 60, 40, 255],
+    [55, 45, 40, 255],
+];
+
+// The generated tileset is one image of whole-pixel tiles.
+const MAX_TILE_SIZE: f32 = 256.0;
+
+// Keeps any sum of weights finite for the samplers' `gen_range`.
+pub const MAX_WEIGHT: f32 = 1000.0;
+
+// Keywords that may follow a spawner's position.
+const SPAWNER_OPTIONS: [&str; 3] = ["timer", "sizes", "table"];
+
+#[derive(Resource)]
+pub struct SelectedArena(pub String);
+
+impl SelectedArena {
+    // `--arena <name>` wins over `GAME_ARENA`, falling back to the open field.
+    fn from_env() -> Self {
+        let mut args = std::env::args().skip_while(|a| a != "--arena").skip(1);
+        let name = args
+            .next()
+            .or_else(|| std::env::var("GAME_ARENA").ok())
+            .unwrap_or_else(|| DEFAULT_ARENA.into());
+        Self(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnerKind {
+    Rock,
+    Ship,
+    PowerUp,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpawnerDef {
+    pub kind: SpawnerKind,
+    pub position: Vec2,
+    pub timer: f32,
+    // Rock radii, only used by rock spawners.
+    pub sizes: Vec<f32>,
+    // Drop chances, only used by power-up spawners.
+    pub table: Vec<TableEntry>,
+}
+
+impl SpawnerDef {
+    pub fn new(kind: SpawnerKind, position: Vec2) -> Self {
+        Self {
+            kind,
+            position,
+            timer: SPAWNER_TIMER,
+            sizes: ROCK_SIZES.to_vec(),
+            table: powerups::default_table(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LayerDef {
+    pub name: String,
+    pub solid: bool,
+    pub size: UVec2,
+    // Row-major, bottom row first, indices into `TILE_CHARS`.
+    pub tiles: Vec<Option<u32>>,
+}
+
+impl LayerDef {
+    pub fn get(&self, x: u32, y: u32) -> Option<u32> {
+        if x >= self.size.x || y >= self.size.y {
+            return None;
+        }
+        self.tiles[(y * self.size.x + x) as usize]
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct Arena {
+    pub name: String,
+    pub tile_size: f32,
+    // Empty when the file keeps the default area.
+    pub spawn: Regions,
+    pub safe: Regions,
+    pub play: Regions,
+    pub spawners: Vec<SpawnerDef>,
+    pub layers: Vec<LayerDef>,
+}
+
+impl Arena {
+    // Used when the selected file is missing or broken so the game stays playable.
+    // Keeps the selected name, so saving from the editor creates that arena.
+    fn fallback(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            tile_size: 32.0,
+            spawn: Regions::default(),
+            safe: Regions::default(),
+            play: Regions::default(),
+            spawners: vec![
+                SpawnerDef::new(SpawnerKind::Rock, Vec2::ZERO),
+                SpawnerDef::new(SpawnerKind::Ship, Vec2::ZERO),
+                SpawnerDef::new(SpawnerKind::PowerUp, Vec2::ZERO),
+            ],
+            layers: Vec::new(),
+        }
+    }
+
+    pub fn path(name: &str) -> PathBuf {
+        FileAssetReader::get_base_path()
+            .join("assets")
+            .join("arenas")
+            .join(format!("{name}.arena"))
+    }
+
+    pub fn load(name: &str) -> Result<Self, ArenaError> {
+        let path = Self::path(name);
+        let source = fs::read_to_string(&path).map_err(|e| ArenaError::Io(path, e))?;
+        Self::parse(name, &source)
+    }
+
+    pub fn parse(name: &str, source: &str) -> Result<Self, ArenaError> {
+        let mut arena = Self {
+            name: name.into(),
+            tile_size: 32.0,
+            spawn: Regions::default(),
+            safe: Regions::default(),
+            play: Regions::default(),
+            spawners: Vec::new(),
+            layers: Vec::new(),
+        };
+        let mut lines = source.lines().enumerate();
+        while let Some((n, line)) = lines.next() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap();
+            let args: Vec<&str> = words.collect();
+            match keyword {
+                "tile" => {
+                    arena.tile_size = parse_positive(n, &args)?;
+                    if arena.tile_size.fract() != 0.0 || arena.tile_size > MAX_TILE_SIZE {
+                        return Err(ArenaError::Syntax(
+                            n + 1,
+                            "tile size must be a whole number up to 256",
+                        ));
+                    }
+                }
+                "spawn" => arena.spawn.0.push(parse_region(n, &args)?),
+                "safe" => arena.safe.0.push(parse_region(n, &args)?),
+                "play" => arena.play.0.push(parse_region(n, &args)?),
+                "spawner" => {
+                    let kind = match args.first() {
+                        Some(&"rock") => SpawnerKind::Rock,
+                        Some(&"ship") => SpawnerKind::Ship,
+                        Some(&"powerup") => SpawnerKind::PowerUp,
+                        _ => {
+                            return Err(ArenaError::Syntax(n + 1, "expected rock, ship or powerup"))
+                        }
+                    };
+                    if args.len() < 3 {
+                        return Err(ArenaError::Syntax(n + 1, "spawner needs a position"));
+                    }
+                    let [x, y] = parse_floats::<2>(n, &args[1..3])?;
+                    let mut spawner = SpawnerDef::new(kind, Vec2::new(x, y));
+                    // Optional `timer <seconds>`, `sizes <radius>...` and
+                    // `table <powerup> <weight>...` after the position.
+                    let mut rest = args[3..].iter().peekable();
+                    while let Some(key) = rest.next() {
+                        match *key {
+                            "timer" => {
+                                let value = rest.next().copied().unwrap_or_default();
+                                spawner.timer = parse_positive(n, &[value])?;
+                            }
+                            "sizes" => {
+                                spawner.sizes.clear();
+                                while let Some(v) = rest.next_if(|v| v.parse::<f32>().is_ok()) {
+                                    let size = parse_positive(n, &[v])?;
+                                    if size > MAX_ROCK_SIZE {
+                                        return Err(ArenaError::Syntax(
+                                            n + 1,
+                                            "rock size too large",
+                                        ));
+                                    }
+                                    spawner.sizes.push(size);
+                                }
+                                if spawner.sizes.is_empty() {
+                                    return Err(ArenaError::Syntax(n + 1, "sizes needs a value"));
+                                }
+                            }
+                            "table" => {
+                                spawner.table.clear();
+                                while let Some(name) = rest.next_if(|v| {
+                                    v.parse::<f32>().is_err() && !SPAWNER_OPTIONS.contains(*v)
+                                }) {
+                                    let kind = PowerUpKind::ALL
+                                        .into_iter()
+                                        .find(|k| k.name() == *name)
+                                        .ok_or(ArenaError::Syntax(n + 1, "unknown power-up"))?;
+                                    let weight = rest.next().copied().unwrap_or_default();
+                                    let weight = parse_weight(n, &[weight])?;
+                                    spawner.table.push(TableEntry { kind, weight });
+                                }
+                                if spawner.table.is_empty() {
+                                    return Err(ArenaError::Syntax(n + 1, "table needs a value"));
+                                }
+                            }
+                            _ => return Err(ArenaError::Syntax(n + 1, "unknown spawner option")),
+                        }
+                    }
+                    arena.spawners.push(spawner);
+                }
+                "layer" => {
+                    let name = args
+                        .first()
+                        .ok_or(ArenaError::Syntax(n + 1, "layer needs a name"))?;
+                    let solid = args.get(1) == Some(&"solid");
+                    // Layer rows are kept verbatim, so '#' is a wall here rather than a comment.
+                    let mut rows = Vec::new();
+                    loop {
+                        let Some((_, row)) = lines.next() else {
+                            return Err(ArenaError::Syntax(n + 1, "layer is missing `end`"));
+                        };
+                        if row.trim() == "end" {
+                            break;
+                        }
+                        rows.push(row.trim_end());
+                    }
+                    arena.layers.push(parse_layer(n, name, solid, &rows)?);
+                }
+                _ => return Err(ArenaError::Syntax(n + 1, "unknown keyword")),
+            }
+        }
+        Ok(arena)
+    }
+
+    // Writes the arena back out in the same format `parse` reads.
+    pub fn to_source(&self) -> String {
+        let mut out = String::new();
+        let regions = |out: &mut String, key: &str, regions: &Regions| {
+            for region in regions.0.iter() {
+                let _ = match &region.shape {
+                    AreaShape::Rect(r) => write!(
+                        out,
+                        "{key} rect {} {} {} {}",
+                        r.min.x, r.min.y, r.max.x, r.max.y
+                    ),
+                    AreaShape::Circle { center, radius } => {
+                        write!(out, "{key} circle {} {} {radius}", center.x, center.y)
+                    }
+                    AreaShape::Polygon(points) => {
+                        out.push_str(key);
+                        out.push_str(" poly");
+                        points
+                            .iter()
+                            .try_for_each(|p| write!(out, " {} {}", p.x, p.y))
+                    }
+                };
+                if region.weight != 1.0 {
+                    let _ = write!(out, " weight {}", region.weight);
+                }
+                out.push('\n');
+            }
+        };
+        let _ = writeln!(out, "tile {}", self.tile_size);
+        regions(&mut out, "spawn", &self.spawn);
+        regions(&mut out, "safe", &self.safe);
+        regions(&mut out, "play", &self.play);
+        for s in self.spawners.iter() {
+            let kind = match s.kind {
+                SpawnerKind::Rock => "rock",
+                SpawnerKind::Ship => "ship",
+                SpawnerKind::PowerUp => "powerup",
+            };
+            let _ = write!(
+                out,
+                "spawner {kind} {} {} timer {}",
+                s.position.x, s.position.y, s.timer
+            );
+            if s.kind == SpawnerKind::Rock {
+                out.push_str(" sizes");
+                for r in s.sizes.iter() {
+                    let _ = write!(out, " {r}");
+                }
+            }
+            if s.kind == SpawnerKind::PowerUp {
+                out.push_str(" table");
+                for e in s.table.iter() {
+                    let _ = write!(out, " {} {}", e.kind.name(), e.weight);
+                }
+            }
+            out.push('\n');
+        }
+        for layer in self.layers.iter() {
+            let solid = if layer.solid { " solid" } else { "" };
+            let _ = writeln!(out, "\nlayer {}{solid}", layer.name);
+            for y in (0..layer.size.y).rev() {
+                for x in 0..layer.size.x {
+                    out.push(match layer.get(x, y) {
+                        Some(t) => TILE_CHARS[t as usize],
+                        None => '.',
+                    });
+                }
+                out.push('\n');
+            }
+            out.push_str("end\n");
+        }
+        out
+    }
+
+    pub fn save(&self) -> Result<PathBuf, ArenaError> {
+        let path = Self::path(&self.name);
+        fs::write(&path, self.to_source()).map_err(|e| ArenaError::Io(path.clone(), e))?;
+        Ok(path)
+    }
+}
+
+fn parse_floats<const N: usize>(n: usize, args: &[&str]) -> Result<[f32; N], ArenaError> {
+    if args.len() != N {
+        return Err(ArenaError::Syntax(n + 1, "wrong number of values"));
+    }
+    let mut out = [0.0; N];
+    for (o, a) in out.iter_mut().zip(args) {
+        *o = a
+            .parse()
+            .map_err(|_| ArenaError::Syntax(n + 1, "expected a number"))?;
+    }
+    Ok(out)
+}
+// Sizes and durations, which panic further down when zero, negative or not finite.
+fn parse_positive(n: usize, args: &[&str]) -> Result<f32, ArenaError> {
+    let [value] = parse_floats::<1>(n, args)?;
+    if !value.is_finite() || value <= 0.0 {
+        return Err(ArenaError::Syntax(n + 1, "expected a positive number"));
+    }
+    Ok(value)
+}
+fn parse_weight(n: usize, args: &[&str]) -> Result<f32, ArenaError> {
+    let [value] = parse_floats::<1>(n, args)?;
+    if !(0.0..=MAX_WEIGHT).contains(&value) {
+        return Err(ArenaError::Syntax(n + 1, "weight must be from 0 to 1000"));
+    }
+    Ok(value)
+}
+// `rect x0 y0 x1 y1`, `circle x y radius` or `poly x y x y x y...`, optionally
+// followed by `weight <w>`. Four bare numbers are read as a rect.
+fn parse_region(n: usize, args: &[&str]) -> Result<Region, ArenaError> {
+    let (args, weight) = match args.iter().position(|a| *a == "weight") {
+        Some(i) => (&args[..i], parse_weight(n, &args[i + 1..])?),
+        None => (args, 1.0),
+    };
+    let shape = match args.first().copied() {
+        Some("rect") => {
+            let [x0, y0, x1, y1] = parse_floats::<4>(n, &args[1..])?;
+            AreaShape::Rect(Rect::new(x0, y0, x1, y1))
+        }
+        Some("circle") => {
+            let [x, y, radius] = parse_floats::<3>(n, &args[1..])?;
+            AreaShape::Circle {
+                center: Vec2::new(x, y),
+                radius,
+            }
+        }
+        Some("poly") => {
+            let coords = args[1..]
+                .iter()
+                .map(|a| a.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| ArenaError::Syntax(n + 1, "expected a number"))?;
+            if coords.len() < 6 || coords.len() % 2 != 0 {
+                return Err(ArenaError::Syntax(
+                    n + 1,
+                    "poly needs at least three x y pairs",
+                ));
+            }
+            AreaShape::Polygon(coords.chunks(2).map(|c| Vec2::new(c[0], c[1])).collect())
+        }
+        _ => {
+            let [x0, y0, x1, y1] = parse_floats::<4>(n, args)?;
+            AreaShape::Rect(Rect::new(x0, y0, x1, y1))
+        }
+    };
+    Ok(Region { shape, weight })
+}
+fn parse_layer(n: usize, name: &str, solid: bool, rows: &[&str]) -> Result<LayerDef, ArenaError> {
+    let width = rows.iter().map(|r| r.chars().count()).max().unwrap_or(0) as u32;
+    let height = rows.len() as u32;
+    let mut tiles = vec![None; (width * height) as usize];
+    for (row, line) in rows.iter().enumerate() {
+        // The file is written top to bottom, tiles are stored bottom to top.
+        let y = height - 1 - row as u32;
+        for (x, c) in line.chars().enumerate() {
+            let index = match c {
+                '.' | ' ' => continue,
+                c => TILE_CHARS
+                    .iter()
+                    .position(|t| *t == c)
+                    .ok_or(ArenaError::Syntax(n + 1, "unknown tile character"))?,
+            };
+            tiles[(y * width) as usize + x] = Some(index as u32);
+        }
+    }
+    Ok(LayerDef {
+        name: name.into(),
+        solid,
+        size: UVec2::new(width, height),
+        tiles,
+    })
+}
+
+#[derive(Debug)]
+pub enum ArenaError {
+    Io(PathBuf, std::io::Error),
+    Syntax(usize, &'static str),
+}
+impl fmt::Display for ArenaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArenaError::Io(path, e) => write!(f, "could not read {}: {e}", path.display()),
+            ArenaError::Syntax(line, msg) => write!(f, "line {line}: {msg}"),
+        }
+    }
+}
+impl std::error::Error for ArenaError {}
+
+#[derive(Component)]
+pub struct ArenaLayer {
+    pub index: usize,
+    pub solid: bool,
+}
+
+#[derive(Component)]
+pub struct Wall;
+
+pub struct ArenaPlugin;
+impl Plugin for ArenaPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(TilemapPlugin)
+            .insert_resource(SelectedArena::from_env())
+            .add_systems(Startup, load_arena)
+            .add_systems(Update, build_wall_colliders)
+            .add_systems(
+                FixedUpdate,
+                resolve_wall_collisions
+                    .after(crate::player_movement)
+                    .after(crate::rotate_to_player)
+                    .after(crate::rotate_to_point)
+                    .before(crate::player_collision),
+            );
+    }
+}
+
+fn load_arena(
+    mut cmds: Commands,
+    selected: Res<SelectedArena>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    assets: Res<GameAssets>,
+    mut spawn: ResMut<SpawnArea>,
+    mut safe: ResMut<SafeArea>,
+    mut play: ResMut<PlayArea>,
+) {
+    let arena = Arena::load(&selected.0).unwrap_or_else(|e| {
+        error!("failed to load arena {:?}: {}", selected.0, e);
+        Arena::fallback(&selected.0)
+    });
+    info!("loaded arena {:?}", arena.name);
+
+    if !arena.spawn.0.is_empty() {
+        spawn.regions = arena.spawn.clone();
+    }
+    if !arena.safe.0.is_empty() {
+        safe.regions = arena.safe.clone();
+    }
+    if !arena.play.0.is_empty() {
+        play.regions = arena.play.clone();
+    }
+    for s in arena.spawners.iter() {
+        spawn_spawner(&mut cmds, &mut meshes, &assets, s);
+    }
+
+    let texture = images.add(create_tileset(arena.tile_size as u32));
+    for (index, layer) in arena.layers.iter().enumerate() {
+        spawn_layer(&mut cmds, &arena, index, layer, texture.clone());
+    }
+    cmds.insert_resource(arena);
+}
+
+pub fn spawn_spawner(
+    cmds: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    assets: &GameAssets,
+    def: &SpawnerDef,
+) -> Entity {
+    match def.kind {
+        SpawnerKind::Rock => {
+            crate::spawn_rock_spawner(cmds, meshes, assets, def.position, def.timer, &def.sizes)
+        }
+        SpawnerKind::Ship => crate::spawn_ship_spawner(cmds, assets, def.position, def.timer),
+        SpawnerKind::PowerUp => {
+            crate::spawn_power_up_spawner(cmds, assets, def.position, def.timer, &def.table)
+        }
+    }
+}
+
+fn spawn_layer(
+    cmds: &mut Commands,
+    arena: &Arena,
+    index: usize,
+    layer: &LayerDef,
+    texture: Handle<Image>,
+) {
+    let map_size = TilemapSize {
+        x: layer.size.x,
+        y: layer.size.y,
+    };
+    let tile_size = TilemapTileSize {
+        x: arena.tile_size,
+        y: arena.tile_size,
+    };
+    let grid_size = tile_size.into();
+    let map_type = TilemapType::default();
+    let mut storage = TileStorage::empty(map_size);
+    let tilemap = cmds.spawn_empty().id();
+    for y in 0..layer.size.y {
+        for x in 0..layer.size.x {
+            let Some(tile) = layer.get(x, y) else {
+                continue;
+            };
+            let position = TilePos { x, y };
+            let entity = cmds
+                .spawn(TileBundle {
+                    position,
+                    tilemap_id: TilemapId(tilemap),
+                    texture_index: TileTextureIndex(tile),
+                    ..default()
+                })
+                .id();
+            storage.set(&position, entity);
+        }
+    }
+    // Layers sit below every gameplay entity, later layers drawn on top of earlier ones.
+    let z = -10.0 + index as f32;
+    cmds.entity(tilemap).insert((
+        TilemapBundle {
+            grid_size,
+            map_type,
+            size: map_size,
+            storage,
+            texture: TilemapTexture::Single(texture),
+            tile_size,
+            transform: get_tilemap_center_transform(&map_size, &grid_size, &map_type, z),
+            ..default()
+        },
+        ArenaLayer {
+            index,
+            solid: layer.solid,
+        },
+        Name::new(format!("arena layer {}", layer.name)),
+    ));
+}
+
+// Builds a horizontal strip with one flat tile per `TILE_CHARS` entry, so arenas
+// render without any art on disk.
+fn create_tileset(tile: u32) -> Image {
+    let width = tile * TILE_COLORS.len() as u32;
+    let mut data = Vec::with_capacity((width * tile * 4) as usize);
+    for y in 0..tile {
+        for x in 0..width {
+            let color = TILE_COLORS[(x / tile) as usize];
+            let (tx, ty) = (x % tile, y);
+            let edge = tx == 0 || ty == 0 || tx == tile - 1 || ty == tile - 1;
+            if edge {
+                data.extend_from_slice(&[color[0] / 2, color[1] / 2, color[2] / 2, 255]);
+            } else {
+                data.extend_from_slice(&color);
+            }
+        }
+    }
+    let mut image = Image::new(
+        Extent3d {
+            width,
+            height: tile,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage |= TextureUsages::COPY_SRC;
+    image
+}
+
+// Greedily merges solid tiles into rectangles: each rectangle grows right along
+// its row first, then down while the whole span below is still solid and unclaimed.
+pub fn merge_solid_tiles(size: UVec2, solid: impl Fn(u32, u32) -> bool) -> Vec<URect> {
+    let mut claimed = vec![false; (size.x * size.y) as usize];
+    let free =
+        |claimed: &[bool], x: u32, y: u32| solid(x, y) && !claimed[(y * size.x + x) as usize];
+    let mut rects = Vec::new();
+    for y in 0..size.y {
+        let mut x = 0;
+        while x < size.x {
+            if !free(&claimed, x, y) {
+                x += 1;
+                continue;
+            }
+            let mut x1 = x;
+            while x1 + 1 < size.x && free(&claimed, x1 + 1, y) {
+                x1 += 1;
+            }
+            let mut y1 = y;
+            while y1 + 1 < size.y && (x..=x1).all(|tx| free(&claimed, tx, y1 + 1)) {
+                y1 += 1;
+            }
+            for ty in y..=y1 {
+                for tx in x..=x1 {
+                    claimed[(ty * size.x + tx) as usize] = true;
+                }
+            }
+            rects.push(URect::new(x, y, x1, y1));
+            x = x1 + 1;
+        }
+    }
+    rects
+}
+
+fn build_wall_colliders(
+    mut cmds: Commands,
+    layers: Query<
+        (
+            &ArenaLayer,
+            &TileStorage,
+            &TilemapSize,
+            &TilemapGridSize,
+            &TilemapType,
+            &Transform,
+        ),
+        Added<ArenaLayer>,
+    >,
+) {
+    for (layer, storage, map_size, grid_size, map_type, transform) in layers.iter() {
+        if !layer.solid {
+            continue;
+        }
+        let rects = merge_solid_tiles(UVec2::new(map_size.x, map_size.y), |x, y| {
+            storage.get(&TilePos { x, y }).is_some()
+        });
+        for r in rects.iter() {
+            let min = TilePos::new(r.min.x, r.min.y).center_in_world(grid_size, map_type);
+            let max = TilePos::new(r.max.x, r.max.y).center_in_world(grid_size, map_type);
+            let center = transform
+                .transform_point(((min + max) / 2.).extend(0.))
+                .xy();
+            let half = Vec2::new(
+                (r.width() + 1) as f32 * grid_size.x,
+                (r.height() + 1) as f32 * grid_size.y,
+            ) / 2.;
+            let shape = Polygon::from_vertices(
+                (center.x, center.y),
+                vec![
+                    (-half.x, -half.y),
+                    (half.x, -half.y),
+                    (half.x, half.y),
+                    (-half.x, half.y),
+                ],
+            );
+            cmds.spawn((
+                TransformBundle::from_transform(Transform::from_translation(center.extend(0.))),
+                Sepax {
+                    convex: Convex::Polygon(shape),
+                },
+                Wall,
+            ));
+        }
+        info!(
+            "arena layer {} produced {} wall colliders",
+            layer.index,
+            rects.len()
+        );
+    }
+}
+
+// Pushes anything that moves back out of the walls. The Sepax shapes are only
+// synced with transforms later in the frame, so a copy is moved to where the
+// entity is now before testing it.
+fn resolve_wall_collisions(
+    walls: Query<&Sepax, With<Wall>>,
+    mut movers: Query<
+        (&Sepax, &mut Transform),
+        (Without<Wall>, Or<(With<Player>, With<Rock>, With<Ship>)>),
+    >,
+) {
+    for (sepax, mut transform) in movers.iter_mut() {
+        let mut shape = Sepax {
+            convex: sepax.convex.clone(),
+        };
+        for wall in walls.iter() {
+            shape
+                .shape_mut()
+                .set_position((transform.translation.x, transform.translation.y));
+            if sat_overlap(wall.shape(), shape.shape()) {
+                let (x, y) = sat_collision(wall.shape(), shape.shape());
+                transform.translation.x += x;
+                transform.translation.y += y;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+tile 16
+spawn rect -100 -100 100 100
+safe circle 0 0 20 weight 2
+spawner rock 10 -5 timer 2.5 sizes 10 20
+spawner ship 0 0
+
+layer floor
+,,,
+end
+
+layer walls solid
+#.#
+##.
+end
+";
+
+    #[test]
+    fn parses_an_arena() {
+        let arena = Arena::parse("test", SOURCE).unwrap();
+        assert_eq!(arena.tile_size, 16.0);
+        assert_eq!(arena.spawn.0.len(), 1);
+        assert_eq!(arena.safe.0[0].weight, 2.0);
+        assert_eq!(arena.spawners.len(), 2);
+        assert_eq!(arena.spawners[0].kind, SpawnerKind::Rock);
+        assert_eq!(arena.spawners[0].position, Vec2::new(10.0, -5.0));
+        assert_eq!(arena.spawners[0].timer, 2.5);
+        assert_eq!(arena.spawners[0].sizes, vec![10.0, 20.0]);
+        assert_eq!(arena.spawners[1].timer, SPAWNER_TIMER);
+
+        let walls = &arena.layers[1];
+        assert!(walls.solid);
+        assert_eq!(walls.size, UVec2::new(3, 2));
+        // The last row in the file is the bottom one.
+        assert_eq!(walls.get(0, 0), Some(0));
+        assert_eq!(walls.get(2, 0), None);
+        assert_eq!(walls.get(1, 1), None);
+        assert_eq!(walls.get(3, 0), None);
+    }
+
+    #[test]
+    fn source_round_trips() {
+        let arena = Arena::parse("test", SOURCE).unwrap();
+        let source = arena.to_source();
+        let again = Arena::parse("test", &source).unwrap();
+        assert_eq!(again.to_source(), source);
+        assert_eq!(again.spawn, arena.spawn);
+        assert_eq!(again.safe, arena.safe);
+        assert_eq!(again.layers[1].tiles, arena.layers[1].tiles);
+    }
+
+    #[test]
+    fn rejects_bad_timers() {
+        for timer in ["-1", "0", "inf", "NaN", "1e40"] {
+            let source = format!("spawner rock 0 0 timer {timer}");
+            assert!(
+                matches!(Arena::parse("test", &source), Err(ArenaError::Syntax(1, _))),
+                "timer {timer}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_bad_tile_sizes() {
+        for tile in ["0", "-16", "0.5", "16.5", "257", "100000", "inf"] {
+            let source = format!("\n\ntile {tile}");
+            assert!(
+                matches!(Arena::parse("test", &source), Err(ArenaError::Syntax(3, _))),
+                "tile {tile}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_bad_sizes() {
+        for size in ["0", "-10", "inf", "NaN", "161"] {
+            let source = format!("spawner rock 0 0 sizes 10 {size}");
+            assert!(
+                matches!(Arena::parse("test", &source), Err(ArenaError::Syntax(1, _))),
+                "size {size}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_bad_region_weights() {
+        for weight in ["-1", "1001", "1e39", "inf", "NaN"] {
+            let source = format!("spawn rect 0 0 10 10 weight {weight}");
+            assert!(
+                matches!(Arena::parse("test", &source), Err(ArenaError::Syntax(1, _))),
+                "weight {weight}"
+            );
+        }
+    }
+
+    #[test]
+    fn reads_options_after_a_table() {
+        let source = "spawner powerup 0 0 table shield 1 rapid 2.5 timer 5";
+        let arena = Arena::parse("test", source).unwrap();
+        let spawner = &arena.spawners[0];
+        assert_eq!(spawner.timer, 5.0);
+        assert_eq!(
+            spawner.table,
+            vec![
+                TableEntry {
+                    kind: PowerUpKind::Shield,
+                    weight: 1.0
+                },
+                TableEntry {
+                    kind: PowerUpKind::RapidFire,
+                    weight: 2.5
+                },
+            ]
+        );
+        assert!(matches!(
+            Arena::parse("test", "spawner powerup 0 0 table laser 1"),
+            Err(ArenaError::Syntax(1, "unknown power-up"))
+        ));
+        for weight in ["-1", "1e39", "NaN"] {
+            let source = format!("spawner powerup 0 0 table shield {weight}");
+            assert!(
+                matches!(Arena::parse("test", &source), Err(ArenaError::Syntax(1, _))),
+                "weight {weight}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unterminated_layers() {
+        let source = "layer walls solid\n##\n";
+        assert!(matches!(
+            Arena::parse("test", source),
+            Err(ArenaError::Syntax(1, _))
+        ));
+    }
+
+    #[test]
+    fn merges_a_full_block_into_one_rect() {
+        assert_eq!(merge_solid_tiles(UVec2::new(4, 3), |_, _| false), vec![]);
+        assert_eq!(
+            merge_solid_tiles(UVec2::new(4, 3), |_, _| true),
+            vec![URect::new(0, 0, 3, 2)]
+        );
+    }
+
+    #[test]
+    fn merged_rects_cover_each_solid_tile_once() {
+        let rows = ["##..#", "###.#", ".##.#", "....."];
+        let size = UVec2::new(5, rows.len() as u32);
+        let solid = |x: u32, y: u32| rows[y as usize].as_bytes()[x as usize] == b'#';
+        let rects = merge_solid_tiles(size, solid);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let covering = rects
+                    .iter()
+                    .filter(|r| {
+                        (r.min.x..=r.max.x).contains(&x) && (r.min.y..=r.max.y).contains(&y)
+                    })
+                    .count();
+                assert_eq!(covering, solid(x, y) as usize, "tile {x} {y}");
+            }
+        }
+        // One rect for the column on the right, three for the stepped shape.
+        assert_eq!(rects.len(), 4);
+    }
+}
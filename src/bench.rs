@@ -0,0 +1,398 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    ecs::{schedule::ExecutorKind, system::RunSystemOnce},
+    prelude::*,
+};
+use bevy_sepax2d::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::{
+    areas::{PlayArea, SafeArea, SpawnArea},
+    camera, particles,
+    players::PlayerInput,
+    pool::{EntityPool, GameAssets},
+    progress,
+    sampling::{GameRng, SpawnSampler},
+    weapon, ROCK_SIZES, SPAWNER_TIMER,
+};
+
+const DEFAULT_COUNTS: [usize; 3] = [100, 1_000, 10_000];
+const DEFAULT_TICKS: u32 = 600;
+// Runs with the same seed start from the same layout and can be compared.
+const DEFAULT_SEED: u64 = 1;
+// Same rate as the fixed schedule in the game.
+const TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchConfig {
+    // Each run starts with this many rocks and as many ships.
+    pub counts: Vec<usize>,
+    pub ticks: u32,
+    pub seed: u64,
+}
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            counts: DEFAULT_COUNTS.to_vec(),
+            ticks: DEFAULT_TICKS,
+            seed: DEFAULT_SEED,
+        }
+    }
+}
+
+impl BenchConfig {
+    // `--counts 100,1000 --ticks 600 --seed 1`, all optional.
+    pub fn parse(args: &[String]) -> Result<Self, BenchError> {
+        let mut config = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !matches!(arg.as_str(), "--counts" | "--ticks" | "--seed") {
+                return Err(BenchError::Unknown(arg.clone()));
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| BenchError::Missing(arg.clone()))?;
+            let invalid = |_| BenchError::Invalid(arg.clone(), value.clone());
+            match arg.as_str() {
+                "--counts" => {
+                    config.counts = value
+                        .split(',')
+                        .map(|c| c.trim().parse())
+                        .collect::<Result<_, _>>()
+                        .map_err(invalid)?
+                }
+                "--ticks" => config.ticks = value.parse().map_err(invalid)?,
+                _ => config.seed = value.parse().map_err(invalid)?,
+            }
+        }
+        if config.counts.is_empty() || config.ticks == 0 {
+            return Err(BenchError::Empty);
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Debug)]
+pub enum BenchError {
+    Unknown(String),
+    Missing(String),
+    Invalid(String, String),
+    Empty,
+    Io(io::Error),
+}
+impl fmt::Display for BenchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BenchError::Unknown(arg) => write!(f, "unknown argument {arg}"),
+            BenchError::Missing(arg) => write!(f, "{arg} needs a value"),
+            BenchError::Invalid(arg, value) => write!(f, "invalid value {value} for {arg}"),
+            BenchError::Empty => write!(f, "nothing to run"),
+            BenchError::Io(e) => write!(f, "could not write results: {e}"),
+        }
+    }
+}
+impl std::error::Error for BenchError {}
+
+#[derive(Default)]
+struct Timing {
+    runs: u32,
+    total: Duration,
+    max: Duration,
+}
+impl Timing {
+    fn add(&mut self, elapsed: Duration) {
+        self.runs += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+    }
+    fn mean(&self) -> Duration {
+        self.total / self.runs.max(1)
+    }
+    fn per_second(&self) -> f64 {
+        self.runs as f64 / self.total.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+// Each system gets a schedule of its own so it can be timed on its own.
+struct Stage {
+    name: &'static str,
+    schedule: Schedule,
+    timing: Timing,
+}
+impl Stage {
+    fn new<M>(name: &'static str, system: impl IntoSystemConfigs<M>) -> Self {
+        let mut schedule = Schedule::default();
+        schedule.add_systems(system);
+        Self::with(name, schedule)
+    }
+    fn with(name: &'static str, mut schedule: Schedule) -> Self {
+        schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+        Self {
+            name,
+            schedule,
+            timing: Timing::default(),
+        }
+    }
+}
+
+struct RunResult {
+    count: usize,
+    ticks: u32,
+    entities: u32,
+    tick: Timing,
+    stages: Vec<Stage>,
+}
+
+// Entry point for `game --bench ...`, prints csv rows on stdout and a summary on stderr.
+pub fn main(args: &[String]) {
+    let config = match BenchConfig::parse(args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("bench: {e}");
+            eprintln!("usage: game --bench [--counts 100,1000,10000] [--ticks 600] [--seed 1]");
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = run(&config, &mut io::stdout().lock()) {
+        eprintln!("bench: {e}");
+        std::process::exit(1);
+    }
+}
+
+pub fn run(config: &BenchConfig, out: &mut impl Write) -> Result<(), BenchError> {
+    writeln!(
+        out,
+        "count,ticks,stage,mean_us,max_us,total_ms,per_second,entities"
+    )
+    .map_err(BenchError::Io)?;
+    for &count in config.counts.iter() {
+        let result = run_once(count, config.ticks, config.seed);
+        write_rows(out, &result).map_err(BenchError::Io)?;
+        eprintln!(
+            "{} rocks + {} ships: {:.1} ticks/s over {} ticks, {} entities at the end",
+            count,
+            count,
+            result.tick.per_second(),
+            result.ticks,
+            result.entities
+        );
+    }
+    Ok(())
+}
+
+fn write_rows(out: &mut impl Write, result: &RunResult) -> io::Result<()> {
+    let rows = result
+        .stages
+        .iter()
+        .map(|s| (s.name, &s.timing))
+        .chain([("tick", &result.tick)]);
+    for (name, timing) in rows {
+        writeln!(
+            out,
+            "{},{},{},{:.2},{:.2},{:.3},{:.1},{}",
+            result.count,
+            result.ticks,
+            name,
+            timing.mean().as_secs_f64() * 1e6,
+            timing.max.as_secs_f64() * 1e6,
+            timing.total.as_secs_f64() * 1e3,
+            timing.per_second(),
+            result.entities
+        )?;
+    }
+    Ok(())
+}
+
+// The gameplay plugins also pull in windows, input and rendering, so the
+// bench registers only what the timed systems read.
+fn headless_app(seed: u64) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        HierarchyPlugin,
+        TransformPlugin,
+        SepaxPlugin,
+    ))
+    .init_asset::<Mesh>()
+    .init_asset::<ColorMaterial>()
+    .init_resource::<SpawnArea>()
+    .init_resource::<SafeArea>()
+    .init_resource::<PlayArea>()
+    .init_resource::<SpawnSampler>()
+    .insert_resource(GameRng::seeded(seed))
+    .init_resource::<EntityPool>()
+    .init_resource::<GameAssets>()
+    .add_event::<camera::Trauma>()
+    .add_event::<progress::PlayerHit>()
+    .add_event::<particles::Burst>()
+    .add_event::<weapon::EnemyDestroyed>();
+    app.finish();
+    app.cleanup();
+    app
+}
+
+fn populate(
+    In(count): In<usize>,
+    mut cmds: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut pool: ResMut<EntityPool>,
+    assets: Res<GameAssets>,
+    spawn: Res<SpawnArea>,
+    safe: Res<SafeArea>,
+    mut sampler: ResMut<SpawnSampler>,
+    mut rng: ResMut<GameRng>,
+) {
+    sampler.rebuild(&spawn.regions, &safe.regions);
+    // Same layout as the fallback arena.
+    crate::create_player(&mut cmds, &mut meshes, &mut materials, 0, Vec2::ZERO);
+    crate::spawn_rock_spawner(
+        &mut cmds,
+        &mut meshes,
+        &assets,
+        Vec2::ZERO,
+        SPAWNER_TIMER,
+        &ROCK_SIZES,
+    );
+    crate::spawn_ship_spawner(&mut cmds, &assets, Vec2::ZERO, SPAWNER_TIMER);
+
+    let rock_meshes = crate::rock_meshes(&mut meshes, &ROCK_SIZES);
+    let rng = &mut rng.0;
+    // The sampler keeps spawns apart, which thousands of them can't be.
+    let point = |rng: &mut StdRng| {
+        (0..32)
+            .filter_map(|_| spawn.regions.sample(rng))
+            .find(|p| !safe.regions.contains(*p))
+            .unwrap_or(Vec2::ZERO)
+    };
+    for _ in 0..count {
+        let at = point(rng);
+        crate::create_rock(
+            &mut cmds,
+            &mut pool,
+            &assets,
+            rng,
+            &ROCK_SIZES,
+            &rock_meshes,
+            at,
+            Vec2::ZERO,
+        );
+        let at = point(rng);
+        crate::create_ship(&mut cmds, &mut pool, &assets, at);
+    }
+}
+
+fn run_once(count: usize, ticks: u32, seed: u64) -> RunResult {
+    let mut app = headless_app(seed);
+    app.world.run_system_once_with(count, populate);
+    // Hold fire so the projectile systems have work to do.
+    for mut input in app
+        .world
+        .query::<&mut PlayerInput>()
+        .iter_mut(&mut app.world)
+    {
+        input.fire = true;
+    }
+
+    // In the order the game runs them, fixed update first.
+    let mut stages = vec![
+        Stage::new("player_movement", crate::player_movement),
+        Stage::new("fire", weapon::fire),
+        Stage::new("rotate_to_player", crate::rotate_to_player),
+        Stage::new("rotate_to_point", crate::rotate_to_point),
+        Stage::new("move_projectiles", weapon::move_projectiles),
+        Stage::new("player_collision", crate::player_collision),
+        Stage::new("projectile_hits", weapon::projectile_hits),
+        Stage::new("rock_despawn", crate::rock_despawn),
+        Stage::new("ship_despawn", crate::ship_despawn),
+        Stage::new("spawn_rocks", crate::spawn_rocks),
+        Stage::new("spawn_ships", crate::spawn_ships),
+    ];
+    // Sepax's own collision systems and transform propagation live in these.
+    let mut schedules = app.world.resource_mut::<Schedules>();
+    let update = schedules.remove(Update);
+    let post_update = schedules.remove(PostUpdate);
+    stages.extend(update.map(|s| Stage::with("update", s)));
+    stages.extend(post_update.map(|s| Stage::with("post_update", s)));
+
+    let mut clock = Time::<()>::default();
+    let mut tick = Timing::default();
+    for _ in 0..ticks {
+        // Event buffers and the real clocks, the game time is stepped by hand.
+        app.world.run_schedule(First);
+        clock.advance_by(TICK);
+        app.world.insert_resource(clock);
+        let start = Instant::now();
+        for stage in stages.iter_mut() {
+            let stage_start = Instant::now();
+            stage.schedule.run(&mut app.world);
+            stage.timing.add(stage_start.elapsed());
+        }
+        tick.add(start.elapsed());
+    }
+    RunResult {
+        count,
+        ticks,
+        entities: app.world.entities().len(),
+        tick,
+        stages,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rock;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parses_options() {
+        assert_eq!(BenchConfig::parse(&[]).unwrap(), BenchConfig::default());
+        assert_eq!(
+            BenchConfig::parse(&args("--seed 42 --counts 10,20 --ticks 5")).unwrap(),
+            BenchConfig {
+                counts: vec![10, 20],
+                ticks: 5,
+                seed: 42,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_bad_options() {
+        for line in [
+            "--fast",
+            "--seed",
+            "--seed -1",
+            "--ticks x",
+            "--counts 10,x",
+            "--ticks 0",
+        ] {
+            assert!(BenchConfig::parse(&args(line)).is_err(), "{line}");
+        }
+    }
+
+    #[test]
+    fn populates_the_same_with_a_seed() {
+        let layout = |seed| {
+            let mut app = headless_app(seed);
+            app.world.run_system_once_with(20, populate);
+            app.world
+                .query_filtered::<&Transform, With<Rock>>()
+                .iter(&app.world)
+                .map(|t| t.translation)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(layout(7), layout(7));
+        assert_ne!(layout(7), layout(8));
+    }
+}
@@ -0,0 +1,389 @@
+use bevy::{
+    prelude::*,
+    render::camera::{ScalingMode, Viewport},
+    transform::TransformSystem,
+    window::PrimaryWindow,
+};
+
+use crate::{
+    areas::PlayArea,
+    players::MAX_PLAYERS,
+    view::{self, PlayField},
+    Player, Rock, Ship,
+};
+
+// Zoom the shared camera would need to fit everyone before the screen splits,
+// and the zoom it has to get back under before it merges again.
+const SPLIT_ZOOM: f32 = 2.0;
+const MERGE_ZOOM: f32 = 1.5;
+// World units kept around the outermost players on the shared camera.
+const FIT_MARGIN: f32 = 150.0;
+// Black line between split views in physical pixels.
+const SPLIT_GAP: u32 = 2;
+
+// Added to the camera's trauma, which is kept in 0..=1 and decays over time.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Trauma(pub f32);
+
+#[derive(Component, Debug, Clone)]
+pub struct CameraRig {
+    // Higher follows faster, roughly the inverse of the catch-up time in seconds.
+    pub smoothing: f32,
+    // Half size of the box around the focus the player can move in freely.
+    pub deadzone: Vec2,
+    pub trauma: f32,
+    pub trauma_decay: f32,
+    pub max_offset: f32,
+    pub max_roll: f32,
+    // Enemies within `crowd_radius` of the player beyond `crowd_threshold`
+    // each zoom out by `zoom_per_enemy`, up to `max_zoom`.
+    pub crowd_radius: f32,
+    pub crowd_threshold: usize,
+    pub zoom_per_enemy: f32,
+    pub max_zoom: f32,
+    pub zoom_speed: f32,
+    // Entity to center on instead of the players, e.g. the inspector's selection.
+    pub follow: Option<Entity>,
+    // Smoothed position before shake is applied.
+    focus: Vec2,
+    zoom: f32,
+    elapsed: f32,
+}
+impl Default for CameraRig {
+    fn default() -> Self {
+        Self {
+            smoothing: 4.0,
+            deadzone: Vec2::new(80.0, 60.0),
+            trauma: 0.0,
+            trauma_decay: 1.2,
+            max_offset: 24.0,
+            max_roll: 0.05,
+            crowd_radius: 400.0,
+            crowd_threshold: 4,
+            zoom_per_enemy: 0.05,
+            max_zoom: 1.6,
+            zoom_speed: 1.5,
+            follow: None,
+            focus: Vec2::ZERO,
+            zoom: 1.0,
+            elapsed: 0.0,
+        }
+    }
+}
+
+// The player slot a camera follows in split screen. The main camera is slot
+// 0 and follows everyone while the screen is shared.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerView(pub usize);
+
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    #[default]
+    Shared,
+    Split,
+}
+
+impl CameraRig {
+    // Point the camera should move towards so the player stays inside the deadzone.
+    pub fn desired(&self, player: Vec2) -> Vec2 {
+        let delta = player - self.focus;
+        self.focus + delta - delta.clamp(-self.deadzone, self.deadzone)
+    }
+    // Smooth pseudo-random shake in -1..=1, one channel per `seed`.
+    fn noise(&self, seed: f32) -> f32 {
+        let t = self.elapsed * 25.0 + seed * 17.0;
+        ((t.sin() + (t * 2.3).sin() * 0.5 + (t * 4.7).sin() * 0.25) / 1.75).clamp(-1.0, 1.0)
+    }
+}
+
+// Keeps a rectangle of `half` size centered on `p` inside `bounds`, centering
+// it on any axis where it does not fit.
+pub fn clamp_view(p: Vec2, half: Vec2, bounds: Rect) -> Vec2 {
+    let min = bounds.min + half;
+    let max = bounds.max - half;
+    let axis = |p: f32, min: f32, max: f32| {
+        if min > max {
+            (min + max) / 2.
+        } else {
+            p.clamp(min, max)
+        }
+    };
+    Vec2::new(axis(p.x, min.x, max.x), axis(p.y, min.y, max.y))
+}
+
+// Smallest rect holding every point, grown by `margin`.
+fn spread(points: &[Vec2], margin: f32) -> Option<Rect> {
+    let first = *points.first()?;
+    let rect = points
+        .iter()
+        .fold(Rect::from_center_size(first, Vec2::ZERO), |r, p| {
+            r.union_point(*p)
+        });
+    Some(rect.inset(margin))
+}
+
+// Zoom needed to show all of `rect` in a view of `visible` world units.
+fn fit_zoom(rect: Rect, visible: Vec2) -> f32 {
+    (rect.size() / visible.max(Vec2::ONE)).max_element()
+}
+
+// Side by side for two views, a 2x2 grid for three or four.
+fn panes(base: URect, count: usize) -> Vec<URect> {
+    let (columns, rows) = match count {
+        0 | 1 => (1, 1),
+        2 => (2, 1),
+        _ => (2, 2),
+    };
+    let size = base.size() / UVec2::new(columns, rows);
+    (0..count.max(1) as u32)
+        .map(|i| {
+            let cell = UVec2::new(i % columns, i / columns);
+            let min = base.min + cell * size;
+            let gap =
+                UVec2::new((cell.x + 1 < columns) as u32, (cell.y + 1 < rows) as u32) * SPLIT_GAP;
+            URect::from_corners(min, (min + size - gap).max(min + UVec2::ONE))
+        })
+        .collect()
+}
+
+pub struct CameraPlugin;
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Trauma>()
+            .init_resource::<CameraMode>()
+            .add_systems(Startup, spawn_player_views)
+            .add_systems(
+                PostUpdate,
+                (
+                    choose_mode,
+                    layout_views,
+                    add_trauma,
+                    zoom_to_crowd,
+                    follow_player,
+                )
+                    .chain()
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+// Idle until the screen splits, the main camera is player one's view.
+fn spawn_player_views(mut cmds: Commands) {
+    for slot in 1..MAX_PLAYERS {
+        cmds.spawn((
+            Camera2dBundle {
+                camera: Camera {
+                    order: slot as isize,
+                    is_active: false,
+                    ..default()
+                },
+                ..default()
+            },
+            CameraRig::default(),
+            PlayerView(slot),
+        ));
+    }
+}
+
+fn choose_mode(
+    field: Res<PlayField>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    players: Query<&Transform, With<Player>>,
+    mut mode: ResMut<CameraMode>,
+) {
+    let Some((_, visible)) = view::field_view(&field, &windows) else {
+        return;
+    };
+    let points: Vec<Vec2> = players.iter().map(|t| t.translation.xy()).collect();
+    let zoom = spread(&points, FIT_MARGIN).map_or(1.0, |r| fit_zoom(r, visible));
+    let next = match *mode {
+        _ if points.len() < 2 => CameraMode::Shared,
+        CameraMode::Shared if zoom > SPLIT_ZOOM => CameraMode::Split,
+        CameraMode::Split if zoom < MERGE_ZOOM => CameraMode::Shared,
+        current => current,
+    };
+    if *mode != next {
+        info!("camera {:?}", next);
+        *mode = next;
+    }
+}
+
+// Gives every view that is in use a pane of the field, keeping the same world
+// units per pixel as the shared view so splitting doesn't change the scale.
+fn layout_views(
+    mode: Res<CameraMode>,
+    field: Res<PlayField>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    players: Query<&Player>,
+    mut cameras: Query<(
+        &PlayerView,
+        &mut Camera,
+        &mut OrthographicProjection,
+        &mut CameraRig,
+    )>,
+) {
+    let Some((base, visible)) = view::field_view(&field, &windows) else {
+        return;
+    };
+    // The main camera always keeps a pane, it also carries the shared HUD.
+    let mut slots = vec![0];
+    if *mode == CameraMode::Split {
+        slots.extend(players.iter().map(|p| p.slot).filter(|s| *s != 0));
+        slots.sort();
+        slots.dedup();
+    }
+    let panes = panes(base, slots.len());
+    let units = visible / base.size().max(UVec2::ONE).as_vec2();
+    let main = cameras
+        .iter()
+        .find(|(view, ..)| view.0 == 0)
+        .map(|(.., rig)| (rig.focus, rig.zoom));
+    for (view, mut camera, mut projection, mut rig) in cameras.iter_mut() {
+        let pane = slots.iter().position(|s| *s == view.0).map(|i| panes[i]);
+        let active = pane.is_some();
+        if camera.is_active != active {
+            camera.is_active = active;
+            // New views start where the shared one was, then drift to their player.
+            if let (true, Some((focus, zoom))) = (active, main) {
+                rig.focus = focus;
+                rig.zoom = zoom;
+            }
+        }
+        let Some(pane) = pane else {
+            continue;
+        };
+        let placed = camera.viewport.as_ref().map(|v| {
+            URect::from_corners(v.physical_position, v.physical_position + v.physical_size)
+        });
+        if placed != Some(pane) {
+            camera.viewport = Some(Viewport {
+                physical_position: pane.min,
+                physical_size: pane.size(),
+                ..default()
+            });
+        }
+        let size = pane.size().as_vec2() * units;
+        let fitted = matches!(
+            projection.scaling_mode,
+            ScalingMode::Fixed { width, height } if width == size.x && height == size.y
+        );
+        if !fitted {
+            projection.scaling_mode = ScalingMode::Fixed {
+                width: size.x,
+                height: size.y,
+            };
+        }
+    }
+}
+
+// Where each view should look: its own player when split, the middle of
+// everyone on the shared main camera.
+fn view_targets(mode: CameraMode, view: PlayerView, players: &[(usize, Vec2)]) -> Vec<Vec2> {
+    players
+        .iter()
+        .filter(|(slot, _)| mode == CameraMode::Shared || *slot == view.0)
+        .map(|(_, p)| *p)
+        .collect()
+}
+
+fn add_trauma(mut events: EventReader<Trauma>, mut rigs: Query<&mut CameraRig>) {
+    for Trauma(amount) in events.read() {
+        for mut rig in rigs.iter_mut() {
+            rig.trauma = (rig.trauma + amount).clamp(0.0, 1.0);
+        }
+    }
+}
+
+fn zoom_to_crowd(
+    time: Res<Time>,
+    mode: Res<CameraMode>,
+    field: Res<PlayField>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    player: Query<(&Player, &Transform)>,
+    enemies: Query<&Transform, Or<(With<Rock>, With<Ship>)>>,
+    mut rigs: Query<(
+        &PlayerView,
+        &Camera,
+        &mut CameraRig,
+        &mut OrthographicProjection,
+    )>,
+) {
+    let players: Vec<(usize, Vec2)> = player
+        .iter()
+        .map(|(p, t)| (p.slot, t.translation.xy()))
+        .collect();
+    let visible = view::field_view(&field, &windows).map(|(_, v)| v);
+    for (view, camera, mut rig, mut projection) in rigs.iter_mut() {
+        if !camera.is_active {
+            continue;
+        }
+        let targets = view_targets(*mode, *view, &players);
+        if targets.is_empty() {
+            continue;
+        }
+        let near = enemies
+            .iter()
+            .filter(|t| {
+                let p = t.translation.xy();
+                targets.iter().any(|t| t.distance(p) < rig.crowd_radius)
+            })
+            .count();
+        let crowd = near.saturating_sub(rig.crowd_threshold) as f32;
+        let mut target = (1.0 + crowd * rig.zoom_per_enemy).min(rig.max_zoom);
+        // The shared view also zooms out until everyone fits.
+        if let (CameraMode::Shared, Some(rect), Some(visible)) =
+            (*mode, spread(&targets, FIT_MARGIN), visible)
+        {
+            target = target.max(fit_zoom(rect, visible).min(SPLIT_ZOOM));
+        }
+        let step = rig.zoom_speed * time.delta_seconds();
+        rig.zoom += (target - rig.zoom).clamp(-step, step);
+        projection.scale = rig.zoom;
+    }
+}
+
+pub fn follow_player(
+    time: Res<Time>,
+    mode: Res<CameraMode>,
+    play: Res<PlayArea>,
+    player: Query<(&Player, &Transform), Without<CameraRig>>,
+    targets: Query<&GlobalTransform, Without<CameraRig>>,
+    mut rigs: Query<(
+        &PlayerView,
+        &Camera,
+        &mut CameraRig,
+        &mut Transform,
+        &OrthographicProjection,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    let players: Vec<(usize, Vec2)> = player
+        .iter()
+        .map(|(p, t)| (p.slot, t.translation.xy()))
+        .collect();
+    for (view, camera, mut rig, mut transform, projection) in rigs.iter_mut() {
+        if !camera.is_active {
+            continue;
+        }
+        let center = spread(&view_targets(*mode, *view, &players), 0.0).map(|r| r.center());
+        // Snapped rather than smoothed so it also works while time is paused.
+        if let Some(target) = rig.follow.and_then(|e| targets.get(e).ok()) {
+            rig.focus = target.translation().xy();
+        } else if let Some(center) = center {
+            let desired = rig.desired(center);
+            let t = 1.0 - (-rig.smoothing * dt).exp();
+            rig.focus = rig.focus.lerp(desired, t);
+        }
+        if let Some(bounds) = play.regions.bounds() {
+            rig.focus = clamp_view(rig.focus, projection.area.half_size(), bounds);
+        }
+        rig.elapsed += dt;
+        rig.trauma = (rig.trauma - rig.trauma_decay * dt).max(0.0);
+        // Squared so small hits barely move the camera and big ones shake hard.
+        let shake = rig.trauma * rig.trauma;
+        let offset = Vec2::new(rig.noise(1.0), rig.noise(2.0)) * rig.max_offset * shake;
+        transform.translation.x = rig.focus.x + offset.x;
+        transform.translation.y = rig.focus.y + offset.y;
+        transform.rotation = Quat::from_rotation_z(rig.max_roll * shake * rig.noise(3.0));
+    }
+}
@@ -0,0 +1,571 @@
+use std::{collections::VecDeque, fmt};
+
+use bevy::{
+    input::{keyboard::KeyboardInput, InputSystem},
+    prelude::*,
+    window::ReceivedCharacter,
+};
+use rand::thread_rng;
+
+use crate::{
+    areas::SpawnArea,
+    debug::{DebugLayers, Layer},
+    inspector::Inspector,
+    pool::{EntityPool, GameAssets},
+    progress::{self, Wave},
+    Health, Invulnerable, Player, Spawner, MAX_ROCK_SIZE,
+};
+
+const LOG_LINES: usize = 12;
+const HISTORY: usize = 32;
+// Past this every spawner has long hit its fastest rate.
+const MAX_WAVE: u32 = 200;
+const MAX_TIMESCALE: f32 = 100.0;
+
+// First words and the words allowed after them, used for completion.
+const WORDS: [(&str, &[&str]); 9] = [
+    ("spawn", &["rock", "ship"]),
+    ("teleport", &[]),
+    ("god", &[]),
+    ("health", &[]),
+    ("wave", &[]),
+    ("timescale", &[]),
+    ("area", &["show", "hide"]),
+    ("help", &[]),
+    ("clear", &[]),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    SpawnRock { size: f32, at: Vec2 },
+    SpawnShip { at: Option<Vec2> },
+    // Moves the inspector's selection, or player one when nothing is selected.
+    Teleport(Vec2),
+    God,
+    Health(i32),
+    Wave(u32),
+    TimeScale(f32),
+    Area(bool),
+    Help,
+    Clear,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConsoleError {
+    Unknown(String),
+    Missing(&'static str),
+    BadNumber(String),
+}
+impl fmt::Display for ConsoleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsoleError::Unknown(word) => write!(f, "unknown command {word}, try help"),
+            ConsoleError::Missing(what) => write!(f, "missing {what}"),
+            ConsoleError::BadNumber(word) => write!(f, "{word} is not a number"),
+        }
+    }
+}
+impl std::error::Error for ConsoleError {}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, ConsoleError> {
+        let mut words = line.split_whitespace();
+        let number = |word: Option<&str>, what: &'static str| -> Result<f32, ConsoleError> {
+            let word = word.ok_or(ConsoleError::Missing(what))?;
+            word.parse()
+                .ok()
+                .filter(|n: &f32| n.is_finite())
+                .ok_or_else(|| ConsoleError::BadNumber(word.to_string()))
+        };
+        let keyword = words.next().unwrap_or_default();
+        Ok(match keyword {
+            "spawn" => match words.next() {
+                Some("rock") => {
+                    let word = words.next();
+                    let size = number(word, "size")?;
+                    if size <= 0.0 || size > MAX_ROCK_SIZE {
+                        return Err(ConsoleError::BadNumber(word.unwrap_or_default().into()));
+                    }
+                    Command::SpawnRock {
+                        size,
+                        at: Vec2::new(number(words.next(), "x")?, number(words.next(), "y")?),
+                    }
+                }
+                Some("ship") => Command::SpawnShip {
+                    at: match words.next() {
+                        Some(x) => {
+                            Some(Vec2::new(number(Some(x), "x")?, number(words.next(), "y")?))
+                        }
+                        None => None,
+                    },
+                },
+                Some(other) => return Err(ConsoleError::Unknown(format!("spawn {other}"))),
+                None => return Err(ConsoleError::Missing("rock or ship")),
+            },
+            "teleport" => Command::Teleport(Vec2::new(
+                number(words.next(), "x")?,
+                number(words.next(), "y")?,
+            )),
+            "god" => Command::God,
+            "health" => Command::Health(number(words.next(), "health")? as i32),
+            "wave" => {
+                Command::Wave(number(words.next(), "wave")?.clamp(1.0, MAX_WAVE as f32) as u32)
+            }
+            "timescale" => {
+                Command::TimeScale(number(words.next(), "scale")?.clamp(0.0, MAX_TIMESCALE))
+            }
+            "area" => match words.next() {
+                Some("show") => Command::Area(true),
+                Some("hide") => Command::Area(false),
+                _ => return Err(ConsoleError::Missing("show or hide")),
+            },
+            "help" => Command::Help,
+            "clear" => Command::Clear,
+            other => return Err(ConsoleError::Unknown(other.to_string())),
+        })
+    }
+}
+
+// Completes the last word as far as all candidates agree, returning the
+// candidates when more than one is left.
+pub fn complete(line: &str) -> (String, Vec<&'static str>) {
+    let mut words: Vec<&str> = line.split_whitespace().collect();
+    if line.is_empty() || line.ends_with(' ') {
+        words.push("");
+    }
+    let candidates: Vec<&'static str> = match words.as_slice() {
+        [_] => WORDS.iter().map(|(w, _)| *w).collect(),
+        [first, _] => WORDS
+            .iter()
+            .find(|(w, _)| w == first)
+            .map_or(Vec::new(), |(_, next)| next.to_vec()),
+        _ => Vec::new(),
+    };
+    let last = words.last().copied().unwrap_or_default();
+    let matches: Vec<&'static str> = candidates
+        .into_iter()
+        .filter(|c| c.starts_with(last))
+        .collect();
+    let Some(first) = matches.first() else {
+        return (line.to_string(), matches);
+    };
+    let common = matches.iter().fold(first.to_string(), |prefix, m| {
+        prefix
+            .chars()
+            .zip(m.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a)
+            .collect()
+    });
+    let head = &line[..line.len() - last.len()];
+    let mut completed = format!("{head}{common}");
+    if matches.len() == 1 {
+        completed.push(' ');
+        return (completed, Vec::new());
+    }
+    (completed, matches)
+}
+
+#[derive(Resource, Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    history: Vec<String>,
+    // Position while browsing the history with the arrow keys.
+    browsing: Option<usize>,
+    log: VecDeque<String>,
+}
+impl Console {
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.log.push_back(line.into());
+        while self.log.len() > LOG_LINES {
+            self.log.pop_front();
+        }
+    }
+}
+
+#[derive(Event, Debug, Clone)]
+struct RunCommand(Command);
+
+#[derive(Component)]
+struct ConsolePanel;
+#[derive(Component)]
+struct ConsoleText;
+
+pub struct ConsolePlugin;
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .add_event::<RunCommand>()
+            .add_systems(Startup, create_panel)
+            // Before anything else sees the keyboard, so typing doesn't move the ship.
+            .add_systems(
+                PreUpdate,
+                console_input
+                    .after(InputSystem)
+                    .before(crate::players::read_input),
+            )
+            .add_systems(Update, (run_commands, update_panel.after(run_commands)));
+    }
+}
+
+fn console_input(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut keys: EventReader<KeyboardInput>,
+    mut chars: EventReader<ReceivedCharacter>,
+    mut console: ResMut<Console>,
+    mut run: EventWriter<RunCommand>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Backquote) {
+        console.open = !console.open;
+        keyboard_input.reset_all();
+        keys.clear();
+        chars.clear();
+        return;
+    }
+    if !console.open {
+        keys.clear();
+        chars.clear();
+        return;
+    }
+    for c in chars.read() {
+        let text: String = c.char.chars().filter(|c| !c.is_control()).collect();
+        console.input.push_str(&text);
+    }
+    // Key events rather than `just_pressed` so held keys repeat.
+    for key in keys.read() {
+        if !key.state.is_pressed() {
+            continue;
+        }
+        match key.key_code {
+            KeyCode::Backspace => {
+                console.input.pop();
+            }
+            KeyCode::Escape => console.open = false,
+            KeyCode::Tab => {
+                let (completed, options) = complete(&console.input);
+                console.input = completed;
+                if !options.is_empty() {
+                    console.print(options.join(" "));
+                }
+            }
+            KeyCode::ArrowUp | KeyCode::ArrowDown => {
+                let len = console.history.len();
+                if len == 0 {
+                    continue;
+                }
+                let i = match (key.key_code, console.browsing) {
+                    (KeyCode::ArrowUp, None) => len - 1,
+                    (KeyCode::ArrowUp, Some(i)) => i.saturating_sub(1),
+                    (_, Some(i)) if i + 1 < len => i + 1,
+                    _ => {
+                        console.browsing = None;
+                        console.input.clear();
+                        continue;
+                    }
+                };
+                console.browsing = Some(i);
+                console.input = console.history[i].clone();
+            }
+            KeyCode::Enter | KeyCode::NumpadEnter => {
+                let line = std::mem::take(&mut console.input).trim().to_string();
+                console.browsing = None;
+                if line.is_empty() {
+                    continue;
+                }
+                console.print(format!("> {line}"));
+                if console.history.last() != Some(&line) {
+                    console.history.push(line.clone());
+                }
+                if console.history.len() > HISTORY {
+                    console.history.remove(0);
+                }
+                match Command::parse(&line) {
+                    Ok(command) => {
+                        run.send(RunCommand(command));
+                    }
+                    Err(e) => console.print(e.to_string()),
+                }
+            }
+            _ => {}
+        }
+    }
+    keyboard_input.reset_all();
+}
+
+fn run_commands(
+    mut cmds: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut pool: ResMut<EntityPool>,
+    assets: Res<GameAssets>,
+    spawn: Res<SpawnArea>,
+    mut events: EventReader<RunCommand>,
+    mut console: ResMut<Console>,
+    mut players: Query<(Entity, &mut Health, Has<Invulnerable>), With<Player>>,
+    mut spawners: Query<(&mut Spawner, &mut Transform)>,
+    mut movable: Query<(Entity, &mut Transform, Option<&Player>), Without<Spawner>>,
+    inspector: Option<Res<Inspector>>,
+    mut wave: ResMut<Wave>,
+    mut time: ResMut<Time<Virtual>>,
+    mut layers: ResMut<DebugLayers>,
+) {
+    for RunCommand(command) in events.read() {
+        match command {
+            Command::SpawnRock { size, at } => {
+                // Rocks fly towards a rock spawner, like spawned ones do.
+                let target = spawners
+                    .iter()
+                    .find(|(s, _)| matches!(**s, Spawner::Rock { .. }))
+                    .map_or(Vec2::ZERO, |(_, t)| t.translation.xy());
+                let rock_meshes = crate::rock_meshes(&mut meshes, &[*size]);
+                crate::create_rock(
+                    &mut cmds,
+                    &mut pool,
+                    &assets,
+                    &mut thread_rng(),
+                    &[*size],
+                    &rock_meshes,
+                    *at,
+                    target,
+                );
+                console.print(format!("rock {} at {} {}", size, at.x, at.y));
+            }
+            Command::SpawnShip { at } => {
+                let Some(at) = at.or_else(|| spawn.regions.sample(&mut thread_rng())) else {
+                    console.print("no spawn area to place the ship in");
+                    continue;
+                };
+                crate::create_ship(&mut cmds, &mut pool, &assets, at);
+                console.print(format!("ship at {:.0} {:.0}", at.x, at.y));
+            }
+            Command::Teleport(to) => {
+                let target = inspector.as_ref().and_then(|i| i.selected()).or_else(|| {
+                    movable
+                        .iter()
+                        .find(|(.., p)| p.is_some_and(|p| p.slot == 0))
+                        .map(|(e, ..)| e)
+                });
+                let transform = target.and_then(|e| {
+                    movable
+                        .get_mut(e)
+                        .map(|(_, t, _)| t)
+                        .or_else(|_| spawners.get_mut(e).map(|(_, t)| t))
+                        .ok()
+                });
+                let Some(mut transform) = transform else {
+                    console.print("nothing to teleport");
+                    continue;
+                };
+                transform.translation.x = to.x;
+                transform.translation.y = to.y;
+                console.print(format!("teleported to {} {}", to.x, to.y));
+            }
+            Command::God => {
+                for (e, _, god) in players.iter() {
+                    if god {
+                        cmds.entity(e).remove::<Invulnerable>();
+                    } else {
+                        cmds.entity(e).insert(Invulnerable);
+                    }
+                    console.print(format!("god mode {}", if god { "off" } else { "on" }));
+                }
+            }
+            Command::Health(n) => {
+                for (_, mut health, _) in players.iter_mut() {
+                    health.0 = *n;
+                }
+                console.print(format!("health {n}"));
+            }
+            Command::Wave(n) => {
+                let steps = (*n as i64 - wave.number as i64)
+                    .clamp(-(MAX_WAVE as i64), MAX_WAVE as i64) as i32;
+                for (mut spawner, _) in spawners.iter_mut() {
+                    progress::speed_up(&mut spawner, steps);
+                }
+                wave.number = *n;
+                wave.timer.reset();
+                console.print(format!("wave {n}"));
+            }
+            Command::TimeScale(scale) => {
+                time.set_relative_speed(*scale);
+                console.print(format!("timescale {scale}"));
+            }
+            Command::Area(show) => {
+                layers.set(Layer::Areas, *show);
+                console.print(format!("areas {}", if *show { "shown" } else { "hidden" }));
+            }
+            Command::Help => {
+                console.print("spawn rock <size> <x> <y>, spawn ship [x y], teleport <x> <y>");
+                console.print("god, health <n>, wave <n>, timescale <f>, area show|hide, clear");
+            }
+            Command::Clear => console.log.clear(),
+        }
+    }
+}
+
+fn create_panel(mut cmds: Commands) {
+    cmds.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(0.0),
+                top: Val::Px(0.0),
+                width: Val::Percent(100.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            background_color: Color::rgba(0.05, 0.05, 0.05, 0.85).into(),
+            visibility: Visibility::Hidden,
+            z_index: ZIndex::Global(10),
+            ..default()
+        },
+        ConsolePanel,
+    ))
+    .with_children(|p| {
+        p.spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 16.0,
+                    ..default()
+                },
+            ),
+            ConsoleText,
+        ));
+    });
+}
+
+fn update_panel(
+    console: Res<Console>,
+    mut panel: Query<&mut Visibility, With<ConsolePanel>>,
+    mut text: Query<&mut Text, With<ConsoleText>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+    for mut v in panel.iter_mut() {
+        *v = if console.open {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+    for mut t in text.iter_mut() {
+        let mut value: String = console.log.iter().map(|l| format!("{l}\n")).collect();
+        value.push_str(&format!("> {}_", console.input));
+        t.sections[0].value = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(
+            Command::parse("spawn rock 20 -5 7.5"),
+            Ok(Command::SpawnRock {
+                size: 20.0,
+                at: Vec2::new(-5.0, 7.5)
+            })
+        );
+        assert_eq!(
+            Command::parse("spawn ship"),
+            Ok(Command::SpawnShip { at: None })
+        );
+        assert_eq!(
+            Command::parse("  spawn   ship 1 2 "),
+            Ok(Command::SpawnShip {
+                at: Some(Vec2::new(1.0, 2.0))
+            })
+        );
+        assert_eq!(
+            Command::parse("teleport -100 50"),
+            Ok(Command::Teleport(Vec2::new(-100.0, 50.0)))
+        );
+        assert_eq!(Command::parse("health 40"), Ok(Command::Health(40)));
+        assert_eq!(Command::parse("wave 3"), Ok(Command::Wave(3)));
+        assert_eq!(Command::parse("timescale 0.5"), Ok(Command::TimeScale(0.5)));
+        assert_eq!(Command::parse("area hide"), Ok(Command::Area(false)));
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert_eq!(
+            Command::parse("jump"),
+            Err(ConsoleError::Unknown("jump".into()))
+        );
+        assert_eq!(
+            Command::parse("spawn tree"),
+            Err(ConsoleError::Unknown("spawn tree".into()))
+        );
+        assert_eq!(
+            Command::parse("spawn rock 20 1"),
+            Err(ConsoleError::Missing("y"))
+        );
+        assert_eq!(
+            Command::parse("teleport 5"),
+            Err(ConsoleError::Missing("y"))
+        );
+        assert_eq!(
+            Command::parse("health lots"),
+            Err(ConsoleError::BadNumber("lots".into()))
+        );
+        assert_eq!(
+            Command::parse("area"),
+            Err(ConsoleError::Missing("show or hide"))
+        );
+    }
+
+    #[test]
+    fn keeps_numbers_in_range() {
+        for word in ["inf", "-inf", "NaN", "1e40"] {
+            assert_eq!(
+                Command::parse(&format!("timescale {word}")),
+                Err(ConsoleError::BadNumber(word.into()))
+            );
+        }
+        assert_eq!(
+            Command::parse("timescale 1e30"),
+            Ok(Command::TimeScale(MAX_TIMESCALE))
+        );
+        assert_eq!(Command::parse("timescale -2"), Ok(Command::TimeScale(0.0)));
+        assert_eq!(
+            Command::parse("wave 4000000000"),
+            Ok(Command::Wave(MAX_WAVE))
+        );
+        assert_eq!(Command::parse("wave -3"), Ok(Command::Wave(1)));
+        for word in ["0", "-5", "161", "1e30"] {
+            assert_eq!(
+                Command::parse(&format!("spawn rock {word} 0 0")),
+                Err(ConsoleError::BadNumber(word.into()))
+            );
+        }
+        assert_eq!(
+            Command::parse("spawn rock 160 0 0"),
+            Ok(Command::SpawnRock {
+                size: MAX_ROCK_SIZE,
+                at: Vec2::ZERO
+            })
+        );
+    }
+
+    #[test]
+    fn completes_words() {
+        assert_eq!(complete("ti"), ("timescale ".to_string(), vec![]));
+        assert_eq!(complete("te"), ("teleport ".to_string(), vec![]));
+        assert_eq!(complete("spawn r"), ("spawn rock ".to_string(), vec![]));
+        // `health` and `help` agree up to `he`.
+        assert_eq!(complete("h"), ("he".to_string(), vec!["health", "help"]));
+        assert_eq!(
+            complete("spawn "),
+            ("spawn ".to_string(), vec!["rock", "ship"])
+        );
+        assert_eq!(complete("xyz"), ("xyz".to_string(), vec![]));
+        assert_eq!(
+            complete("spawn rock 1"),
+            ("spawn rock 1".to_string(), vec![])
+        );
+    }
+}
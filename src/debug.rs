@@ -0,0 +1,260 @@
+use bevy::{
+    diagnostic::{DiagnosticPath, DiagnosticsStore},
+    ecs::entity::EntityHashMap,
+    prelude::*,
+};
+use bevy_sepax2d::prelude::*;
+
+use crate::{
+    areas::{PlayArea, SafeArea, SpawnArea},
+    players::nearest,
+    pool,
+    sampling::SpawnSampler,
+    MoveTo, Player, Ship,
+};
+
+// Seconds of movement a velocity arrow covers.
+const VELOCITY_SCALE: f32 = 0.25;
+const STEERING_LENGTH: f32 = 40.0;
+// Seconds between asset count log lines.
+const ASSET_LOG_INTERVAL: f32 = 5.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layer {
+    Areas,
+    Targets,
+    Colliders,
+    Velocity,
+    SpawnHistory,
+    Steering,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 6] = [
+        Layer::Areas,
+        Layer::Targets,
+        Layer::Colliders,
+        Layer::Velocity,
+        Layer::SpawnHistory,
+        Layer::Steering,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Areas => "areas",
+            Layer::Targets => "targets",
+            Layer::Colliders => "colliders",
+            Layer::Velocity => "velocity",
+            Layer::SpawnHistory => "spawns",
+            Layer::Steering => "steering",
+        }
+    }
+    // Toggled with Ctrl and this key, Ctrl+0 flips them all.
+    pub fn key(&self) -> KeyCode {
+        match self {
+            Layer::Areas => KeyCode::Digit1,
+            Layer::Targets => KeyCode::Digit2,
+            Layer::Colliders => KeyCode::Digit3,
+            Layer::Velocity => KeyCode::Digit4,
+            Layer::SpawnHistory => KeyCode::Digit5,
+            Layer::Steering => KeyCode::Digit6,
+        }
+    }
+    fn index(&self) -> usize {
+        Self::ALL.iter().position(|l| l == self).unwrap_or(0)
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct DebugLayers {
+    enabled: [bool; Layer::ALL.len()],
+}
+impl Default for DebugLayers {
+    // What used to be drawn unconditionally.
+    fn default() -> Self {
+        let mut layers = Self {
+            enabled: [false; Layer::ALL.len()],
+        };
+        layers.set(Layer::Areas, true);
+        layers.set(Layer::Targets, true);
+        layers
+    }
+}
+impl DebugLayers {
+    pub fn is_on(&self, layer: Layer) -> bool {
+        self.enabled[layer.index()]
+    }
+    pub fn set(&mut self, layer: Layer, on: bool) {
+        self.enabled[layer.index()] = on;
+    }
+}
+
+fn layer_on(layer: Layer) -> impl Fn(Res<DebugLayers>) -> bool {
+    move |layers| layers.is_on(layer)
+}
+
+#[derive(Default, Reflect, GizmoConfigGroup)]
+pub struct DebugGizmos;
+
+pub struct DebugPlugin;
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugLayers>()
+            .init_gizmo_group::<DebugGizmos>()
+            .add_systems(
+                Update,
+                (
+                    toggle_layers,
+                    draw_areas.run_if(layer_on(Layer::Areas)),
+                    draw_targets.run_if(layer_on(Layer::Targets)),
+                    draw_colliders.run_if(layer_on(Layer::Colliders)),
+                    draw_velocity.run_if(layer_on(Layer::Velocity)),
+                    draw_spawn_history.run_if(layer_on(Layer::SpawnHistory)),
+                    draw_steering.run_if(layer_on(Layer::Steering)),
+                    log_asset_counts,
+                ),
+            );
+    }
+}
+
+// Asset counts should level off once the pools are warm, a steady climb
+// means something still allocates per entity.
+fn log_asset_counts(
+    time: Res<Time<Real>>,
+    diagnostics: Res<DiagnosticsStore>,
+    mut since: Local<f32>,
+) {
+    *since += time.delta_seconds();
+    if *since < ASSET_LOG_INTERVAL {
+        return;
+    }
+    *since = 0.0;
+    let count =
+        |path: &DiagnosticPath| diagnostics.get(path).and_then(|d| d.value()).unwrap_or(0.0);
+    info!(
+        "{} meshes, {} materials, {} pooled",
+        count(&pool::MESH_COUNT),
+        count(&pool::MATERIAL_COUNT),
+        count(&pool::POOLED_COUNT),
+    );
+}
+
+fn toggle_layers(keyboard_input: Res<ButtonInput<KeyCode>>, mut layers: ResMut<DebugLayers>) {
+    if !keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Digit0) {
+        let on = !Layer::ALL.iter().all(|l| layers.is_on(*l));
+        for layer in Layer::ALL {
+            layers.set(layer, on);
+        }
+        info!("debug layers {}", if on { "on" } else { "off" });
+        return;
+    }
+    for layer in Layer::ALL {
+        if keyboard_input.just_pressed(layer.key()) {
+            let on = !layers.is_on(layer);
+            layers.set(layer, on);
+            info!(
+                "debug layer {} {}",
+                layer.name(),
+                if on { "on" } else { "off" }
+            );
+        }
+    }
+}
+
+fn draw_areas(
+    mut gizmos: Gizmos<DebugGizmos>,
+    spawn: Res<SpawnArea>,
+    exlude: Res<SafeArea>,
+    play: Res<PlayArea>,
+) {
+    spawn.regions.draw(&mut gizmos, Color::RED);
+    exlude.regions.draw(&mut gizmos, Color::BLUE);
+    play.regions.draw(&mut gizmos, Color::WHITE);
+}
+
+fn draw_targets(
+    mut gizmos: Gizmos<DebugGizmos>,
+    query: Query<&GlobalTransform, With<Ship>>,
+    players: Query<&GlobalTransform, (With<Player>, Without<Ship>)>,
+) {
+    let players: Vec<Vec2> = players.iter().map(|t| t.translation().xy()).collect();
+    for t in query.iter() {
+        let from = t.translation().xy();
+        if let Some(to) = nearest(&players, from) {
+            gizmos.line_2d(from, to, Color::YELLOW);
+        }
+    }
+}
+
+fn draw_colliders(mut gizmos: Gizmos<DebugGizmos>, query: Query<(&Sepax, &GlobalTransform)>) {
+    for (sepax, t) in query.iter() {
+        let position = t.translation().xy();
+        match &sepax.convex {
+            Convex::Circle(circle) => {
+                gizmos.circle_2d(position, circle.radius, Color::LIME_GREEN);
+            }
+            // Everything in the game is a circle, mark anything else.
+            _ => gizmos.rect_2d(position, 0., Vec2::splat(8.0), Color::LIME_GREEN),
+        }
+    }
+}
+
+// Measured from the movement between frames so it covers every way things move.
+fn draw_velocity(
+    mut gizmos: Gizmos<DebugGizmos>,
+    time: Res<Time>,
+    mut last: Local<EntityHashMap<Vec2>>,
+    query: Query<(Entity, &GlobalTransform), With<Movable>>,
+) {
+    let dt = time.delta_seconds();
+    let mut positions = EntityHashMap::default();
+    for (e, t) in query.iter() {
+        let position = t.translation().xy();
+        if let Some(previous) = last.get(&e).filter(|_| dt > 0.) {
+            let velocity = (position - *previous) / dt;
+            if velocity.length_squared() > 1.0 {
+                gizmos.arrow_2d(position, position + velocity * VELOCITY_SCALE, Color::CYAN);
+            }
+        }
+        positions.insert(e, position);
+    }
+    *last = positions;
+}
+
+fn draw_spawn_history(mut gizmos: Gizmos<DebugGizmos>, sampler: Res<SpawnSampler>) {
+    let count = sampler.recent().count().max(1) as f32;
+    // Older spawns fade out.
+    for (i, p) in sampler.recent().enumerate() {
+        let alpha = (i + 1) as f32 / count;
+        gizmos.circle_2d(*p, sampler.min_spawn_distance, Color::ORANGE.with_a(alpha));
+        gizmos.circle_2d(*p, 3.0, Color::ORANGE.with_a(alpha));
+    }
+}
+
+// Heading in green, where the steering wants to go in red.
+fn draw_steering(
+    mut gizmos: Gizmos<DebugGizmos>,
+    query: Query<(&MoveTo, &GlobalTransform)>,
+    players: Query<&GlobalTransform, With<Player>>,
+) {
+    let players: Vec<Vec2> = players.iter().map(|t| t.translation().xy()).collect();
+    for (move_to, t) in query.iter() {
+        let position = t.translation().xy();
+        let target = match move_to {
+            MoveTo::Player { .. } => nearest(&players, position),
+            MoveTo::Point { x, y, .. } => Some(Vec2::new(*x, *y)),
+            MoveTo::None => None,
+        };
+        let Some(target) = target else {
+            continue;
+        };
+        let (_, rotation, _) = t.to_scale_rotation_translation();
+        let forward = (rotation * Vec3::Y).xy();
+        let desired = (target - position).normalize_or_zero();
+        gizmos.arrow_2d(position, position + forward * STEERING_LENGTH, Color::GREEN);
+        gizmos.arrow_2d(position, position + desired * STEERING_LENGTH, Color::RED);
+    }
+}
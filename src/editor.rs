@@ -0,0 +1,673 @@
+use std::{fs, time::Duration};
+
+use bevy::{ecs::system::EntityCommands, prelude::*, window::PrimaryWindow};
+
+use crate::{
+    areas::{AreaShape, PlayArea, Regions, SafeArea, SpawnArea},
+    arena::{Arena, SpawnerDef, SpawnerKind, MAX_WEIGHT},
+    menu::GameState,
+    pool::GameAssets,
+    MainCamera, Spawner, MAX_ROCK_SIZE,
+};
+
+const PICK_RADIUS: f32 = 25.0;
+const EDGE_GRAB: f32 = 8.0;
+const MIN_AREA_SIZE: f32 = 40.0;
+const TIMER_STEP: f32 = 0.5;
+const SIZE_STEP: f32 = 5.0;
+const WEIGHT_STEP: f32 = 0.5;
+
+#[derive(States, Default, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum EditorState {
+    #[default]
+    Off,
+    On,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AreaKind {
+    Spawn,
+    Safe,
+    Play,
+}
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Edge {
+    Left,
+    Right,
+    Bottom,
+    Top,
+}
+// The part of an area region being dragged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Grip {
+    Edge(Edge),
+    Rim,
+    Vertex(usize),
+}
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Drag {
+    Spawner(Entity, Vec2),
+    Region(AreaKind, usize, Grip),
+}
+
+#[derive(Resource)]
+struct EditorSelection {
+    spawner: Option<Entity>,
+    place: SpawnerKind,
+    drag: Option<Drag>,
+    // Set whenever the side panel needs to be rebuilt.
+    dirty: bool,
+    // Areas dragged since the editor started, saved even if the arena had none.
+    edited: Vec<AreaKind>,
+}
+impl Default for EditorSelection {
+    fn default() -> Self {
+        Self {
+            spawner: None,
+            place: SpawnerKind::Rock,
+            drag: None,
+            dirty: true,
+            edited: Vec::new(),
+        }
+    }
+}
+
+#[derive(Component)]
+struct EditorPanel;
+#[derive(Component)]
+struct EditorPanelBody;
+#[derive(Component, Clone, Copy)]
+enum EditorButton {
+    Place(SpawnerKind),
+    TimerDown,
+    TimerUp,
+    SizeDown(usize),
+    SizeUp(usize),
+    WeightDown(usize),
+    WeightUp(usize),
+    AddSize,
+    RemoveSize,
+    Delete,
+    Save,
+}
+
+pub struct EditorPlugin;
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<EditorState>()
+            .init_resource::<EditorSelection>()
+            .add_event::<SaveArena>()
+            .add_systems(Startup, create_panel)
+            .add_systems(Update, toggle_editor.run_if(in_state(GameState::Playing)))
+            .add_systems(OnEnter(EditorState::On), enter_editor)
+            .add_systems(OnExit(EditorState::On), exit_editor)
+            .add_systems(
+                Update,
+                (
+                    pick,
+                    drag.after(pick),
+                    delete_spawner,
+                    panel_buttons,
+                    save_shortcut,
+                    save_arena.after(save_shortcut).after(panel_buttons),
+                    refresh_panel.after(panel_buttons).after(pick),
+                    draw_selection,
+                )
+                    .run_if(in_state(EditorState::On)),
+            );
+    }
+}
+
+fn toggle_editor(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    state: Res<State<EditorState>>,
+    mut next: ResMut<NextState<EditorState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F1) {
+        next.set(match state.get() {
+            EditorState::Off => EditorState::On,
+            EditorState::On => EditorState::Off,
+        });
+    }
+}
+
+// Gameplay runs on virtual time, so pausing it freezes spawners and movement
+// while the level is being edited.
+fn enter_editor(
+    mut time: ResMut<Time<Virtual>>,
+    mut panel: Query<&mut Visibility, With<EditorPanel>>,
+    mut selection: ResMut<EditorSelection>,
+) {
+    time.pause();
+    for mut v in panel.iter_mut() {
+        *v = Visibility::Visible;
+    }
+    selection.dirty = true;
+}
+fn exit_editor(
+    state: Res<State<GameState>>,
+    mut time: ResMut<Time<Virtual>>,
+    mut panel: Query<&mut Visibility, With<EditorPanel>>,
+    mut selection: ResMut<EditorSelection>,
+) {
+    // A menu opened over the editor keeps the game paused.
+    if *state.get() == GameState::Playing {
+        time.unpause();
+    }
+    for mut v in panel.iter_mut() {
+        *v = Visibility::Hidden;
+    }
+    selection.drag = None;
+}
+
+fn cursor_world(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    camera: &Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+) -> Option<Vec2> {
+    let (camera, transform) = camera.get_single().ok()?;
+    crate::view::cursor_world(windows.get_single().ok()?, camera, transform)
+}
+
+fn area_regions<'a>(
+    kind: AreaKind,
+    spawn: &'a mut SpawnArea,
+    safe: &'a mut SafeArea,
+    play: &'a mut PlayArea,
+) -> &'a mut Regions {
+    match kind {
+        AreaKind::Spawn => &mut spawn.regions,
+        AreaKind::Safe => &mut safe.regions,
+        AreaKind::Play => &mut play.regions,
+    }
+}
+
+fn grip_under(shape: &AreaShape, p: Vec2) -> Option<Grip> {
+    match shape {
+        AreaShape::Rect(rect) => edge_under(*rect, p).map(Grip::Edge),
+        AreaShape::Circle { center, radius } => {
+            ((center.distance(p) - radius).abs() < EDGE_GRAB).then_some(Grip::Rim)
+        }
+        AreaShape::Polygon(points) => points
+            .iter()
+            .position(|v| v.distance(p) < EDGE_GRAB)
+            .map(Grip::Vertex),
+    }
+}
+fn apply_grip(shape: &mut AreaShape, grip: Grip, cursor: Vec2) {
+    match (shape, grip) {
+        (AreaShape::Rect(rect), Grip::Edge(edge)) => match edge {
+            Edge::Left => rect.min.x = cursor.x.min(rect.max.x - MIN_AREA_SIZE),
+            Edge::Right => rect.max.x = cursor.x.max(rect.min.x + MIN_AREA_SIZE),
+            Edge::Bottom => rect.min.y = cursor.y.min(rect.max.y - MIN_AREA_SIZE),
+            Edge::Top => rect.max.y = cursor.y.max(rect.min.y + MIN_AREA_SIZE),
+        },
+        (AreaShape::Circle { center, radius }, Grip::Rim) => {
+            *radius = center.distance(cursor).max(MIN_AREA_SIZE / 2.)
+        }
+        (AreaShape::Polygon(points), Grip::Vertex(i)) => {
+            if let Some(v) = points.get_mut(i) {
+                *v = cursor;
+            }
+        }
+        _ => {}
+    }
+}
+
+fn edge_under(rect: Rect, p: Vec2) -> Option<Edge> {
+    let in_x = p.x > rect.min.x - EDGE_GRAB && p.x < rect.max.x + EDGE_GRAB;
+    let in_y = p.y > rect.min.y - EDGE_GRAB && p.y < rect.max.y + EDGE_GRAB;
+    if in_y && (p.x - rect.min.x).abs() < EDGE_GRAB {
+        Some(Edge::Left)
+    } else if in_y && (p.x - rect.max.x).abs() < EDGE_GRAB {
+        Some(Edge::Right)
+    } else if in_x && (p.y - rect.min.y).abs() < EDGE_GRAB {
+        Some(Edge::Bottom)
+    } else if in_x && (p.y - rect.max.y).abs() < EDGE_GRAB {
+        Some(Edge::Top)
+    } else {
+        None
+    }
+}
+
+fn pick(
+    mut cmds: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    assets: Res<GameAssets>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    ui: Query<&Interaction>,
+    spawners: Query<(Entity, &Transform), With<Spawner>>,
+    spawn: Res<SpawnArea>,
+    safe: Res<SafeArea>,
+    play: Res<PlayArea>,
+    mut selection: ResMut<EditorSelection>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) || ui.iter().any(|i| *i != Interaction::None) {
+        return;
+    }
+    let Some(cursor) = cursor_world(&windows, &camera) else {
+        return;
+    };
+    let hit = spawners
+        .iter()
+        .find(|(_, t)| t.translation.xy().distance(cursor) < PICK_RADIUS);
+    if let Some((e, t)) = hit {
+        selection.spawner = Some(e);
+        selection.drag = Some(Drag::Spawner(e, t.translation.xy() - cursor));
+        selection.dirty = true;
+        return;
+    }
+    // Smaller areas first, the safe area usually sits inside the others.
+    for (kind, regions) in [
+        (AreaKind::Safe, &safe.regions),
+        (AreaKind::Play, &play.regions),
+        (AreaKind::Spawn, &spawn.regions),
+    ] {
+        for (i, region) in regions.0.iter().enumerate() {
+            if let Some(grip) = grip_under(&region.shape, cursor) {
+                selection.drag = Some(Drag::Region(kind, i, grip));
+                return;
+            }
+        }
+    }
+    let def = SpawnerDef::new(selection.place, cursor);
+    let e = crate::arena::spawn_spawner(&mut cmds, &mut meshes, &assets, &def);
+    selection.spawner = Some(e);
+    selection.dirty = true;
+}
+
+fn drag(
+    mouse: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut spawners: Query<&mut Transform, With<Spawner>>,
+    mut spawn: ResMut<SpawnArea>,
+    mut safe: ResMut<SafeArea>,
+    mut play: ResMut<PlayArea>,
+    mut selection: ResMut<EditorSelection>,
+) {
+    let Some(current) = selection.drag else {
+        return;
+    };
+    if !mouse.pressed(MouseButton::Left) {
+        selection.drag = None;
+        return;
+    }
+    let Some(cursor) = cursor_world(&windows, &camera) else {
+        return;
+    };
+    match current {
+        Drag::Spawner(e, offset) => {
+            if let Ok(mut t) = spawners.get_mut(e) {
+                let p = cursor + offset;
+                t.translation.x = p.x;
+                t.translation.y = p.y;
+            }
+        }
+        Drag::Region(kind, i, grip) => {
+            let regions = area_regions(kind, &mut spawn, &mut safe, &mut play);
+            if let Some(region) = regions.0.get_mut(i) {
+                apply_grip(&mut region.shape, grip, cursor);
+                if !selection.edited.contains(&kind) {
+                    selection.edited.push(kind);
+                }
+            }
+        }
+    }
+}
+
+fn delete_spawner(
+    mut cmds: Commands,
+    mouse: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    spawners: Query<(Entity, &Transform), With<Spawner>>,
+    mut selection: ResMut<EditorSelection>,
+) {
+    if !mouse.just_pressed(MouseButton::Right) {
+        return;
+    }
+    let Some(cursor) = cursor_world(&windows, &camera) else {
+        return;
+    };
+    for (e, t) in spawners.iter() {
+        if t.translation.xy().distance(cursor) < PICK_RADIUS {
+            cmds.entity(e).despawn_recursive();
+            if selection.spawner == Some(e) {
+                selection.spawner = None;
+                selection.dirty = true;
+            }
+            return;
+        }
+    }
+}
+
+fn panel_buttons(
+    mut cmds: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    interactions: Query<(&Interaction, &EditorButton), Changed<Interaction>>,
+    mut spawners: Query<&mut Spawner>,
+    mut selection: ResMut<EditorSelection>,
+    mut save: EventWriter<SaveArena>,
+) {
+    for (interaction, button) in interactions.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        selection.dirty = true;
+        match *button {
+            EditorButton::Place(kind) => {
+                selection.place = kind;
+                continue;
+            }
+            EditorButton::Save => {
+                save.send(SaveArena);
+                continue;
+            }
+            EditorButton::Delete => {
+                if let Some(e) = selection.spawner.take() {
+                    cmds.entity(e).despawn_recursive();
+                }
+                continue;
+            }
+            _ => {}
+        }
+        let Some(mut spawner) = selection.spawner.and_then(|e| spawners.get_mut(e).ok()) else {
+            continue;
+        };
+        let (timer, sizes, table) = match spawner.as_mut() {
+            Spawner::Rock {
+                timer,
+                sizes,
+                meshes: rock_meshes,
+                ..
+            } => (timer, Some((sizes, rock_meshes)), None),
+            Spawner::Ship { timer } => (timer, None, None),
+            Spawner::PowerUp { timer, table } => (timer, None, Some(table)),
+        };
+        let step = |timer: &mut Timer, by: f32| {
+            let secs = (timer.duration().as_secs_f32() + by).max(TIMER_STEP);
+            timer.set_duration(Duration::from_secs_f32(secs));
+        };
+        match (*button, sizes, table) {
+            (EditorButton::TimerDown, ..) => step(timer, -TIMER_STEP),
+            (EditorButton::TimerUp, ..) => step(timer, TIMER_STEP),
+            (EditorButton::WeightDown(i), _, Some(table)) => {
+                table[i].weight = (table[i].weight - WEIGHT_STEP).max(0.0)
+            }
+            (EditorButton::WeightUp(i), _, Some(table)) => {
+                table[i].weight = (table[i].weight + WEIGHT_STEP).min(MAX_WEIGHT)
+            }
+            (button, Some((radii, rock_meshes)), _) => {
+                match button {
+                    EditorButton::SizeDown(i) => radii[i] = (radii[i] - SIZE_STEP).max(SIZE_STEP),
+                    EditorButton::SizeUp(i) => radii[i] = (radii[i] + SIZE_STEP).min(MAX_ROCK_SIZE),
+                    EditorButton::AddSize => radii.push(radii.last().copied().unwrap_or(10.0)),
+                    EditorButton::RemoveSize if radii.len() > 1 => {
+                        radii.pop();
+                    }
+                    _ => continue,
+                }
+                *rock_meshes = crate::rock_meshes(&mut meshes, radii);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Event)]
+struct SaveArena;
+
+fn save_shortcut(keyboard_input: Res<ButtonInput<KeyCode>>, mut save: EventWriter<SaveArena>) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if ctrl && keyboard_input.just_pressed(KeyCode::KeyS) {
+        save.send(SaveArena);
+    }
+}
+
+fn save_arena(
+    mut events: EventReader<SaveArena>,
+    arena: Option<ResMut<Arena>>,
+    spawners: Query<(&Spawner, &Transform)>,
+    spawn: Res<SpawnArea>,
+    safe: Res<SafeArea>,
+    play: Res<PlayArea>,
+    selection: Res<EditorSelection>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    let Some(mut arena) = arena else {
+        warn!("no arena loaded, nothing to save");
+        return;
+    };
+    // The fallback stands in for a file that failed to load. Like broken
+    // stats, that file is moved aside rather than lost to the save.
+    let path = Arena::path(&arena.name);
+    if path.exists() {
+        if let Err(e) = Arena::load(&arena.name) {
+            let aside = path.with_extension("broken");
+            warn!(
+                "arena {:?} doesn't load: {}, kept as {}",
+                arena.name,
+                e,
+                aside.display()
+            );
+            if let Err(e) = fs::rename(&path, &aside) {
+                error!("not saving arena {:?}: {}", arena.name, e);
+                return;
+            }
+        }
+    }
+    // Areas the file leaves out follow the window, see `view::fit_areas`.
+    let arena = &mut *arena;
+    for (kind, regions, current) in [
+        (AreaKind::Spawn, &mut arena.spawn, &spawn.regions),
+        (AreaKind::Safe, &mut arena.safe, &safe.regions),
+        (AreaKind::Play, &mut arena.play, &play.regions),
+    ] {
+        if !regions.0.is_empty() || selection.edited.contains(&kind) {
+            *regions = current.clone();
+        }
+    }
+    arena.spawners = spawners
+        .iter()
+        .map(|(s, t)| {
+            let (kind, timer) = match s {
+                Spawner::Rock { timer, .. } => (SpawnerKind::Rock, timer),
+                Spawner::Ship { timer } => (SpawnerKind::Ship, timer),
+                Spawner::PowerUp { timer, .. } => (SpawnerKind::PowerUp, timer),
+            };
+            let mut def = SpawnerDef::new(kind, t.translation.xy());
+            def.timer = timer.duration().as_secs_f32();
+            match s {
+                Spawner::Rock { sizes, .. } => def.sizes = sizes.clone(),
+                Spawner::PowerUp { table, .. } => def.table = table.clone(),
+                Spawner::Ship { .. } => {}
+            }
+            def
+        })
+        .collect();
+    match arena.save() {
+        Ok(path) => info!("saved arena to {}", path.display()),
+        Err(e) => error!("failed to save arena: {}", e),
+    }
+}
+
+fn create_panel(mut cmds: Commands) {
+    cmds.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(0.0),
+                top: Val::Px(0.0),
+                width: Val::Px(260.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(8.0)),
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            background_color: Color::rgba(0.1, 0.1, 0.12, 0.9).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        Interaction::default(),
+        EditorPanel,
+    ))
+    .with_children(|p| {
+        label(p, "Editor (F1)", 24.0);
+        label(p, "LMB place / drag, RMB delete", 14.0);
+        label(p, "Drag area edges, rims and corners", 14.0);
+        p.spawn(NodeBundle {
+            style: Style {
+                column_gap: Val::Px(4.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|p| {
+            button(p, "Rock", EditorButton::Place(SpawnerKind::Rock));
+            button(p, "Ship", EditorButton::Place(SpawnerKind::Ship));
+            button(p, "Power-up", EditorButton::Place(SpawnerKind::PowerUp));
+            button(p, "Save", EditorButton::Save);
+        });
+        p.spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                ..default()
+            },
+            EditorPanelBody,
+        ));
+    });
+}
+
+fn refresh_panel(
+    mut cmds: Commands,
+    mut selection: ResMut<EditorSelection>,
+    body: Query<Entity, With<EditorPanelBody>>,
+    spawners: Query<&Spawner>,
+) {
+    if !selection.dirty {
+        return;
+    }
+    selection.dirty = false;
+    let Ok(body) = body.get_single() else {
+        return;
+    };
+    cmds.entity(body).despawn_descendants();
+    let place = match selection.place {
+        SpawnerKind::Rock => "Placing: rock",
+        SpawnerKind::Ship => "Placing: ship",
+        SpawnerKind::PowerUp => "Placing: power-up",
+    };
+    let spawner = selection.spawner.and_then(|e| spawners.get(e).ok());
+    cmds.entity(body).with_children(|p| {
+        label(p, place, 16.0);
+        let Some(spawner) = spawner else {
+            label(p, "No spawner selected", 16.0);
+            return;
+        };
+        let (name, timer) = match spawner {
+            Spawner::Rock { timer, .. } => ("Rock spawner", timer),
+            Spawner::Ship { timer } => ("Ship spawner", timer),
+            Spawner::PowerUp { timer, .. } => ("Power-up spawner", timer),
+        };
+        label(p, name, 20.0);
+        row(p, format!("Timer {:.1}s", timer.duration().as_secs_f32())).with_children(|p| {
+            button(p, "-", EditorButton::TimerDown);
+            button(p, "+", EditorButton::TimerUp);
+        });
+        if let Spawner::Rock { sizes, .. } = spawner {
+            for (i, r) in sizes.iter().enumerate() {
+                row(p, format!("Size {}", r)).with_children(|p| {
+                    button(p, "-", EditorButton::SizeDown(i));
+                    button(p, "+", EditorButton::SizeUp(i));
+                });
+            }
+            p.spawn(NodeBundle::default()).with_children(|p| {
+                button(p, "Add size", EditorButton::AddSize);
+                button(p, "Remove size", EditorButton::RemoveSize);
+            });
+        }
+        if let Spawner::PowerUp { table, .. } = spawner {
+            for (i, e) in table.iter().enumerate() {
+                row(p, format!("{} x{}", e.kind.label(), e.weight)).with_children(|p| {
+                    button(p, "-", EditorButton::WeightDown(i));
+                    button(p, "+", EditorButton::WeightUp(i));
+                });
+            }
+        }
+        button(p, "Delete", EditorButton::Delete);
+    });
+}
+
+fn draw_selection(
+    mut gizmos: Gizmos,
+    selection: Res<EditorSelection>,
+    spawners: Query<&Transform, With<Spawner>>,
+) {
+    for t in spawners.iter() {
+        gizmos.circle_2d(t.translation.xy(), PICK_RADIUS, Color::GRAY);
+    }
+    if let Some(t) = selection.spawner.and_then(|e| spawners.get(e).ok()) {
+        gizmos.circle_2d(t.translation.xy(), PICK_RADIUS + 4.0, Color::YELLOW);
+    }
+}
+
+fn label(p: &mut ChildBuilder, text: &str, font_size: f32) {
+    p.spawn(TextBundle::from_section(
+        text,
+        TextStyle {
+            font_size,
+            ..default()
+        },
+    ));
+}
+fn row<'a>(p: &'a mut ChildBuilder, text: String) -> EntityCommands<'a> {
+    let mut row = p.spawn(NodeBundle {
+        style: Style {
+            column_gap: Val::Px(4.0),
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        ..default()
+    });
+    row.with_children(|p| {
+        p.spawn(TextBundle::from_section(
+            text,
+            TextStyle {
+                font_size: 16.0,
+                ..default()
+            },
+        ));
+    });
+    row
+}
+fn button(p: &mut ChildBuilder, text: &str, action: EditorButton) {
+    p.spawn((
+        ButtonBundle {
+            style: Style {
+                padding: UiRect::axes(Val::Px(8.0), Val::Px(2.0)),
+                ..default()
+            },
+            background_color: Color::rgb(0.25, 0.25, 0.3).into(),
+            ..default()
+        },
+        action,
+    ))
+    .with_children(|p| {
+        p.spawn(TextBundle::from_section(
+            text,
+            TextStyle {
+                font_size: 16.0,
+                ..default()
+            },
+        ));
+    });
+}
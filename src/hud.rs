@@ -0,0 +1,468 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+
+use crate::{
+    camera::{CameraMode, PlayerView},
+    players::{self, LivesPool, PlayerSetup, MAX_PLAYERS},
+    progress::{Lives, OwnLives, Score, Wave},
+    settings::Settings,
+    stats::Unlocked,
+    Health, Player, PLAYER_HEALTH,
+};
+
+const BAR_SIZE: Vec2 = Vec2::new(200.0, 16.0);
+const FPS_INTERVAL: f32 = 0.5;
+const SECTION_MARGIN: f32 = 5.0;
+const SECTION_GAP: f32 = 12.0;
+const NOTICE_SECONDS: f32 = 3.0;
+
+// Anything with a countdown worth showing, e.g. active power-ups.
+#[derive(Component, Debug, Clone)]
+pub struct HudTimer {
+    pub label: String,
+    pub timer: Timer,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct HudSettings {
+    pub show_fps: bool,
+}
+impl Default for HudSettings {
+    fn default() -> Self {
+        Self {
+            show_fps: cfg!(feature = "debug"),
+        }
+    }
+}
+
+// One per player slot, hidden for slots nobody is playing.
+#[derive(Component)]
+struct PlayerSection(usize);
+#[derive(Component)]
+struct HealthFill(usize);
+// `None` is the shared pool.
+#[derive(Component)]
+struct LivesRow(Option<usize>);
+#[derive(Component)]
+enum HudText {
+    Score,
+    Wave,
+    Timers,
+    Fps,
+    Notice,
+}
+
+// Unlock messages waiting their turn, one is shown at a time.
+#[derive(Default)]
+struct Notices {
+    queue: VecDeque<String>,
+    shown: Option<Timer>,
+}
+
+pub struct HudPlugin;
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+            app.add_plugins(FrameTimeDiagnosticsPlugin);
+        }
+        app.init_resource::<HudSettings>()
+            .add_systems(Startup, create_hud)
+            .add_systems(
+                Update,
+                (
+                    update_sections,
+                    place_sections,
+                    update_health,
+                    update_lives,
+                    update_score,
+                    update_wave,
+                    update_timers,
+                    toggle_fps,
+                    update_fps.after(toggle_fps),
+                    show_notices,
+                ),
+            );
+    }
+}
+
+fn text(value: &str, font_size: f32, kind: HudText) -> (TextBundle, HudText) {
+    (
+        TextBundle::from_section(
+            value,
+            TextStyle {
+                font_size,
+                ..default()
+            },
+        ),
+        kind,
+    )
+}
+
+fn lives_row() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            column_gap: Val::Px(4.0),
+            ..default()
+        },
+        ..default()
+    }
+}
+
+// Position of a section while the screen is shared, along the bottom edge.
+// Split views each show their own section in the corner instead.
+fn section_style(slot: usize, mode: CameraMode) -> Style {
+    let left = match mode {
+        CameraMode::Shared => SECTION_MARGIN + slot as f32 * (BAR_SIZE.x + SECTION_GAP),
+        CameraMode::Split => SECTION_MARGIN,
+    };
+    Style {
+        position_type: PositionType::Absolute,
+        left: Val::Px(left),
+        bottom: Val::Px(SECTION_MARGIN),
+        flex_direction: FlexDirection::Column,
+        row_gap: Val::Px(4.0),
+        ..default()
+    }
+}
+
+// A root node of its own so it can be moved onto a player's split view.
+fn player_section(cmds: &mut Commands, slot: usize) {
+    cmds.spawn((
+        NodeBundle {
+            style: section_style(slot, CameraMode::Shared),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        PlayerSection(slot),
+    ))
+    .with_children(|p| {
+        p.spawn(TextBundle::from_section(
+            format!("P{}", slot + 1),
+            TextStyle {
+                font_size: 18.0,
+                ..default()
+            },
+        ));
+        p.spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(BAR_SIZE.x),
+                height: Val::Px(BAR_SIZE.y),
+                border: UiRect::all(Val::Px(2.0)),
+                ..default()
+            },
+            background_color: Color::rgb(0.15, 0.15, 0.15).into(),
+            border_color: Color::WHITE.into(),
+            ..default()
+        })
+        .with_children(|p| {
+            p.spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    background_color: Color::GREEN.into(),
+                    ..default()
+                },
+                HealthFill(slot),
+            ));
+        });
+        p.spawn((lives_row(), LivesRow(Some(slot))));
+    });
+}
+
+fn create_hud(mut cmds: Commands) {
+    cmds.spawn(NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            align_items: AlignItems::Start,
+            justify_content: JustifyContent::Start,
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::px(5.0, 5.0, 5.0, 5.0),
+            row_gap: Val::Px(4.0),
+            ..default()
+        },
+        ..default()
+    })
+    .with_children(|p| {
+        p.spawn((lives_row(), LivesRow(None)));
+        p.spawn(text("Score: 0", 32.0, HudText::Score));
+        p.spawn(text("Wave 1", 24.0, HudText::Wave));
+        p.spawn(text("", 18.0, HudText::Timers));
+    });
+    let (mut fps, kind) = text("", 18.0, HudText::Fps);
+    fps.style = Style {
+        position_type: PositionType::Absolute,
+        right: Val::Px(5.0),
+        top: Val::Px(5.0),
+        ..default()
+    };
+    cmds.spawn((fps, kind));
+    cmds.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            top: Val::Px(40.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        ..default()
+    })
+    .with_children(|p| {
+        p.spawn(text("", 24.0, HudText::Notice));
+    });
+    for slot in 0..MAX_PLAYERS {
+        player_section(&mut cmds, slot);
+    }
+}
+
+// Sections follow the menu's player count, the names take the player's colour.
+fn update_sections(
+    setup: Res<PlayerSetup>,
+    settings: Res<Settings>,
+    mut sections: Query<(&PlayerSection, &mut Visibility, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !setup.is_changed() && !settings.is_changed() {
+        return;
+    }
+    let colors = settings.palette.colors();
+    for (section, mut visibility, children) in sections.iter_mut() {
+        *visibility = if section.0 < setup.count {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        let mut name = texts.iter_many_mut(children.iter());
+        while let Some(mut text) = name.fetch_next() {
+            text.sections[0].style.color = players::color(section.0, &colors);
+        }
+    }
+}
+
+fn place_sections(
+    mut cmds: Commands,
+    mode: Res<CameraMode>,
+    views: Query<(Entity, &PlayerView, &Camera)>,
+    mut sections: Query<(Entity, &PlayerSection, &mut Style, Option<&TargetCamera>)>,
+) {
+    for (e, section, mut style, target) in sections.iter_mut() {
+        let view = match *mode {
+            CameraMode::Shared => None,
+            CameraMode::Split => views
+                .iter()
+                .find(|(_, v, c)| v.0 == section.0 && c.is_active)
+                .map(|(e, ..)| e),
+        };
+        // Without a view of its own the section stays on the default UI camera.
+        let mode = if view.is_some() {
+            CameraMode::Split
+        } else {
+            CameraMode::Shared
+        };
+        let placed = section_style(section.0, mode);
+        if *style != placed {
+            *style = placed;
+        }
+        match (view, target.map(|t| t.entity())) {
+            (Some(view), Some(current)) if view == current => {}
+            (Some(view), _) => {
+                cmds.entity(e).insert(TargetCamera(view));
+            }
+            (None, Some(_)) => {
+                cmds.entity(e).remove::<TargetCamera>();
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+fn update_health(
+    players: Query<(&Player, Ref<Health>)>,
+    mut removed: RemovedComponents<Player>,
+    mut fill: Query<(&HealthFill, &mut Style, &mut BackgroundColor)>,
+) {
+    let removed = removed.read().count() > 0;
+    if !removed && !players.iter().any(|(_, h)| h.is_changed()) {
+        return;
+    }
+    for (slot, mut style, mut color) in fill.iter_mut() {
+        // Players out of lives are gone and show an empty bar.
+        let health = players
+            .iter()
+            .find(|(p, _)| p.slot == slot.0)
+            .map_or(0, |(_, h)| h.0);
+        let fraction = (health as f32 / PLAYER_HEALTH as f32).clamp(0.0, 1.0);
+        style.width = Val::Percent(fraction * 100.0);
+        // Green at full health fading through yellow to red.
+        *color = Color::rgb(
+            (2.0 - fraction * 2.0).min(1.0),
+            (fraction * 2.0).min(1.0),
+            0.1,
+        )
+        .into();
+    }
+}
+
+fn update_lives(
+    mut cmds: Commands,
+    lives: Res<Lives>,
+    setup: Res<PlayerSetup>,
+    settings: Res<Settings>,
+    players: Query<(&Player, Ref<OwnLives>)>,
+    mut removed: RemovedComponents<Player>,
+    rows: Query<(Entity, &LivesRow)>,
+) {
+    let removed = removed.read().count() > 0;
+    let own_changed = players.iter().any(|(_, l)| l.is_changed());
+    if !lives.is_changed()
+        && !setup.is_changed()
+        && !settings.is_changed()
+        && !own_changed
+        && !removed
+    {
+        return;
+    }
+    let colors = settings.palette.colors();
+    for (row, slot) in rows.iter() {
+        let (count, color) = match (slot.0, setup.lives) {
+            (None, LivesPool::Shared) => (lives.0, colors.player),
+            (Some(slot), LivesPool::Split) => (
+                players
+                    .iter()
+                    .find(|(p, _)| p.slot == slot)
+                    .map_or(0, |(_, l)| l.0),
+                players::color(slot, &colors),
+            ),
+            _ => (0, colors.player),
+        };
+        cmds.entity(row).despawn_descendants().with_children(|p| {
+            for _ in 0..count {
+                p.spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(12.0),
+                        height: Val::Px(20.0),
+                        ..default()
+                    },
+                    background_color: color.into(),
+                    ..default()
+                });
+            }
+        });
+    }
+}
+
+fn update_score(score: Res<Score>, mut texts: Query<(&mut Text, &HudText)>) {
+    if !score.is_changed() {
+        return;
+    }
+    for (mut t, kind) in texts.iter_mut() {
+        if let HudText::Score = kind {
+            t.sections[0].value = format!("Score: {}  x{}", score.points, score.multiplier);
+        }
+    }
+}
+
+fn update_wave(wave: Res<Wave>, mut texts: Query<(&mut Text, &HudText)>) {
+    if !wave.is_changed() {
+        return;
+    }
+    for (mut t, kind) in texts.iter_mut() {
+        if let HudText::Wave = kind {
+            t.sections[0].value = format!("Wave {}", wave.number);
+        }
+    }
+}
+
+fn update_timers(
+    timers: Query<Ref<HudTimer>>,
+    mut removed: RemovedComponents<HudTimer>,
+    mut texts: Query<(&mut Text, &HudText)>,
+) {
+    let removed = removed.read().count() > 0;
+    if !removed && !timers.iter().any(|t| t.is_changed()) {
+        return;
+    }
+    let value = timers
+        .iter()
+        .map(|t| format!("{} {:.1}s", t.label, t.timer.remaining_secs()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    for (mut t, kind) in texts.iter_mut() {
+        if let HudText::Timers = kind {
+            t.sections[0].value = value.clone();
+        }
+    }
+}
+
+fn toggle_fps(keyboard_input: Res<ButtonInput<KeyCode>>, mut settings: ResMut<HudSettings>) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        settings.show_fps = !settings.show_fps;
+    }
+}
+
+fn update_fps(
+    time: Res<Time<Real>>,
+    settings: Res<HudSettings>,
+    diagnostics: Res<DiagnosticsStore>,
+    mut since: Local<f32>,
+    mut texts: Query<(&mut Text, &mut Visibility, &HudText)>,
+) {
+    *since += time.delta_seconds();
+    let refresh = *since >= FPS_INTERVAL;
+    if !refresh && !settings.is_changed() {
+        return;
+    }
+    *since = 0.0;
+    let fps = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|d| d.smoothed());
+    for (mut t, mut visibility, kind) in texts.iter_mut() {
+        if let HudText::Fps = kind {
+            *visibility = if settings.show_fps {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            };
+            t.sections[0].value = fps.map_or(String::new(), |fps| format!("{fps:.0} fps"));
+        }
+    }
+}
+
+// Real time, so a message from the last moments of a game is still read on the
+// game over screen.
+fn show_notices(
+    time: Res<Time<Real>>,
+    mut unlocked: EventReader<Unlocked>,
+    mut notices: Local<Notices>,
+    mut texts: Query<(&mut Text, &HudText)>,
+) {
+    notices.queue.extend(
+        unlocked
+            .read()
+            .map(|u| format!("Achievement unlocked: {}", u.title)),
+    );
+    // Nothing to change while a message is up or none are waiting.
+    let idle = match notices.shown.as_mut() {
+        Some(timer) => !timer.tick(time.delta()).finished(),
+        None => notices.queue.is_empty(),
+    };
+    if idle {
+        return;
+    }
+    let next = notices.queue.pop_front();
+    notices.shown = next
+        .as_ref()
+        .map(|_| Timer::from_seconds(NOTICE_SECONDS, TimerMode::Once));
+    for (mut t, kind) in texts.iter_mut() {
+        if let HudText::Notice = kind {
+            t.sections[0].value = next.clone().unwrap_or_default();
+        }
+    }
+}
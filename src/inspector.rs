@@ -0,0 +1,558 @@
+use std::time::Duration;
+
+use bevy::{
+    ecs::query::{QueryItem, ROQueryItem},
+    prelude::*,
+    window::PrimaryWindow,
+};
+use bevy_sepax2d::prelude::*;
+
+use crate::{
+    camera::CameraRig, pool::Pooled, weapon::Projectile, Attack, Health, MainCamera, MoveTo,
+    Player, Rock, Ship, Spawner,
+};
+
+// Used for anything whose collider isn't a circle.
+const PICK_RADIUS: f32 = 25.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    X,
+    Y,
+    Rotation,
+    Health,
+    Attack,
+    MoveSpeed,
+    TurnSpeed,
+    TargetX,
+    TargetY,
+    Timer,
+}
+
+impl Field {
+    const ALL: [Field; 10] = [
+        Field::X,
+        Field::Y,
+        Field::Rotation,
+        Field::Health,
+        Field::Attack,
+        Field::MoveSpeed,
+        Field::TurnSpeed,
+        Field::TargetX,
+        Field::TargetY,
+        Field::Timer,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Field::X => "x",
+            Field::Y => "y",
+            Field::Rotation => "rotation",
+            Field::Health => "health",
+            Field::Attack => "attack",
+            Field::MoveSpeed => "speed",
+            Field::TurnSpeed => "turn rate",
+            Field::TargetX => "target x",
+            Field::TargetY => "target y",
+            Field::Timer => "timer",
+        }
+    }
+    // Amount one -/+ press changes the value by.
+    fn step(&self) -> f32 {
+        match self {
+            Field::X | Field::Y | Field::TargetX | Field::TargetY => 10.0,
+            Field::Rotation => 15.0,
+            Field::Health => 10.0,
+            Field::Attack => 1.0,
+            Field::MoveSpeed => 10.0,
+            Field::TurnSpeed => 0.5,
+            Field::Timer => 0.5,
+        }
+    }
+}
+
+type Inspected = (
+    &'static mut Transform,
+    Option<&'static mut Health>,
+    Option<&'static mut Attack>,
+    Option<&'static mut MoveTo>,
+    Option<&'static mut Spawner>,
+);
+
+fn spawner_timer(spawner: &Spawner) -> &Timer {
+    match spawner {
+        Spawner::Rock { timer, .. } | Spawner::Ship { timer } | Spawner::PowerUp { timer, .. } => {
+            timer
+        }
+    }
+}
+fn spawner_timer_mut(spawner: &mut Spawner) -> &mut Timer {
+    match spawner {
+        Spawner::Rock { timer, .. } | Spawner::Ship { timer } | Spawner::PowerUp { timer, .. } => {
+            timer
+        }
+    }
+}
+
+// Current value of `field`, `None` when the entity doesn't have it.
+fn read(field: Field, item: &ROQueryItem<Inspected>) -> Option<f32> {
+    let (transform, health, attack, move_to, spawner) = *item;
+    match (field, move_to) {
+        (Field::X, _) => Some(transform.translation.x),
+        (Field::Y, _) => Some(transform.translation.y),
+        (Field::Rotation, _) => Some(transform.rotation.to_euler(EulerRot::ZYX).0.to_degrees()),
+        (Field::Health, _) => health.map(|h| h.0 as f32),
+        (Field::Attack, _) => attack.map(|a| a.0 as f32),
+        (Field::MoveSpeed, Some(MoveTo::Player { movement_speed, .. }))
+        | (Field::MoveSpeed, Some(MoveTo::Point { movement_speed, .. })) => Some(*movement_speed),
+        (Field::TurnSpeed, Some(MoveTo::Player { rotation_speed, .. })) => Some(*rotation_speed),
+        (Field::TargetX, Some(MoveTo::Point { x, .. })) => Some(*x),
+        (Field::TargetY, Some(MoveTo::Point { y, .. })) => Some(*y),
+        (Field::Timer, _) => spawner.map(|s| spawner_timer(s).duration().as_secs_f32()),
+        _ => None,
+    }
+}
+
+fn nudge(field: Field, by: f32, item: &mut QueryItem<Inspected>) {
+    let (transform, health, attack, move_to, spawner) = item;
+    match (field, move_to.as_deref_mut()) {
+        (Field::X, _) => transform.translation.x += by,
+        (Field::Y, _) => transform.translation.y += by,
+        (Field::Rotation, _) => transform.rotate_z(by.to_radians()),
+        (Field::Health, _) => {
+            if let Some(health) = health {
+                health.0 += by as i32;
+            }
+        }
+        (Field::Attack, _) => {
+            if let Some(attack) = attack {
+                attack.0 = (attack.0 + by as i32).max(0);
+            }
+        }
+        (Field::MoveSpeed, Some(MoveTo::Player { movement_speed, .. }))
+        | (Field::MoveSpeed, Some(MoveTo::Point { movement_speed, .. })) => {
+            *movement_speed = (*movement_speed + by).max(0.0)
+        }
+        (Field::TurnSpeed, Some(MoveTo::Player { rotation_speed, .. })) => {
+            *rotation_speed = (*rotation_speed + by).max(0.0)
+        }
+        (Field::TargetX, Some(MoveTo::Point { x, .. })) => *x += by,
+        (Field::TargetY, Some(MoveTo::Point { y, .. })) => *y += by,
+        (Field::Timer, _) => {
+            if let Some(spawner) = spawner {
+                let timer = spawner_timer_mut(spawner);
+                let secs = (timer.duration().as_secs_f32() + by).max(Field::Timer.step());
+                timer.set_duration(Duration::from_secs_f32(secs));
+            }
+        }
+        _ => {}
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Inspector {
+    open: bool,
+    selected: Option<Entity>,
+    follow: bool,
+    // Set whenever the panel layout needs to be rebuilt.
+    dirty: bool,
+}
+impl Inspector {
+    pub fn selected(&self) -> Option<Entity> {
+        self.selected
+    }
+}
+
+#[derive(Component)]
+struct InspectorPanel;
+#[derive(Component)]
+struct InspectorBody;
+#[derive(Component)]
+struct ValueText(Field);
+#[derive(Component)]
+struct PauseText;
+#[derive(Component, Clone, Copy)]
+enum InspectorButton {
+    Down(Field),
+    Up(Field),
+    Pause,
+    Step,
+    Follow,
+    Clear,
+}
+
+pub struct InspectorPlugin;
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Inspector>()
+            .add_systems(Startup, create_panel)
+            .add_systems(Update, toggle_inspector)
+            .add_systems(
+                Update,
+                (
+                    pick,
+                    inspector_buttons,
+                    refresh_panel.after(pick).after(inspector_buttons),
+                    update_values.after(refresh_panel),
+                    draw_selection,
+                )
+                    .run_if(|inspector: Res<Inspector>| inspector.open),
+            )
+            .add_systems(
+                PostUpdate,
+                follow_selection.before(crate::camera::follow_player),
+            );
+    }
+}
+
+fn toggle_inspector(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut inspector: ResMut<Inspector>,
+    mut panel: Query<&mut Visibility, With<InspectorPanel>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F2) {
+        return;
+    }
+    inspector.open = !inspector.open;
+    inspector.dirty = true;
+    for mut v in panel.iter_mut() {
+        *v = if inspector.open {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+fn pick(
+    mouse: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    ui: Query<&Interaction>,
+    colliders: Query<(Entity, &Sepax, &GlobalTransform)>,
+    mut inspector: ResMut<Inspector>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) || ui.iter().any(|i| *i != Interaction::None) {
+        return;
+    }
+    let (Ok(window), Ok((camera, transform))) = (windows.get_single(), camera.get_single()) else {
+        return;
+    };
+    let Some(cursor) = crate::view::cursor_world(window, camera, transform) else {
+        return;
+    };
+    let hit = colliders
+        .iter()
+        .filter_map(|(e, sepax, t)| {
+            let radius = match &sepax.convex {
+                Convex::Circle(circle) => circle.radius,
+                _ => PICK_RADIUS,
+            };
+            let distance = t.translation().xy().distance(cursor);
+            (distance < radius).then_some((e, distance))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+    if let Some((e, _)) = hit {
+        inspector.selected = Some(e);
+        inspector.dirty = true;
+    }
+}
+
+fn inspector_buttons(
+    interactions: Query<(&Interaction, &InspectorButton), Changed<Interaction>>,
+    mut inspected: Query<Inspected>,
+    mut inspector: ResMut<Inspector>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut fixed: ResMut<Time<Fixed>>,
+) {
+    for (interaction, button) in interactions.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match *button {
+            InspectorButton::Pause => {
+                if virtual_time.is_paused() {
+                    virtual_time.unpause();
+                } else {
+                    virtual_time.pause();
+                }
+            }
+            // The fixed loop runs once per timestep of accumulated time,
+            // so adding one timestep while paused runs exactly one tick.
+            InspectorButton::Step => {
+                if virtual_time.is_paused() {
+                    let timestep = fixed.timestep();
+                    fixed.accumulate(timestep);
+                }
+            }
+            InspectorButton::Follow => inspector.follow = !inspector.follow,
+            InspectorButton::Clear => {
+                inspector.selected = None;
+                inspector.dirty = true;
+            }
+            InspectorButton::Down(field) | InspectorButton::Up(field) => {
+                let by = match *button {
+                    InspectorButton::Down(_) => -field.step(),
+                    _ => field.step(),
+                };
+                if let Some(mut item) = inspector.selected.and_then(|e| inspected.get_mut(e).ok()) {
+                    nudge(field, by, &mut item);
+                }
+            }
+        }
+    }
+}
+
+fn create_panel(mut cmds: Commands) {
+    cmds.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(0.0),
+                top: Val::Px(0.0),
+                width: Val::Px(260.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(8.0)),
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            background_color: Color::rgba(0.1, 0.12, 0.1, 0.9).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        Interaction::default(),
+        InspectorPanel,
+    ))
+    .with_children(|p| {
+        label(p, "Inspector (F2)", 24.0);
+        label(p, "LMB select", 14.0);
+        p.spawn(NodeBundle {
+            style: Style {
+                column_gap: Val::Px(4.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|p| {
+            p.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::axes(Val::Px(8.0), Val::Px(2.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.25, 0.3, 0.25).into(),
+                    ..default()
+                },
+                InspectorButton::Pause,
+            ))
+            .with_children(|p| {
+                p.spawn((
+                    TextBundle::from_section(
+                        "Pause",
+                        TextStyle {
+                            font_size: 16.0,
+                            ..default()
+                        },
+                    ),
+                    PauseText,
+                ));
+            });
+            button(p, "Step", InspectorButton::Step);
+            button(p, "Follow", InspectorButton::Follow);
+        });
+        p.spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                ..default()
+            },
+            InspectorBody,
+        ));
+    });
+}
+
+// Names the selection after whatever gameplay marker it carries.
+fn describe(
+    player: bool,
+    rock: Option<&Rock>,
+    ship: bool,
+    spawner: Option<&Spawner>,
+    projectile: bool,
+) -> String {
+    match (player, rock, ship, spawner, projectile) {
+        (true, ..) => "Player".into(),
+        (_, Some(rock), ..) => format!("Rock (size {})", rock.size),
+        (_, _, true, ..) => "Ship".into(),
+        (.., Some(Spawner::Rock { .. }), _) => "Rock spawner".into(),
+        (.., Some(Spawner::Ship { .. }), _) => "Ship spawner".into(),
+        (.., Some(Spawner::PowerUp { .. }), _) => "Power-up spawner".into(),
+        (.., true) => "Projectile".into(),
+        _ => "Entity".into(),
+    }
+}
+
+fn refresh_panel(
+    mut cmds: Commands,
+    mut inspector: ResMut<Inspector>,
+    body: Query<Entity, With<InspectorBody>>,
+    mut inspected: Query<Inspected>,
+    markers: Query<(
+        Has<Player>,
+        Option<&Rock>,
+        Has<Ship>,
+        Has<Projectile>,
+        Option<&Sepax>,
+    )>,
+    pooled: Query<(), With<Pooled>>,
+) {
+    // Despawned or pooled selections are dropped.
+    if let Some(e) = inspector.selected {
+        if inspected.get(e).is_err() || pooled.contains(e) {
+            inspector.selected = None;
+            inspector.dirty = true;
+        }
+    }
+    if !inspector.dirty {
+        return;
+    }
+    inspector.dirty = false;
+    let Ok(body) = body.get_single() else {
+        return;
+    };
+    cmds.entity(body).despawn_descendants();
+    let selected = inspector.selected;
+    cmds.entity(body).with_children(|p| {
+        let Some(e) = selected else {
+            label(p, "Nothing selected", 16.0);
+            return;
+        };
+        let (Ok(item), Ok((player, rock, ship, projectile, sepax))) =
+            (inspected.get(e), markers.get(e))
+        else {
+            return;
+        };
+        label(p, &describe(player, rock, ship, item.4, projectile), 20.0);
+        label(p, &format!("{e:?}"), 14.0);
+        if let Some(sepax) = sepax {
+            let shape = match &sepax.convex {
+                Convex::Circle(circle) => format!("Collider: circle r{}", circle.radius),
+                _ => "Collider: polygon".into(),
+            };
+            label(p, &shape, 14.0);
+        }
+        for field in Field::ALL {
+            if read(field, &item).is_none() {
+                continue;
+            }
+            p.spawn(NodeBundle {
+                style: Style {
+                    column_gap: Val::Px(4.0),
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|p| {
+                p.spawn((
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 16.0,
+                            ..default()
+                        },
+                    ),
+                    ValueText(field),
+                ));
+                button(p, "-", InspectorButton::Down(field));
+                button(p, "+", InspectorButton::Up(field));
+            });
+        }
+        button(p, "Clear", InspectorButton::Clear);
+    });
+}
+
+// Values change every frame, so only the text is updated rather than the layout.
+fn update_values(
+    inspector: Res<Inspector>,
+    virtual_time: Res<Time<Virtual>>,
+    inspected: Query<Inspected>,
+    mut texts: Query<(&mut Text, Option<&ValueText>), Or<(With<ValueText>, With<PauseText>)>>,
+) {
+    let item = inspector.selected.and_then(|e| inspected.get(e).ok());
+    for (mut text, value) in texts.iter_mut() {
+        let new = match (value, item.as_ref()) {
+            (Some(ValueText(field)), Some(item)) => {
+                let v = read(*field, item).unwrap_or_default();
+                format!("{} {:.1}", field.label(), v)
+            }
+            (Some(_), None) => continue,
+            (None, _) if virtual_time.is_paused() => "Resume".into(),
+            (None, _) => "Pause".into(),
+        };
+        if text.sections[0].value != new {
+            text.sections[0].value = new;
+        }
+    }
+}
+
+fn follow_selection(inspector: Res<Inspector>, mut rigs: Query<&mut CameraRig>) {
+    let follow = inspector
+        .selected
+        .filter(|_| inspector.open && inspector.follow);
+    for mut rig in rigs.iter_mut() {
+        if rig.follow != follow {
+            rig.follow = follow;
+        }
+    }
+}
+
+fn draw_selection(
+    mut gizmos: Gizmos,
+    inspector: Res<Inspector>,
+    query: Query<(&GlobalTransform, Option<&Sepax>)>,
+) {
+    let Some((t, sepax)) = inspector.selected.and_then(|e| query.get(e).ok()) else {
+        return;
+    };
+    let radius = match sepax.map(|s| &s.convex) {
+        Some(Convex::Circle(circle)) => circle.radius,
+        _ => PICK_RADIUS,
+    };
+    gizmos.circle_2d(t.translation().xy(), radius + 4.0, Color::FUCHSIA);
+}
+
+fn label(p: &mut ChildBuilder, text: &str, font_size: f32) {
+    p.spawn(TextBundle::from_section(
+        text,
+        TextStyle {
+            font_size,
+            ..default()
+        },
+    ));
+}
+fn button(p: &mut ChildBuilder, text: &str, action: InspectorButton) {
+    p.spawn((
+        ButtonBundle {
+            style: Style {
+                padding: UiRect::axes(Val::Px(8.0), Val::Px(2.0)),
+                ..default()
+            },
+            background_color: Color::rgb(0.25, 0.3, 0.25).into(),
+            ..default()
+        },
+        action,
+    ))
+    .with_children(|p| {
+        p.spawn(TextBundle::from_section(
+            text,
+            TextStyle {
+                font_size: 16.0,
+                ..default()
+            },
+        ));
+    });
+}
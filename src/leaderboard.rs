@@ -0,0 +1,433 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+    fs, io,
+    path::PathBuf,
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    progress::{Difficulty, GameMode, GameOver, Score, Wave},
+    settings::data_dir,
+};
+
+const FILE_NAME: &str = "leaderboard.txt";
+// Bumped whenever the line format changes, see `upgrade`.
+const VERSION: u32 = 1;
+pub const TOP_N: usize = 10;
+pub const NAME_LEN: usize = 8;
+// What a gamepad cycles through, typed names are limited to the same.
+const NAME_CHARS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-";
+
+// Each mode and difficulty keeps a board of its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct BoardKey {
+    pub mode: GameMode,
+    pub difficulty: Difficulty,
+}
+impl BoardKey {
+    pub fn label(&self) -> String {
+        format!("{} / {}", self.mode.label(), self.difficulty.label())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub score: u64,
+    pub wave: u32,
+}
+
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct Leaderboard {
+    // Best first, at most `TOP_N` each.
+    boards: HashMap<BoardKey, Vec<Entry>>,
+}
+
+impl Leaderboard {
+    pub fn path() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join(FILE_NAME))
+    }
+
+    // Like the stats, a file that can't be read is moved aside so a save
+    // doesn't replace it with an empty board.
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        let Ok(source) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        Self::parse(&source).unwrap_or_else(|e| {
+            let aside = path.with_extension("broken");
+            error!(
+                "ignoring {}: {}, kept as {}",
+                path.display(),
+                e,
+                aside.display()
+            );
+            let _ = fs::rename(&path, aside);
+            Self::default()
+        })
+    }
+
+    // `version <n>` first, then `<mode> <difficulty> <score> <wave> <name>` per entry.
+    pub fn parse(source: &str) -> Result<Self, LeaderboardError> {
+        let mut lines = source
+            .lines()
+            .enumerate()
+            .map(|(n, line)| (n + 1, line.split('#').next().unwrap_or("").trim()))
+            .filter(|(_, line)| !line.is_empty());
+        let version = match lines.next() {
+            Some((n, line)) => line
+                .strip_prefix("version ")
+                .and_then(|v| v.trim().parse().ok())
+                .ok_or_else(|| LeaderboardError::Line(n, "expected version".into()))?,
+            None => return Ok(Self::default()),
+        };
+        let lines = migrate(version, lines.map(|(n, l)| (n, l.to_string())).collect())?;
+
+        let mut board = Self::default();
+        for (n, line) in lines {
+            let bad = |msg: &str| LeaderboardError::Line(n, msg.to_string());
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let [mode, difficulty, score, wave, name @ ..] = &words[..] else {
+                return Err(bad("expected mode, difficulty, score, wave and name"));
+            };
+            let key = BoardKey {
+                mode: *GameMode::ALL
+                    .iter()
+                    .find(|m| m.label().eq_ignore_ascii_case(mode))
+                    .ok_or_else(|| bad(&format!("unknown mode {mode}")))?,
+                difficulty: *Difficulty::ALL
+                    .iter()
+                    .find(|d| d.label().eq_ignore_ascii_case(difficulty))
+                    .ok_or_else(|| bad(&format!("unknown difficulty {difficulty}")))?,
+            };
+            let entry = Entry {
+                name: name.join(" "),
+                score: score
+                    .parse()
+                    .map_err(|_| bad(&format!("invalid score {score}")))?,
+                wave: wave
+                    .parse()
+                    .map_err(|_| bad(&format!("invalid wave {wave}")))?,
+            };
+            board.insert(key, entry);
+        }
+        Ok(board)
+    }
+
+    pub fn to_source(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "version {VERSION}");
+        for mode in GameMode::ALL {
+            for difficulty in Difficulty::ALL {
+                for e in self.entries(BoardKey { mode, difficulty }) {
+                    let _ = writeln!(
+                        out,
+                        "{} {} {} {} {}",
+                        mode.label().to_lowercase(),
+                        difficulty.label().to_lowercase(),
+                        e.score,
+                        e.wave,
+                        e.name
+                    );
+                }
+            }
+        }
+        out
+    }
+
+    pub fn save(&self) -> Result<PathBuf, io::Error> {
+        let path = Self::path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, self.to_source())?;
+        Ok(path)
+    }
+
+    pub fn entries(&self, key: BoardKey) -> &[Entry] {
+        self.boards.get(&key).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn qualifies(&self, key: BoardKey, score: u64) -> bool {
+        let entries = self.entries(key);
+        score > 0 && (entries.len() < TOP_N || entries.iter().any(|e| score > e.score))
+    }
+
+    // Returns the rank it got, ties go below the scores already there.
+    pub fn insert(&mut self, key: BoardKey, entry: Entry) -> Option<usize> {
+        let entries = self.boards.entry(key).or_default();
+        let rank = entries
+            .iter()
+            .position(|e| entry.score > e.score)
+            .unwrap_or(entries.len());
+        if rank >= TOP_N {
+            return None;
+        }
+        entries.insert(rank, entry);
+        entries.truncate(TOP_N);
+        Some(rank)
+    }
+}
+
+// Brings the lines of an older file up to `VERSION`, one format at a time.
+fn migrate(
+    version: u32,
+    lines: Vec<(usize, String)>,
+) -> Result<Vec<(usize, String)>, LeaderboardError> {
+    if version > VERSION {
+        return Err(LeaderboardError::Newer(version));
+    }
+    (version..VERSION).try_fold(lines, |lines, from| upgrade(from, lines))
+}
+
+// Rewrites lines of version `from` in the format of `from + 1`. Version 1 is
+// the first format, a change to it bumps `VERSION` and adds its step here.
+fn upgrade(
+    from: u32,
+    _lines: Vec<(usize, String)>,
+) -> Result<Vec<(usize, String)>, LeaderboardError> {
+    Err(LeaderboardError::Unknown(from))
+}
+
+#[derive(Debug)]
+pub enum LeaderboardError {
+    Line(usize, String),
+    // Written by a newer build.
+    Newer(u32),
+    Unknown(u32),
+}
+impl fmt::Display for LeaderboardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LeaderboardError::Line(line, msg) => write!(f, "line {line}: {msg}"),
+            LeaderboardError::Newer(v) => write!(f, "version {v} is newer than this build"),
+            LeaderboardError::Unknown(v) => write!(f, "no upgrade from version {v}"),
+        }
+    }
+}
+impl std::error::Error for LeaderboardError {}
+
+// A finished game's score waiting for a name before it goes on the board.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct NameEntry {
+    pub pending: Option<(BoardKey, u64, u32)>,
+    // Kept between games so the last name is offered again.
+    pub name: String,
+}
+impl NameEntry {
+    pub fn type_char(&mut self, c: char) {
+        let c = c.to_ascii_uppercase();
+        if NAME_CHARS.contains(c) && self.name.len() < NAME_LEN {
+            self.name.push(c);
+        }
+    }
+    pub fn backspace(&mut self) {
+        self.name.pop();
+    }
+    // Steps the last letter through `NAME_CHARS`, for gamepads.
+    pub fn cycle(&mut self, step: isize) {
+        let Some(last) = self.name.pop() else {
+            self.name.push('A');
+            return;
+        };
+        let chars: Vec<char> = NAME_CHARS.chars().collect();
+        let i = chars.iter().position(|c| *c == last).unwrap_or(0) as isize;
+        let len = chars.len() as isize;
+        self.name.push(chars[(i + step).rem_euclid(len) as usize]);
+    }
+    pub fn display(&self) -> String {
+        if self.name.len() < NAME_LEN {
+            format!("{}_", self.name)
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+// Which board the leaderboard screen shows, and the rank just entered.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct BoardView {
+    pub key: BoardKey,
+    pub highlight: Option<usize>,
+}
+
+pub struct LeaderboardPlugin;
+impl Plugin for LeaderboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Leaderboard::load())
+            .init_resource::<NameEntry>()
+            .init_resource::<BoardView>()
+            .add_systems(Update, check_score.run_if(on_event::<GameOver>()));
+    }
+}
+
+// Asks for a name when the final score makes the board.
+fn check_score(
+    mut events: EventReader<GameOver>,
+    score: Res<Score>,
+    wave: Res<Wave>,
+    mode: Res<GameMode>,
+    difficulty: Res<Difficulty>,
+    leaderboard: Res<Leaderboard>,
+    mut entry: ResMut<NameEntry>,
+) {
+    events.clear();
+    let key = BoardKey {
+        mode: *mode,
+        difficulty: *difficulty,
+    };
+    if leaderboard.qualifies(key, score.points) {
+        entry.pending = Some((key, score.points, wave.number));
+    }
+}
+
+// Puts the pending score on its board and saves, returning where it landed.
+pub fn submit(leaderboard: &mut Leaderboard, entry: &mut NameEntry) -> Option<(BoardKey, usize)> {
+    let (key, score, wave) = entry.pending.take()?;
+    let name = match entry.name.trim() {
+        "" => "???".to_string(),
+        name => name.to_string(),
+    };
+    let rank = leaderboard.insert(key, Entry { name, score, wave })?;
+    match leaderboard.save() {
+        Ok(path) => info!("saved leaderboard to {}", path.display()),
+        Err(e) => error!("failed to save leaderboard: {}", e),
+    }
+    Some((key, rank))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: u64) -> Entry {
+        Entry {
+            name: name.into(),
+            score,
+            wave: 1,
+        }
+    }
+
+    const HARD: BoardKey = BoardKey {
+        mode: GameMode::Endless,
+        difficulty: Difficulty::Hard,
+    };
+
+    #[test]
+    fn source_round_trips() {
+        let mut board = Leaderboard::default();
+        board.insert(BoardKey::default(), entry("ACE", 900));
+        board.insert(BoardKey::default(), entry("BOB", 300));
+        board.insert(HARD, entry("CAT-2", 50));
+        let source = board.to_source();
+        assert!(source.starts_with(&format!("version {VERSION}\n")));
+        assert_eq!(Leaderboard::parse(&source).unwrap(), board);
+        assert_eq!(Leaderboard::parse("").unwrap(), Leaderboard::default());
+    }
+
+    #[test]
+    fn ranks_entries_and_keeps_the_top() {
+        let mut board = Leaderboard::default();
+        let key = BoardKey::default();
+        assert_eq!(board.insert(key, entry("A", 100)), Some(0));
+        assert_eq!(board.insert(key, entry("B", 300)), Some(0));
+        // Ties go below the score already there.
+        assert_eq!(board.insert(key, entry("C", 100)), Some(2));
+        for i in 0..TOP_N {
+            board.insert(key, entry("FILL", 200 + i as u64));
+        }
+        let entries = board.entries(key);
+        assert_eq!(entries.len(), TOP_N);
+        assert_eq!(entries[0].name, "B");
+        assert!(entries.windows(2).all(|w| w[0].score >= w[1].score));
+        assert_eq!(board.insert(key, entry("LOW", 1)), None);
+        assert!(board.entries(HARD).is_empty());
+    }
+
+    #[test]
+    fn qualifies_only_for_a_place() {
+        let mut board = Leaderboard::default();
+        let key = BoardKey::default();
+        assert!(!board.qualifies(key, 0));
+        assert!(board.qualifies(key, 1));
+        for _ in 0..TOP_N {
+            board.insert(key, entry("FILL", 100));
+        }
+        assert!(!board.qualifies(key, 100));
+        assert!(board.qualifies(key, 101));
+        assert!(board.qualifies(HARD, 1));
+    }
+
+    // Files start at version 1, anything without a version line is moved aside.
+    #[test]
+    fn requires_a_version_line() {
+        for (source, line) in [
+            ("500 3 ACE\n", 1),
+            ("# old list\nsurvival normal 500 3 ACE\n", 2),
+        ] {
+            assert!(
+                matches!(Leaderboard::parse(source), Err(LeaderboardError::Line(n, _)) if n == line),
+                "{source:?}"
+            );
+        }
+        assert!(matches!(
+            Leaderboard::parse("version 0\n"),
+            Err(LeaderboardError::Unknown(0))
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        assert!(matches!(
+            Leaderboard::parse("version 99\n"),
+            Err(LeaderboardError::Newer(99))
+        ));
+        for (source, line) in [
+            ("version one", 1),
+            ("version 1\nsurvival normal 10", 2),
+            (
+                "version 1\n\nsurvival normal 10 1 ACE\nsprint normal 10 1 ACE",
+                4,
+            ),
+            ("version 1\nsurvival insane 10 1 ACE", 2),
+            ("version 1\nsurvival normal -10 1 ACE", 2),
+            ("version 1\nsurvival normal 10 x ACE", 2),
+        ] {
+            assert!(
+                matches!(Leaderboard::parse(source), Err(LeaderboardError::Line(n, _)) if n == line),
+                "{source:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn enters_names() {
+        let mut entry = NameEntry::default();
+        for c in "ab c!9".chars() {
+            entry.type_char(c);
+        }
+        assert_eq!(entry.name, "ABC9");
+        assert_eq!(entry.display(), "ABC9_");
+        entry.backspace();
+        entry.cycle(1);
+        assert_eq!(entry.name, "ABD");
+        entry.name = "A".into();
+        entry.cycle(-1);
+        assert_eq!(entry.name, "-");
+        for c in "LONGERTHANEIGHT".chars() {
+            entry.type_char(c);
+        }
+        assert_eq!(entry.name.len(), NAME_LEN);
+        assert_eq!(entry.display(), entry.name);
+
+        let mut empty = NameEntry::default();
+        empty.cycle(1);
+        assert_eq!(empty.name, "A");
+    }
+}
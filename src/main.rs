@@ -16,14 +16,57 @@ use bevy_sepax2d::prelude::{
     sepax2d::{sat_overlap, Circle as SpxCircle},
     *,
 };
-use rand::thread_rng;
+use pool::{EntityPool, GameAssets, PoolKind, ROCK_SPAWNER_RADIUS, SHIP_SPAWNER_RADIUS};
 use rand::Rng;
-pub const SCREEN_SIZE: Vec2 = Vec2::new(1200.0, 640.0);
-
-#[derive(Component)]
+use sampling::{GameRng, SpawnSampler};
+use sprites::Visual;
+
+mod areas;
+mod arena;
+mod bench;
+mod camera;
+#[cfg(feature = "debug")]
+mod console;
+#[cfg(feature = "debug")]
+mod debug;
+#[cfg(feature = "editor")]
+mod editor;
+mod hud;
+#[cfg(feature = "debug")]
+mod inspector;
+mod leaderboard;
+mod menu;
+mod minimap;
+mod netplay;
+mod particles;
+mod players;
+mod pool;
+mod powerups;
+mod progress;
+mod sampling;
+mod settings;
+mod snapshot;
+mod sprites;
+mod stats;
+mod view;
+mod weapon;
+
+// Logical size of the play field in world units, also the initial window size.
+pub const FIELD_SIZE: Vec2 = Vec2::new(1200.0, 640.0);
+pub const SPAWNER_TIMER: f32 = 5.0;
+pub const ROCK_SIZES: [f32; 4] = [10.0, 25.0, 30.0, 40.0];
+// Largest radius arenas and the editor may give a rock.
+pub const MAX_ROCK_SIZE: f32 = 160.0;
+const ROCK_SIDES: [usize; 4] = [5, 8, 6, 9];
+pub const PLAYER_HEALTH: i32 = 100;
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
 struct Player {
     movement_speed: f32,
     rotation_speed: f32,
+    // Which controls and colour this player uses, 0 to `MAX_PLAYERS - 1`.
+    slot: usize,
 }
 #[derive(Resource)]
 struct Config {
@@ -34,29 +77,40 @@ struct Config {
     rock_spawn_speed: f32,
     rock_movement_speed: f32,
 }
-#[derive(Component)]
-enum UiLabel {
-    Life,
-    Score,
-}
-#[derive(Component)]
+#[derive(Component, Reflect)]
+#[reflect(Component)]
 enum Spawner {
     Rock {
         timer: Timer,
-        sizes: Vec<(Mesh2dHandle, f32)>,
+        sizes: Vec<f32>,
+        // Built from `sizes`, so snapshots only need to store the radii.
+        #[reflect(ignore)]
+        meshes: Vec<Mesh2dHandle>,
         life: u8,
     },
     Ship {
         timer: Timer,
     },
+    PowerUp {
+        timer: Timer,
+        table: Vec<powerups::TableEntry>,
+    },
 }
 
 #[derive(Component)]
-struct Rock;
-#[derive(Component)]
+struct MainCamera;
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct Rock {
+    size: f32,
+}
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 struct Ship;
 
-#[derive(Component, Default)]
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 enum MoveTo {
     Player {
         rotation_speed: f32,
@@ -71,114 +125,93 @@ enum MoveTo {
     #[default]
     None,
 }
-mod areas {
-    use bevy::prelude::*;
-
-    use crate::SCREEN_SIZE;
-    #[derive(Resource)]
-    pub struct SpawnArea {
-        pub(crate) rect: Rect,
-        #[cfg(debug)]
-        pub color: Color,
-    }
-    #[derive(Resource)]
-    pub struct SafeArea {
-        pub(crate) rect: Rect,
-        #[cfg(debug)]
-        pub color: Color,
-    }
-    #[derive(Resource)]
-    pub struct PlayArea {
-        pub(crate) rect: Rect,
-        #[cfg(debug)]
-        pub color: Color,
-    }
-    pub struct AreaPlugin;
-    impl Plugin for AreaPlugin {
-        fn build(&self, app: &mut App) {
-            app.insert_resource(SpawnArea {
-                rect: Rect::new(-SCREEN_SIZE.x, -SCREEN_SIZE.y, SCREEN_SIZE.x, SCREEN_SIZE.y),
-                #[cfg(debug)]
-                color: Color::RED,
-            })
-            .insert_resource(SafeArea {
-                rect: Rect::new(
-                    -SCREEN_SIZE.x / 3.,
-                    -SCREEN_SIZE.y / 3.,
-                    SCREEN_SIZE.x / 3.,
-                    SCREEN_SIZE.y / 3.,
-                ),
-                #[cfg(debug)]
-                color: Color::BLUE,
-            })
-            .insert_resource(PlayArea {
-                rect: Rect::new(
-                    -SCREEN_SIZE.x / 2.,
-                    -SCREEN_SIZE.y / 2.,
-                    SCREEN_SIZE.x / 2.,
-                    SCREEN_SIZE.y / 2.,
-                ),
-                #[cfg(debug)]
-                color: Color::BLUE,
-            });
-        }
-    }
-}
 fn main() {
-    App::new()
-        .add_plugins((
-            DefaultPlugins.set(WindowPlugin {
-                primary_window: Some(Window {
-                    resolution: WindowResolution::new(SCREEN_SIZE.x, SCREEN_SIZE.y),
-                    title: "game".into(),
-                    ..default()
-                }),
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let net = match args.first().map(String::as_str) {
+        Some("--bench") => return bench::main(&args[1..]),
+        Some("--net") => Some(netplay::session_from_args(&args[1..])),
+        _ => None,
+    };
+    let settings = settings::Settings::load();
+    let mut app = App::new();
+    app.add_plugins((
+        DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                resolution: WindowResolution::new(FIELD_SIZE.x, FIELD_SIZE.y),
+                title: "game".into(),
+                mode: settings.window_mode(),
+                present_mode: settings.present_mode(),
                 ..default()
             }),
+            ..default()
+        }),
+        GameplayPlugin,
+    ))
+    .add_plugins((
+        particles::ParticlePlugin,
+        snapshot::SnapshotPlugin,
+        stats::StatsPlugin,
+        leaderboard::LeaderboardPlugin,
+    ))
+    .add_plugins((
+        view::ViewPlugin,
+        camera::CameraPlugin,
+        minimap::MinimapPlugin,
+        hud::HudPlugin,
+        menu::MenuPlugin,
+        settings::SettingsPlugin,
+        sprites::SpriteSheetPlugin,
+        #[cfg(feature = "editor")]
+        editor::EditorPlugin,
+        #[cfg(feature = "debug")]
+        debug::DebugPlugin,
+        #[cfg(feature = "debug")]
+        inspector::InspectorPlugin,
+        #[cfg(feature = "debug")]
+        console::ConsolePlugin,
+    ))
+    .insert_resource(settings)
+    .add_systems(Startup, setup_scene);
+    if let Some(session) = net {
+        app.add_plugins(netplay::NetPlugin).insert_resource(session);
+    }
+    app.run();
+}
+
+// Everything the fixed schedule steps, without windows or rendering of its
+// own. Netplay's test runs two of these headless.
+struct GameplayPlugin;
+impl Plugin for GameplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
             SepaxPlugin,
+            areas::AreaPlugin,
+            arena::ArenaPlugin,
+            sampling::SamplingPlugin,
+            progress::ProgressPlugin,
+            weapon::WeaponPlugin,
+            powerups::PowerUpPlugin,
+            players::PlayersPlugin,
+            pool::PoolPlugin,
         ))
+        // Read by the particle and camera plugins when the game has them.
+        .add_event::<particles::Burst>()
+        .add_event::<camera::Trauma>()
         .insert_resource(Time::<Fixed>::from_hz(60.0))
-        .insert_resource(SpawnArea {
-            rect: Rect::new(-SCREEN_SIZE.x, -SCREEN_SIZE.y, SCREEN_SIZE.x, SCREEN_SIZE.y),
-            #[cfg(debug)]
-            color: Color::RED,
-        })
-        .insert_resource(SafeArea {
-            rect: Rect::new(
-                -SCREEN_SIZE.x / 3.,
-                -SCREEN_SIZE.y / 3.,
-                SCREEN_SIZE.x / 3.,
-                SCREEN_SIZE.y / 3.,
-            ),
-            #[cfg(debug)]
-            color: Color::BLUE,
-        })
-        .insert_resource(PlayArea {
-            rect: Rect::new(
-                -SCREEN_SIZE.x / 2.,
-                -SCREEN_SIZE.y / 2.,
-                SCREEN_SIZE.x / 2.,
-                SCREEN_SIZE.y / 2.,
-            ),
-        })
-        .add_systems(Startup, setup_scene)
         .add_systems(
             FixedUpdate,
             (
                 player_movement,
-                draw_spawn_defs,
-                draw_ship_target,
                 rotate_to_player,
-                update_ui,
                 rotate_to_point,
                 player_collision.after(player_movement),
                 rock_despawn.after(rotate_to_point).after(rotate_to_player),
                 ship_despawn.after(rotate_to_point).after(rotate_to_player),
+                spawn_rocks,
+                spawn_ships.after(spawn_rocks),
             ),
-        )
-        .add_systems(Update, (spawn_rocks, spawn_ships))
-        .add_systems(Update, bevy::window::close_on_esc)
-        .run();
+        );
+    }
 }
 
 fn setup_scene(
@@ -186,154 +219,108 @@ fn setup_scene(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    cmds.spawn(Camera2dBundle::default());
-    create_ui(&mut cmds);
-    create_player(&mut cmds, &mut meshes, &mut materials);
-    spawn_rock_spawner(&mut cmds, &mut meshes, &mut materials);
-    spawn_ship_spawner(&mut cmds, &mut meshes, &mut materials);
-}
-
-fn create_ui(cmds: &mut Commands) {
-    cmds.spawn(NodeBundle {
-        style: Style {
-            width: Val::Percent(100.0),
-            height: Val::Percent(100.0),
-            align_items: AlignItems::Start,
-            justify_content: JustifyContent::Start,
-            flex_direction: FlexDirection::Column,
-            padding: UiRect::px(5.0, 5.0, 5.0, 5.0),
-            ..default()
-        },
-        ..default()
-    })
-    .with_children(|p| {
-        p.spawn(TextBundle::from_section(
-            "Life: 0",
-            TextStyle {
-                font_size: 40.0,
-                ..Default::default()
-            },
-        ))
-        .insert(UiLabel::Life);
-        p.spawn(TextBundle::from_section(
-            "Score: 0",
-            TextStyle {
-                font_size: 40.0,
-                ..Default::default()
-            },
-        ))
-        .insert(UiLabel::Score);
-    });
-}
-
-fn draw_spawn_defs(
-    mut gizmos: Gizmos,
-    spawn: Res<SpawnArea>,
-    exlude: Res<SafeArea>,
-    play: Res<PlayArea>,
-) {
-    gizmos.rect_2d(Vec2::ZERO, 0., spawn.rect.size(), Color::RED);
-    gizmos.rect_2d(Vec2::ZERO, 0., exlude.rect.size(), Color::BLUE);
-    gizmos.rect_2d(Vec2::ZERO, 0., play.rect.size(), Color::WHITE);
-}
-fn draw_ship_target(
-    mut gizmos: Gizmos,
-    query: Query<&GlobalTransform, With<Ship>>,
-    player: Query<&GlobalTransform, (With<Player>, Without<Ship>, Without<Rock>)>,
-) {
-    let pt = player.single();
-    for t in query.iter() {
-        gizmos.line_2d(t.translation().xy(), pt.translation().xy(), Color::YELLOW)
-    }
+    cmds.spawn((
+        Camera2dBundle::default(),
+        camera::CameraRig::default(),
+        camera::PlayerView(0),
+        IsDefaultUiCamera,
+        MainCamera,
+    ));
+    create_player(&mut cmds, &mut meshes, &mut materials, 0, Vec2::ZERO);
 }
 
 fn spawn_ship_spawner(
     cmds: &mut Commands,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    materials: &mut ResMut<Assets<ColorMaterial>>,
-) {
-    let shape = SpxCircle::new((0., 0.), 10.0);
-    cmds.spawn((
-        MaterialMesh2dBundle {
-            mesh: Mesh2dHandle(meshes.add(Circle { radius: 10.0 })),
-            material: materials.add(Color::RED),
-            transform: Transform::from_xyz(0., 0., 3.),
-            ..default()
-        },
-        Sepax {
-            convex: Convex::Circle(shape),
-        },
-        Spawner::Ship {
-            timer: Timer::from_seconds(5.0, TimerMode::Repeating),
-        },
-    ));
+    assets: &GameAssets,
+    position: Vec2,
+    timer: f32,
+) -> Entity {
+    let spawner = Spawner::Ship {
+        timer: Timer::from_seconds(timer, TimerMode::Repeating),
+    };
+    let transform = Transform::from_xyz(position.x, position.y, 3.);
+    let body = spawner_body(&spawner, assets, transform);
+    cmds.spawn((body, spawner)).id()
+}
+fn spawn_power_up_spawner(
+    cmds: &mut Commands,
+    assets: &GameAssets,
+    position: Vec2,
+    timer: f32,
+    table: &[powerups::TableEntry],
+) -> Entity {
+    let spawner = Spawner::PowerUp {
+        timer: Timer::from_seconds(timer, TimerMode::Repeating),
+        table: table.to_vec(),
+    };
+    let transform = Transform::from_xyz(position.x, position.y, 3.);
+    let body = spawner_body(&spawner, assets, transform);
+    cmds.spawn((body, spawner)).id()
 }
 fn spawn_rock_spawner(
     cmds: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
-    materials: &mut ResMut<Assets<ColorMaterial>>,
-) {
-    let sizes = vec![
-        (Mesh2dHandle(meshes.add(RegularPolygon::new(10.0, 5))), 10.0),
-        (Mesh2dHandle(meshes.add(RegularPolygon::new(25.0, 8))), 25.0),
-        (Mesh2dHandle(meshes.add(RegularPolygon::new(30.0, 6))), 30.0),
-        (Mesh2dHandle(meshes.add(RegularPolygon::new(40.0, 9))), 40.0),
-    ];
-    let shape = SpxCircle::new((0., 0.), 25.0);
-    cmds.spawn((
+    assets: &GameAssets,
+    position: Vec2,
+    timer: f32,
+    sizes: &[f32],
+) -> Entity {
+    let spawner = Spawner::Rock {
+        timer: Timer::from_seconds(timer, TimerMode::Repeating),
+        life: 5,
+        sizes: sizes.to_vec(),
+        meshes: rock_meshes(meshes, sizes),
+    };
+    let transform = Transform::from_xyz(position.x, position.y, 0.);
+    let body = spawner_body(&spawner, assets, transform);
+    cmds.spawn((body, spawner)).id()
+}
+fn spawner_body(
+    spawner: &Spawner,
+    assets: &GameAssets,
+    transform: Transform,
+) -> (MaterialMesh2dBundle<ColorMaterial>, Sepax) {
+    let radius = match spawner {
+        Spawner::Rock { .. } => ROCK_SPAWNER_RADIUS,
+        Spawner::Ship { .. } | Spawner::PowerUp { .. } => SHIP_SPAWNER_RADIUS,
+    };
+    let (mesh, material) = assets.spawner(spawner);
+    let shape = SpxCircle::new((0., 0.), radius);
+    (
         MaterialMesh2dBundle {
-            mesh: Mesh2dHandle(meshes.add(Circle { radius: 25.0 })),
-            material: materials.add(Color::BLUE),
+            mesh,
+            material,
+            transform,
             ..default()
         },
         Sepax {
             convex: Convex::Circle(shape),
         },
-        Spawner::Rock {
-            timer: Timer::from_seconds(5.0, TimerMode::Repeating),
-            life: 5,
-            sizes,
-        },
-    ));
-}
-fn find_vertexes(center: Vec2, angle: f32, dims: Vec2) -> (Vec2, Vec2, Vec2, Vec2) {
-    // def get_corners_from_rectangle(center: Vector, angle: float, dimensions: Vector):
-    //# create the (normalized) perpendicular vectors
-    //v1 = Vector(cos(angle), sin(angle))
-    //v2 = Vector(-v1[1], v1[0])  # rotate by 90
-
-    //# scale them appropriately by the dimensions
-    //v1 *= dimensions[0] / 2
-    //v2 *= dimensions[1] / 2
-
-    //# return the corners by moving the center of the rectangle by the vectors
-    //return [
-    //   center + v1 + v2,
-    //   center - v1 + v2,
-    //   center - v1 - v2,
-    //   center + v1 - v2,
-    //]
-    //
-    let mut v1 = Vec2::new(f32::cos(angle), f32::sin(angle));
-    let mut v2 = Vec2::new(-v1.y, v1.x);
-    v1 *= dims.x / 2.;
-    v2 *= dims.y / 2.;
-    (
-        center - v1 + v2,
-        center - v1 - v2,
-        center + v1 + v2, //
-        center + v1 - v2,
     )
 }
+fn rock_meshes(meshes: &mut ResMut<Assets<Mesh>>, radii: &[f32]) -> Vec<Mesh2dHandle> {
+    radii
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let mesh = meshes.add(RegularPolygon::new(*r, ROCK_SIDES[i % ROCK_SIDES.len()]));
+            Mesh2dHandle(mesh)
+        })
+        .collect()
+}
 fn spawn_ships(
     mut cmds: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut pool: ResMut<EntityPool>,
+    assets: Res<GameAssets>,
     spawn: Res<SpawnArea>,
     exlude: Res<SafeArea>,
+    mut sampler: ResMut<SpawnSampler>,
+    mut rng: ResMut<GameRng>,
     time: Res<Time>,
     mut query: Query<(&mut Spawner, &Transform)>,
+    player_query: Query<&Transform, (With<Player>, Without<Spawner>)>,
 ) {
+    let players: Vec<Vec2> = player_query.iter().map(|t| t.translation.xy()).collect();
     for (mut s, t) in query.iter_mut() {
         let (timer,) = match s.as_mut() {
             Spawner::Ship { timer } => (timer,),
@@ -341,209 +328,287 @@ fn spawn_ships(
         };
         timer.tick(time.delta());
         if timer.just_finished() {
-            let (left_top, left_bottom, right_top, _) =
-                find_vertexes(spawn.rect.center(), 0.0, spawn.rect.size());
-
-            let x_range = left_top.x..right_top.x;
-            let y_range = left_bottom.y..left_top.y;
-            // Ensure the spawn point is outside the exclusion zone
-            let mut rng = thread_rng();
-            let mut spawn_point = Vec2::new(rng.gen_range(x_range), rng.gen_range(y_range));
-            while exlude.rect.contains(spawn_point) {
-                let x_range = left_top.x..right_top.x;
-                let y_range = left_bottom.y..left_top.y;
-                spawn_point = Vec2::new(rng.gen_range(x_range), rng.gen_range(y_range));
-            }
-            create_ship(&mut cmds, &mut meshes, &mut materials, spawn_point);
+            // Outside the exclusion zone and away from the player
+            let spawn_point =
+                match sampler.sample(&mut rng.0, &spawn.regions, &exlude.regions, &players) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        warn!("skipping ship spawn: {}", e);
+                        continue;
+                    }
+                };
+            create_ship(&mut cmds, &mut pool, &assets, spawn_point);
         }
     }
 }
 
-fn update_ui(
-    mut cmds: Commands,
-    life_query: Query<&Health, With<Player>>,
-    mut ui_query: Query<(&mut Text, &UiLabel)>,
-) {
-    for (mut t, l) in ui_query.iter_mut() {
-        match l {
-            UiLabel::Life => t.sections[0].value = format!("Life: {}", life_query.single().0),
-            _ => continue,
-        }
-    }
-}
 fn spawn_rocks(
     mut cmds: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut pool: ResMut<EntityPool>,
+    assets: Res<GameAssets>,
     spawn: Res<SpawnArea>,
     exlude: Res<SafeArea>,
+    mut sampler: ResMut<SpawnSampler>,
+    mut rng: ResMut<GameRng>,
     time: Res<Time>,
     mut query: Query<(&mut Spawner, &Transform)>,
+    player_query: Query<&Transform, (With<Player>, Without<Spawner>)>,
 ) {
+    let players: Vec<Vec2> = player_query.iter().map(|t| t.translation.xy()).collect();
     for (mut s, t) in query.iter_mut() {
-        let (timer, sizes, life) = match s.as_mut() {
-            Spawner::Rock { timer, sizes, life } => (timer, sizes, life),
+        let (timer, sizes, meshes) = match s.as_mut() {
+            Spawner::Rock {
+                timer,
+                sizes,
+                meshes,
+                ..
+            } => (timer, sizes, meshes),
             _ => continue,
         };
         timer.tick(time.delta());
         if timer.just_finished() {
-            let (left_top, left_bottom, right_top, _) =
-                find_vertexes(spawn.rect.center(), 0.0, spawn.rect.size());
-
-            let x_range = left_top.x..right_top.x;
-            let y_range = left_bottom.y..left_top.y;
-            // Ensure the spawn point is outside the exclusion zone
-            let mut rng = thread_rng();
-            let mut spawn_point = Vec2::new(rng.gen_range(x_range), rng.gen_range(y_range));
-            while exlude.rect.contains(spawn_point) {
-                let x_range = left_top.x..right_top.x;
-                let y_range = left_bottom.y..left_top.y;
-                spawn_point = Vec2::new(rng.gen_range(x_range), rng.gen_range(y_range));
-            }
-            create_rock(&mut cmds, &mut meshes, &mut materials, &sizes, spawn_point);
+            // Outside the exclusion zone and away from the player
+            let spawn_point =
+                match sampler.sample(&mut rng.0, &spawn.regions, &exlude.regions, &players) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        warn!("skipping rock spawn: {}", e);
+                        continue;
+                    }
+                };
+            create_rock(
+                &mut cmds,
+                &mut pool,
+                &assets,
+                &mut rng.0,
+                sizes,
+                meshes,
+                spawn_point,
+                t.translation.xy(),
+            );
         }
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 struct Attack(i32);
 
-#[derive(Component)]
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 struct Health(i32);
-fn create_ship(
-    cmds: &mut Commands,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    materials: &mut ResMut<Assets<ColorMaterial>>,
-    spawn_point: Vec2,
-) {
+
+// Ignores all damage, toggled from the console.
+#[derive(Component)]
+struct Invulnerable;
+fn create_ship(cmds: &mut Commands, pool: &mut EntityPool, assets: &GameAssets, spawn_point: Vec2) {
     let transform = Transform::from_xyz(spawn_point.x, spawn_point.y, 2.);
+    let e = pool.take(cmds, PoolKind::Ship);
+    cmds.entity(e).insert((
+        ship_body(assets, transform),
+        Attack(2),
+        Ship,
+        MoveTo::Player {
+            movement_speed: 50.,
+            rotation_speed: 3.0,
+        },
+    ));
+}
+fn ship_body(
+    assets: &GameAssets,
+    transform: Transform,
+) -> (MaterialMesh2dBundle<ColorMaterial>, Sepax, Movable, Visual) {
     let shape = SpxCircle::new((0., 0.), 25.0);
-    cmds.spawn((
+    (
         MaterialMesh2dBundle {
-            mesh: Mesh2dHandle(meshes.add(Triangle2d::new(
-                Vec2::Y * 20.0,
-                Vec2::new(-20.0, -20.0),
-                Vec2::new(20.0, -20.0),
-            ))),
-            material: materials.add(Color::PINK),
+            mesh: assets.ship_mesh.clone(),
+            material: assets.ship_material.clone(),
             transform,
             ..default()
         },
-        Attack(2),
-        Ship,
         Sepax {
             convex: Convex::Circle(shape),
         },
         Movable { axes: Vec::new() },
-        MoveTo::Player {
-            movement_speed: 50.,
-            rotation_speed: 3.0,
-        },
-    ));
+        Visual::Ship,
+    )
 }
 fn create_rock(
     cmds: &mut Commands,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    materials: &mut ResMut<Assets<ColorMaterial>>,
-    sizes: &Vec<(Mesh2dHandle, f32)>,
+    pool: &mut EntityPool,
+    assets: &GameAssets,
+    rng: &mut impl Rng,
+    sizes: &[f32],
+    meshes: &[Mesh2dHandle],
     spawn_point: Vec2,
+    target: Vec2,
 ) {
-    let mut rng = thread_rng();
-    let size = rng.gen_range(0..sizes.len());
-    let mut transform = Transform::from_xyz(spawn_point.x, spawn_point.y, 2.);
+    let index = rng.gen_range(0..sizes.len());
+    let transform = Transform::from_xyz(spawn_point.x, spawn_point.y, 2.);
     info!("spawned rock at {:?}", transform);
-    let (mesh, size) = sizes.get(size).unwrap();
-    let shape = SpxCircle::new((0., 0.), *size);
-    cmds.spawn((
+    let size = sizes[index];
+    let e = pool.take(cmds, PoolKind::Rock);
+    cmds.entity(e).insert((
+        rock_body(meshes[index].clone(), assets, transform, size),
+        Rock { size },
+        Attack(1),
+        MoveTo::Point {
+            movement_speed: 50.,
+            x: target.x,
+            y: target.y,
+        },
+    ));
+}
+fn rock_body(
+    mesh: Mesh2dHandle,
+    assets: &GameAssets,
+    transform: Transform,
+    size: f32,
+) -> (MaterialMesh2dBundle<ColorMaterial>, Sepax, Movable, Visual) {
+    let shape = SpxCircle::new((0., 0.), size);
+    (
         MaterialMesh2dBundle {
-            mesh: mesh.clone(),
-            material: materials.add(Color::rgb(0.4, 0.8, 0.1)),
+            mesh,
+            material: assets.rock_material.clone(),
             transform,
             ..default()
         },
-        Rock,
-        Attack(1),
         Sepax {
             convex: Convex::Circle(shape),
         },
         Movable { axes: Vec::new() },
-        MoveTo::Point {
-            movement_speed: 50.,
-            x: 0.,
-            y: 0.,
-        },
-    ));
+        Visual::Rock,
+    )
 }
 fn create_player(
     cmds: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<ColorMaterial>>,
-) {
-    let shape = SpxCircle::new((0., 0.), 25.0);
+    slot: usize,
+    position: Vec2,
+) -> Entity {
+    let transform = Transform::from_xyz(position.x, position.y, 0.);
     cmds.spawn((
+        player_body(meshes, materials, transform),
+        Health(PLAYER_HEALTH),
+        Player {
+            movement_speed: 100.0,
+            rotation_speed: 5.0,
+            slot,
+        },
+        players::PlayerInput::default(),
+        weapon::Weapon::default(),
+    ))
+    .with_children(|p| player_parts(p, meshes, materials))
+    .id()
+}
+fn player_body(
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    transform: Transform,
+) -> (MaterialMesh2dBundle<ColorMaterial>, Sepax, Movable, Visual) {
+    let shape = SpxCircle::new((0., 0.), 25.0);
+    (
         MaterialMesh2dBundle {
             mesh: Mesh2dHandle(meshes.add(Rectangle::new(30., 50.))),
             material: materials.add(Color::rgb(0.4, 0.8, 0.1)),
+            transform,
             ..default()
         },
         Sepax {
             convex: Convex::Circle(shape),
         },
         Movable { axes: Vec::new() },
-        Health(100),
-        Player {
-            movement_speed: 100.0,
-            rotation_speed: 5.0,
-        },
-    ))
-    .with_children(|p| {
-        p.spawn(
-            (MaterialMesh2dBundle {
-                mesh: Mesh2dHandle(meshes.add(Rectangle::new(15., 10.))),
-                transform: Transform::from_xyz(25.0, 0., 1.),
-                material: materials.add(Color::BLACK),
-                ..Default::default()
-            }),
-        );
-        p.spawn(
-            (MaterialMesh2dBundle {
-                mesh: Mesh2dHandle(meshes.add(Rectangle::new(15., 10.))),
-                transform: Transform::from_xyz(-25.0, 0., 1.),
-                material: materials.add(Color::BLACK),
-                ..Default::default()
-            }),
-        );
-    });
+        Visual::Player,
+    )
+}
+fn player_parts(
+    p: &mut ChildBuilder,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+) {
+    p.spawn(
+        (MaterialMesh2dBundle {
+            mesh: Mesh2dHandle(meshes.add(Rectangle::new(15., 10.))),
+            transform: Transform::from_xyz(25.0, 0., 1.),
+            material: materials.add(Color::BLACK),
+            ..Default::default()
+        }),
+    );
+    p.spawn(
+        (MaterialMesh2dBundle {
+            mesh: Mesh2dHandle(meshes.add(Rectangle::new(15., 10.))),
+            transform: Transform::from_xyz(-25.0, 0., 1.),
+            material: materials.add(Color::BLACK),
+            ..Default::default()
+        }),
+    );
+    p.spawn(particles::thrust_emitter());
 }
 fn player_collision(
     mut cmds: Commands,
-    mut query: Query<(&Player, &mut Health, &mut Transform, &Sepax)>,
-    targets: Query<(Entity, &Attack, &Sepax), Without<Player>>,
+    mut query: Query<(
+        Entity,
+        &Player,
+        &mut Health,
+        &mut Transform,
+        &Sepax,
+        Has<powerups::Shielded>,
+        Has<Invulnerable>,
+    )>,
+    targets: Query<(Entity, &Attack, &Sepax, &Transform, Option<&Rock>), Without<Player>>,
+    mut trauma: EventWriter<camera::Trauma>,
+    mut hits: EventWriter<progress::PlayerHit>,
+    mut bursts: EventWriter<particles::Burst>,
+    mut pool: ResMut<EntityPool>,
 ) {
-    for (player, mut health, transform, bbox) in query.iter_mut() {
-        for (e, atk, targets) in targets.iter() {
+    for (player_entity, player, mut health, transform, bbox, shielded, invulnerable) in
+        query.iter_mut()
+    {
+        for (e, atk, targets, target_transform, rock) in targets.iter() {
             if sat_overlap(targets.shape(), bbox.shape()) {
+                let kind = if rock.is_some() {
+                    PoolKind::Rock
+                } else {
+                    PoolKind::Ship
+                };
+                pool.release(&mut cmds, kind, e);
+                bursts.send(particles::Burst::enemy(
+                    rock.map_or(weapon::Enemy::Ship, |r| weapon::Enemy::Rock {
+                        size: r.size,
+                    }),
+                    target_transform.translation.xy(),
+                ));
+                if shielded || invulnerable {
+                    continue;
+                }
                 health.0 -= atk.0;
-                cmds.entity(e).despawn();
+                trauma.send(camera::Trauma(0.25 * atk.0 as f32));
+                hits.send(progress::PlayerHit {
+                    player: player_entity,
+                    damage: atk.0,
+                });
             }
         }
     }
 }
 fn rock_despawn(
     mut cmds: Commands,
-    query: Query<(Entity, &Sepax), With<Rock>>,
+    query: Query<(Entity, &Sepax, &Transform, &Rock)>,
     mut targets: Query<(&Sepax, &mut Spawner), Without<Rock>>,
+    mut bursts: EventWriter<particles::Burst>,
+    mut pool: ResMut<EntityPool>,
 ) {
-    for (e, s) in query.iter() {
+    for (e, s, t, rock) in query.iter() {
         for (targets, mut spawner) in targets.iter_mut() {
-            if let Spawner::Rock {
-                timer: _,
-                sizes: _,
-                life: _,
-            } = spawner.as_mut()
-            {
+            if let Spawner::Rock { .. } = spawner.as_mut() {
                 if sat_overlap(targets.shape(), s.shape()) {
-                    cmds.entity(e).despawn();
+                    pool.release(&mut cmds, PoolKind::Rock, e);
+                    bursts.send(particles::Burst::enemy(
+                        weapon::Enemy::Rock { size: rock.size },
+                        t.translation.xy(),
+                    ));
+                    break;
                 }
             } else {
                 continue;
@@ -553,14 +618,21 @@ fn rock_despawn(
 }
 fn ship_despawn(
     mut cmds: Commands,
-    query: Query<(Entity, &Sepax), With<Ship>>,
+    query: Query<(Entity, &Sepax, &Transform), With<Ship>>,
     mut targets: Query<(&Sepax, &mut Spawner), Without<Ship>>,
+    mut bursts: EventWriter<particles::Burst>,
+    mut pool: ResMut<EntityPool>,
 ) {
-    for (e, s) in query.iter() {
+    for (e, s, t) in query.iter() {
         for (targets, mut spawner) in targets.iter_mut() {
             if let Spawner::Ship { timer: _ } = spawner.as_mut() {
                 if sat_overlap(targets.shape(), s.shape()) {
-                    cmds.entity(e).despawn();
+                    pool.release(&mut cmds, PoolKind::Ship, e);
+                    bursts.send(particles::Burst::new(
+                        particles::Preset::ShipExplosion,
+                        t.translation.xy(),
+                    ));
+                    break;
                 }
             } else {
                 continue;
@@ -570,25 +642,13 @@ fn ship_despawn(
 }
 
 fn player_movement(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
     time: Res<Time>,
     play: Res<PlayArea>,
-    mut query: Query<(&Player, &mut Transform)>,
+    mut query: Query<(&Player, &players::PlayerInput, &mut Transform)>,
 ) {
-    for (player, mut transform) in &mut query {
-        let mut rotation_factor = 0.0;
-        let mut movement_factor = 0.0;
-        if keyboard_input.pressed(KeyCode::KeyA) {
-            rotation_factor += 1.0;
-        }
-
-        if keyboard_input.pressed(KeyCode::KeyD) {
-            rotation_factor -= 1.0;
-        }
-
-        if keyboard_input.pressed(KeyCode::KeyW) {
-            movement_factor += 1.0;
-        }
+    for (player, input, mut transform) in &mut query {
+        let rotation_factor = input.turn;
+        let movement_factor = input.thrust;
 
         // update the ship rotation around the Z axis (perpendicular to the 2D plane of the screen)
         transform.rotate_z(rotation_factor * player.rotation_speed * time.delta_seconds());
@@ -602,13 +662,13 @@ fn player_movement(
         // create the change in translation using the new movement direction and distance
         let translation_delta = movement_direction * movement_distance;
         // update the ship translation with our new translation delta
+        let previous = transform.translation.xy();
         transform.translation += translation_delta;
 
         // bound the ship within the invisible level bounds
-        transform.translation = transform
-            .translation
-            .min(Vec3::from((play.rect.half_size(), 0.0)))
-            .max(Vec3::from((-play.rect.half_size(), 0.0)));
+        let bounded = play.regions.confine(previous, transform.translation.xy());
+        transform.translation.x = bounded.x;
+        transform.translation.y = bounded.y;
     }
 }
 fn rotate_to_player(
@@ -616,9 +676,7 @@ fn rotate_to_player(
     mut query: Query<(&MoveTo, &mut Transform), Without<Player>>,
     player_query: Query<&Transform, With<Player>>,
 ) {
-    let player_transform = player_query.single();
-    // get the player translation in 2D
-    let player_translation = player_transform.translation.xy();
+    let players: Vec<Vec2> = player_query.iter().map(|t| t.translation.xy()).collect();
     for (config, mut enemy_transform) in &mut query {
         let (rotation_speed, movment_speed) = match config {
             MoveTo::Player {
@@ -628,6 +686,12 @@ fn rotate_to_player(
             _ => continue,
         };
 
+        // Chase whoever is closest, players out of lives are despawned.
+        let Some(player_translation) = players::nearest(&players, enemy_transform.translation.xy())
+        else {
+            continue;
+        };
+
         let enemy_forward = (enemy_transform.rotation * Vec3::Y).xy();
 
         let to_player = (player_translation - enemy_transform.translation.xy()).normalize();
@@ -0,0 +1,696 @@
+use bevy::{app::AppExit, input::keyboard::KeyboardInput, prelude::*, window::ReceivedCharacter};
+
+use crate::{
+    leaderboard::{self, BoardKey, BoardView, Leaderboard, NameEntry},
+    players::PlayerSetup,
+    progress::{Difficulty, GameMode, GameOver, NewGame, Score},
+    settings::{Binding, KeyBindings, Settings, VOLUME_STEP},
+    view::ScaleMode,
+};
+
+const NORMAL: Color = Color::rgb(0.25, 0.25, 0.3);
+const FOCUSED: Color = Color::rgb(0.45, 0.45, 0.6);
+// Left stick deflection that counts as a menu step.
+const STICK_STEP: f32 = 0.5;
+
+#[derive(States, Default, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum GameState {
+    #[default]
+    MainMenu,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+// The screen currently shown, settings can be opened from several states.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuPage {
+    Main,
+    Pause,
+    GameOver,
+    NameEntry,
+    Settings(GameState),
+    Leaderboard(GameState),
+}
+
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+enum MenuAction {
+    Start,
+    Mode,
+    Difficulty,
+    Players,
+    Lives,
+    HighScores,
+    Settings,
+    Quit,
+    Resume,
+    Restart,
+    QuitToMenu,
+    Back,
+    SubmitName,
+    BoardMode,
+    BoardDifficulty,
+    MasterVolume,
+    EffectsVolume,
+    Fullscreen,
+    Vsync,
+    Palette,
+    ScaleMode,
+    Minimap,
+    Fps,
+    Bind(Binding),
+}
+
+#[derive(Component)]
+struct MenuRoot;
+#[derive(Component)]
+struct MenuButton(usize);
+
+#[derive(Resource, Default)]
+struct MenuFocus(usize);
+
+// Set while waiting for the key to bind to an action.
+#[derive(Resource, Default)]
+struct Rebinding(Option<Binding>);
+
+pub struct MenuPlugin;
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<GameState>()
+            .insert_resource(MenuPage::Main)
+            .init_resource::<MenuFocus>()
+            .init_resource::<Rebinding>()
+            .add_systems(
+                Update,
+                (
+                    sync_time.run_if(state_changed::<GameState>),
+                    sync_page.run_if(state_changed::<GameState>),
+                    capture_binding,
+                    enter_name.after(capture_binding),
+                    toggle_pause.after(enter_name).run_if(not_rebinding),
+                    game_over.run_if(on_event::<GameOver>()),
+                    navigate.after(enter_name).run_if(not_rebinding),
+                    hover.after(navigate),
+                    activate.after(hover).run_if(not_rebinding),
+                    build_menu.after(activate).after(sync_page),
+                    highlight.after(build_menu),
+                ),
+            );
+    }
+}
+
+fn not_rebinding(rebinding: Res<Rebinding>) -> bool {
+    rebinding.0.is_none()
+}
+
+// Gameplay runs on virtual time, so it only advances while playing, and not
+// while the editor holds it still.
+fn sync_time(
+    state: Res<State<GameState>>,
+    #[cfg(feature = "editor")] editor: Res<State<crate::editor::EditorState>>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    #[cfg(feature = "editor")]
+    if *editor.get() == crate::editor::EditorState::On {
+        time.pause();
+        return;
+    }
+    if *state.get() == GameState::Playing {
+        time.unpause();
+    } else {
+        time.pause();
+    }
+}
+
+fn sync_page(state: Res<State<GameState>>, entry: Res<NameEntry>, mut page: ResMut<MenuPage>) {
+    *page = match state.get() {
+        GameState::MainMenu => MenuPage::Main,
+        GameState::Paused => MenuPage::Pause,
+        // A score that made the board gets its name first.
+        GameState::GameOver if entry.pending.is_some() => MenuPage::NameEntry,
+        GameState::GameOver => MenuPage::GameOver,
+        GameState::Playing => return,
+    };
+}
+
+fn game_over(mut events: EventReader<GameOver>, mut next: ResMut<NextState<GameState>>) {
+    events.clear();
+    next.set(GameState::GameOver);
+}
+
+fn toggle_pause(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepad_input: Res<ButtonInput<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    state: Res<State<GameState>>,
+    mut page: ResMut<MenuPage>,
+    mut next: ResMut<NextState<GameState>>,
+) {
+    let start = gamepads
+        .iter()
+        .any(|g| gamepad_input.just_pressed(GamepadButton::new(g, GamepadButtonType::Start)));
+    if !keyboard_input.just_pressed(KeyCode::Escape) && !start {
+        return;
+    }
+    match (state.get(), *page) {
+        (GameState::Playing, _) => next.set(GameState::Paused),
+        (_, MenuPage::Settings(_) | MenuPage::Leaderboard(_)) => *page = back_from(*state.get()),
+        (GameState::Paused, _) => next.set(GameState::Playing),
+        _ => {}
+    }
+}
+
+fn back_from(state: GameState) -> MenuPage {
+    match state {
+        GameState::Paused => MenuPage::Pause,
+        GameState::GameOver => MenuPage::GameOver,
+        _ => MenuPage::Main,
+    }
+}
+
+fn entries(
+    page: MenuPage,
+    mode: GameMode,
+    difficulty: Difficulty,
+    setup: PlayerSetup,
+    view: &BoardView,
+    settings: &Settings,
+    rebinding: Option<Binding>,
+) -> Vec<(String, MenuAction)> {
+    let on_off = |b: bool| if b { "on" } else { "off" };
+    let percent = |v: f32| format!("{:.0}%", v * 100.0);
+    match page {
+        MenuPage::Main => vec![
+            ("Start".into(), MenuAction::Start),
+            (format!("Mode: {}", mode.label()), MenuAction::Mode),
+            (
+                format!("Difficulty: {}", difficulty.label()),
+                MenuAction::Difficulty,
+            ),
+            (format!("Players: {}", setup.count), MenuAction::Players),
+            (format!("Lives: {}", setup.lives.label()), MenuAction::Lives),
+            ("High scores".into(), MenuAction::HighScores),
+            ("Settings".into(), MenuAction::Settings),
+            ("Quit".into(), MenuAction::Quit),
+        ],
+        MenuPage::Pause => vec![
+            ("Resume".into(), MenuAction::Resume),
+            ("Restart".into(), MenuAction::Restart),
+            ("Settings".into(), MenuAction::Settings),
+            ("Quit to menu".into(), MenuAction::QuitToMenu),
+        ],
+        MenuPage::GameOver => vec![
+            ("Restart".into(), MenuAction::Restart),
+            ("High scores".into(), MenuAction::HighScores),
+            ("Quit to menu".into(), MenuAction::QuitToMenu),
+        ],
+        MenuPage::NameEntry => vec![("Done".into(), MenuAction::SubmitName)],
+        MenuPage::Leaderboard(_) => vec![
+            (
+                format!("Mode: {}", view.key.mode.label()),
+                MenuAction::BoardMode,
+            ),
+            (
+                format!("Difficulty: {}", view.key.difficulty.label()),
+                MenuAction::BoardDifficulty,
+            ),
+            ("Back".into(), MenuAction::Back),
+        ],
+        MenuPage::Settings(_) => {
+            let mut entries = vec![
+                (
+                    format!("Volume: {}", percent(settings.master_volume)),
+                    MenuAction::MasterVolume,
+                ),
+                (
+                    format!("Effects: {}", percent(settings.effects_volume)),
+                    MenuAction::EffectsVolume,
+                ),
+                (
+                    format!("Fullscreen: {}", on_off(settings.fullscreen)),
+                    MenuAction::Fullscreen,
+                ),
+                (
+                    format!("VSync: {}", on_off(settings.vsync)),
+                    MenuAction::Vsync,
+                ),
+                (
+                    format!("Palette: {}", settings.palette.name()),
+                    MenuAction::Palette,
+                ),
+                (
+                    format!("Display: {}", settings.scale_mode.name()),
+                    MenuAction::ScaleMode,
+                ),
+                (
+                    format!("Minimap: {}", on_off(settings.minimap)),
+                    MenuAction::Minimap,
+                ),
+                (
+                    format!("FPS: {}", on_off(settings.show_fps)),
+                    MenuAction::Fps,
+                ),
+            ];
+            // Only player one rebinds here, the others are set in the settings file.
+            for binding in KeyBindings::ALL {
+                let key = if rebinding == Some(binding) {
+                    "press a key".to_string()
+                } else {
+                    format!("{:?}", settings.controls[0].keys.get(binding))
+                };
+                entries.push((
+                    format!("Key {}: {}", binding.name(), key),
+                    MenuAction::Bind(binding),
+                ));
+            }
+            entries.push(("Back".into(), MenuAction::Back));
+            entries
+        }
+    }
+}
+
+// Rebuilt whenever the page or one of the settings it shows changes.
+fn build_menu(
+    mut cmds: Commands,
+    state: Res<State<GameState>>,
+    page: Res<MenuPage>,
+    mode: Res<GameMode>,
+    difficulty: Res<Difficulty>,
+    setup: Res<PlayerSetup>,
+    settings: Res<Settings>,
+    rebinding: Res<Rebinding>,
+    score: Res<Score>,
+    leaderboard: Res<Leaderboard>,
+    view: Res<BoardView>,
+    entry: Res<NameEntry>,
+    mut focus: ResMut<MenuFocus>,
+    roots: Query<Entity, With<MenuRoot>>,
+) {
+    let labels_changed = mode.is_changed()
+        || difficulty.is_changed()
+        || setup.is_changed()
+        || settings.is_changed()
+        || rebinding.is_changed()
+        || view.is_changed()
+        || entry.is_changed();
+    if !state.is_changed() && !page.is_changed() && !labels_changed {
+        return;
+    }
+    for e in roots.iter() {
+        cmds.entity(e).despawn_recursive();
+    }
+    if *state.get() == GameState::Playing {
+        return;
+    }
+    if page.is_changed() {
+        focus.0 = 0;
+    }
+    let title = match *page {
+        MenuPage::Main => "game".to_string(),
+        MenuPage::Pause => "Paused".to_string(),
+        MenuPage::GameOver => format!("Game over\nScore {}", score.points),
+        MenuPage::NameEntry => "New high score".to_string(),
+        MenuPage::Settings(_) => "Settings".to_string(),
+        MenuPage::Leaderboard(_) => "High scores".to_string(),
+    };
+    // Shown between the title and the buttons.
+    let body = match *page {
+        MenuPage::NameEntry => Some(format!(
+            "Score {}\n\nName: {}",
+            score.points,
+            entry.display()
+        )),
+        MenuPage::Leaderboard(_) => Some(board_text(&leaderboard, &view)),
+        _ => None,
+    };
+    let entries = entries(
+        *page,
+        *mode,
+        *difficulty,
+        *setup,
+        &view,
+        &settings,
+        rebinding.0,
+    );
+    // The settings page is long, keep it on screen.
+    let (padding, font_size) = match *page {
+        MenuPage::Settings(_) => (4.0, 22.0),
+        _ => (8.0, 28.0),
+    };
+    cmds.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(8.0),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            z_index: ZIndex::Global(10),
+            ..default()
+        },
+        MenuRoot,
+    ))
+    .with_children(|p| {
+        p.spawn(
+            TextBundle::from_section(
+                title,
+                TextStyle {
+                    font_size: 64.0,
+                    ..default()
+                },
+            )
+            .with_text_justify(JustifyText::Center),
+        );
+        if let Some(body) = body {
+            p.spawn(
+                TextBundle::from_section(
+                    body,
+                    TextStyle {
+                        font_size: 24.0,
+                        ..default()
+                    },
+                )
+                .with_text_justify(JustifyText::Center),
+            );
+        }
+        for (i, (label, action)) in entries.into_iter().enumerate() {
+            p.spawn((
+                ButtonBundle {
+                    style: Style {
+                        width: Val::Px(280.0),
+                        padding: UiRect::all(Val::Px(padding)),
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    background_color: NORMAL.into(),
+                    ..default()
+                },
+                MenuButton(i),
+                action,
+            ))
+            .with_children(|p| {
+                p.spawn(TextBundle::from_section(
+                    label,
+                    TextStyle {
+                        font_size,
+                        ..default()
+                    },
+                ));
+            });
+        }
+    });
+}
+
+fn navigate(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepad_input: Res<ButtonInput<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+    buttons: Query<&MenuButton>,
+    mut focus: ResMut<MenuFocus>,
+    mut stick_held: Local<bool>,
+) {
+    let count = buttons.iter().count();
+    if count == 0 {
+        return;
+    }
+    let pad = |button| {
+        gamepads
+            .iter()
+            .any(|g| gamepad_input.just_pressed(GamepadButton::new(g, button)))
+    };
+    let stick = gamepads
+        .iter()
+        .filter_map(|g| axes.get(GamepadAxis::new(g, GamepadAxisType::LeftStickY)))
+        .find(|y| y.abs() > STICK_STEP);
+    // The stick only steps once per push.
+    let stick_step = match stick {
+        Some(y) if !*stick_held => Some(y),
+        _ => None,
+    };
+    *stick_held = stick.is_some();
+    let up = keyboard_input.any_just_pressed([KeyCode::ArrowUp, KeyCode::KeyW])
+        || pad(GamepadButtonType::DPadUp)
+        || stick_step.is_some_and(|y| y > 0.0);
+    let down = keyboard_input.any_just_pressed([KeyCode::ArrowDown, KeyCode::KeyS])
+        || pad(GamepadButtonType::DPadDown)
+        || stick_step.is_some_and(|y| y < 0.0);
+    if up {
+        focus.0 = (focus.0 + count - 1) % count;
+    }
+    if down {
+        focus.0 = (focus.0 + 1) % count;
+    }
+}
+
+fn hover(
+    buttons: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mut focus: ResMut<MenuFocus>,
+) {
+    for (interaction, button) in buttons.iter() {
+        if *interaction != Interaction::None {
+            focus.0 = button.0;
+        }
+    }
+}
+
+fn activate(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepad_input: Res<ButtonInput<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    focus: Res<MenuFocus>,
+    buttons: Query<(Ref<Interaction>, &MenuButton, &MenuAction)>,
+    state: Res<State<GameState>>,
+    mut page: ResMut<MenuPage>,
+    mut next: ResMut<NextState<GameState>>,
+    mut mode: ResMut<GameMode>,
+    mut difficulty: ResMut<Difficulty>,
+    mut setup: ResMut<PlayerSetup>,
+    mut settings: ResMut<Settings>,
+    mut rebinding: ResMut<Rebinding>,
+    (mut leaderboard, mut entry, mut view): (
+        ResMut<Leaderboard>,
+        ResMut<NameEntry>,
+        ResMut<BoardView>,
+    ),
+    mut new_game: EventWriter<NewGame>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let pad = |button| {
+        gamepads
+            .iter()
+            .any(|g| gamepad_input.just_pressed(GamepadButton::new(g, button)))
+    };
+    // Left and right nudge the focused volume instead of cycling it.
+    let left = keyboard_input.just_pressed(KeyCode::ArrowLeft) || pad(GamepadButtonType::DPadLeft);
+    let right =
+        keyboard_input.just_pressed(KeyCode::ArrowRight) || pad(GamepadButtonType::DPadRight);
+    let nudge = if left {
+        -VOLUME_STEP
+    } else if right {
+        VOLUME_STEP
+    } else {
+        0.0
+    };
+    if nudge != 0.0 {
+        let focused = buttons.iter().find(|(_, b, _)| b.0 == focus.0);
+        match focused.map(|(_, _, action)| *action) {
+            Some(MenuAction::MasterVolume) => {
+                settings.master_volume = (settings.master_volume + nudge).clamp(0.0, 1.0)
+            }
+            Some(MenuAction::EffectsVolume) => {
+                settings.effects_volume = (settings.effects_volume + nudge).clamp(0.0, 1.0)
+            }
+            _ => {}
+        }
+    }
+    let confirm = keyboard_input.any_just_pressed([KeyCode::Enter, KeyCode::Space])
+        || pad(GamepadButtonType::South);
+    let action = buttons
+        .iter()
+        .find(|(interaction, button, _)| {
+            let clicked = interaction.is_changed() && **interaction == Interaction::Pressed;
+            clicked || (confirm && button.0 == focus.0)
+        })
+        .map(|(_, _, action)| *action);
+    let Some(action) = action else {
+        return;
+    };
+    match action {
+        MenuAction::Start | MenuAction::Restart => {
+            new_game.send(NewGame);
+            next.set(GameState::Playing);
+        }
+        MenuAction::Resume => next.set(GameState::Playing),
+        MenuAction::QuitToMenu => next.set(GameState::MainMenu),
+        MenuAction::Quit => {
+            exit.send(AppExit);
+        }
+        MenuAction::Mode => *mode = mode.next(),
+        MenuAction::Difficulty => *difficulty = difficulty.next(),
+        MenuAction::Players => setup.count = setup.next_count(),
+        MenuAction::Lives => setup.lives = setup.lives.next(),
+        MenuAction::HighScores => {
+            *view = BoardView {
+                key: BoardKey {
+                    mode: *mode,
+                    difficulty: *difficulty,
+                },
+                highlight: None,
+            };
+            *page = MenuPage::Leaderboard(*state.get());
+        }
+        MenuAction::SubmitName => {
+            let placed = leaderboard::submit(&mut leaderboard, &mut entry);
+            *view = BoardView {
+                key: placed.map_or(view.key, |(key, _)| key),
+                highlight: placed.map(|(_, rank)| rank),
+            };
+            *page = MenuPage::Leaderboard(*state.get());
+        }
+        MenuAction::BoardMode => {
+            view.key.mode = view.key.mode.next();
+            view.highlight = None;
+        }
+        MenuAction::BoardDifficulty => {
+            view.key.difficulty = view.key.difficulty.next();
+            view.highlight = None;
+        }
+        MenuAction::Settings => *page = MenuPage::Settings(*state.get()),
+        MenuAction::Back => *page = back_from(*state.get()),
+        MenuAction::MasterVolume => settings.master_volume = cycle(settings.master_volume),
+        MenuAction::EffectsVolume => settings.effects_volume = cycle(settings.effects_volume),
+        MenuAction::Fullscreen => settings.fullscreen = !settings.fullscreen,
+        MenuAction::Vsync => settings.vsync = !settings.vsync,
+        MenuAction::Palette => settings.palette = settings.palette.next(),
+        MenuAction::ScaleMode => {
+            settings.scale_mode = match settings.scale_mode {
+                ScaleMode::Letterbox => ScaleMode::Expand,
+                ScaleMode::Expand => ScaleMode::Letterbox,
+            }
+        }
+        MenuAction::Minimap => settings.minimap = !settings.minimap,
+        MenuAction::Fps => settings.show_fps = !settings.show_fps,
+        MenuAction::Bind(binding) => rebinding.0 = Some(binding),
+    }
+}
+
+fn board_text(leaderboard: &Leaderboard, view: &BoardView) -> String {
+    let entries = leaderboard.entries(view.key);
+    if entries.is_empty() {
+        return "No scores yet".into();
+    }
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            let marker = if view.highlight == Some(i) { "> " } else { "" };
+            format!(
+                "{marker}{}. {}  {}  wave {}",
+                i + 1,
+                e.name,
+                e.score,
+                e.wave
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Steps up and wraps back to silent after full volume.
+fn cycle(volume: f32) -> f32 {
+    let next = volume + VOLUME_STEP;
+    if next > 1.0 + f32::EPSILON {
+        0.0
+    } else {
+        next.min(1.0)
+    }
+}
+
+// Takes the next key press for the binding being changed. Escape cancels, and
+// the key is consumed so it does not also navigate the menu.
+fn capture_binding(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: ResMut<Settings>,
+) {
+    let Some(binding) = rebinding.0 else {
+        return;
+    };
+    let Some(key) = keyboard_input.get_just_pressed().next().copied() else {
+        return;
+    };
+    keyboard_input.clear_just_pressed(key);
+    if key != KeyCode::Escape {
+        settings.controls[0].keys.set(binding, key);
+    }
+    rebinding.0 = None;
+}
+
+// Typing goes to the name and the keys it used are consumed, so letters like
+// W and S don't also move the menu. Gamepads step the last letter with up and
+// down, add one with right and remove it with left or B.
+fn enter_name(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    gamepad_input: Res<ButtonInput<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    page: Res<MenuPage>,
+    mut keys: EventReader<KeyboardInput>,
+    mut chars: EventReader<ReceivedCharacter>,
+    mut entry: ResMut<NameEntry>,
+) {
+    // Cleared otherwise so keys from the game don't end up in the name.
+    if *page != MenuPage::NameEntry {
+        keys.clear();
+        chars.clear();
+        return;
+    }
+    for c in chars.read().flat_map(|c| c.char.chars()) {
+        entry.type_char(c);
+    }
+    // Key events rather than `just_pressed` so a held backspace repeats.
+    for key in keys.read() {
+        if key.state.is_pressed() && key.key_code == KeyCode::Backspace {
+            entry.backspace();
+        }
+    }
+    let pad = |button| {
+        gamepads
+            .iter()
+            .any(|g| gamepad_input.just_pressed(GamepadButton::new(g, button)))
+    };
+    if pad(GamepadButtonType::DPadUp) {
+        entry.cycle(1);
+    }
+    if pad(GamepadButtonType::DPadDown) {
+        entry.cycle(-1);
+    }
+    if pad(GamepadButtonType::DPadRight) {
+        entry.type_char('A');
+    }
+    if pad(GamepadButtonType::DPadLeft) || pad(GamepadButtonType::East) {
+        entry.backspace();
+    }
+    let typed: Vec<KeyCode> = keyboard_input
+        .get_just_pressed()
+        .filter(|k| !matches!(k, KeyCode::Enter | KeyCode::NumpadEnter))
+        .copied()
+        .collect();
+    for key in typed {
+        keyboard_input.clear_just_pressed(key);
+    }
+}
+
+fn highlight(focus: Res<MenuFocus>, mut buttons: Query<(&MenuButton, &mut BackgroundColor)>) {
+    for (button, mut color) in buttons.iter_mut() {
+        let target = if button.0 == focus.0 { FOCUSED } else { NORMAL };
+        if color.0 != target {
+            color.0 = target;
+        }
+    }
+}
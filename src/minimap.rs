@@ -0,0 +1,212 @@
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{ScalingMode, Viewport},
+        view::RenderLayers,
+    },
+};
+
+use crate::{
+    areas::{PlayArea, SafeArea, SpawnArea},
+    players::{self, MAX_PLAYERS},
+    settings::Settings,
+    MainCamera, Player, Rock, Ship, Spawner,
+};
+
+// Only the minimap camera renders this layer, so its markers stay off the main view.
+const MINIMAP_LAYER: u8 = 1;
+const MARKER_PX: f32 = 3.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct Minimap {
+    pub visible: bool,
+    pub corner: Corner,
+    // Height of the map as a fraction of the main view, the width follows the world.
+    pub size: f32,
+    // Gap to the edges of the main view in physical pixels.
+    pub margin: u32,
+}
+impl Default for Minimap {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            corner: Corner::default(),
+            size: 0.25,
+            margin: 8,
+        }
+    }
+}
+
+#[derive(Default, Reflect, GizmoConfigGroup)]
+struct MinimapGizmos;
+
+#[derive(Component)]
+struct MinimapCamera;
+
+pub struct MinimapPlugin;
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Minimap>()
+            .init_gizmo_group::<MinimapGizmos>()
+            .add_systems(Startup, (spawn_minimap_camera, configure_gizmos))
+            .add_systems(Update, toggle_minimap)
+            .add_systems(PostUpdate, (place_minimap, draw_minimap));
+    }
+}
+
+fn configure_gizmos(mut store: ResMut<GizmoConfigStore>) {
+    let (config, _) = store.config_mut::<MinimapGizmos>();
+    config.render_layers = RenderLayers::layer(MINIMAP_LAYER);
+}
+
+fn spawn_minimap_camera(mut cmds: Commands) {
+    cmds.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                // Above every player view.
+                order: MAX_PLAYERS as isize,
+                clear_color: ClearColorConfig::Custom(Color::rgb(0.05, 0.05, 0.08)),
+                ..default()
+            },
+            ..default()
+        },
+        RenderLayers::layer(MINIMAP_LAYER),
+        MinimapCamera,
+    ));
+}
+
+fn toggle_minimap(keyboard_input: Res<ButtonInput<KeyCode>>, mut minimap: ResMut<Minimap>) {
+    if keyboard_input.just_pressed(KeyCode::KeyM) {
+        minimap.visible = !minimap.visible;
+    }
+}
+
+// Everything the minimap shows, the spawn area usually reaches past the play area.
+fn world_bounds(spawn: &SpawnArea, play: &PlayArea) -> Option<Rect> {
+    match (spawn.regions.bounds(), play.regions.bounds()) {
+        (Some(a), Some(b)) => Some(a.union(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn place_minimap(
+    minimap: Res<Minimap>,
+    spawn: Res<SpawnArea>,
+    play: Res<PlayArea>,
+    main: Query<&Camera, (With<MainCamera>, Without<MinimapCamera>)>,
+    mut cameras: Query<
+        (&mut Camera, &mut Transform, &mut OrthographicProjection),
+        With<MinimapCamera>,
+    >,
+) {
+    let view = main
+        .get_single()
+        .ok()
+        .and_then(|c| c.physical_viewport_rect());
+    let bounds = world_bounds(&spawn, &play);
+    for (mut camera, mut transform, mut projection) in cameras.iter_mut() {
+        let (Some(view), Some(bounds), true) = (view, bounds, minimap.visible) else {
+            camera.is_active = false;
+            continue;
+        };
+        let height = (view.height() as f32 * minimap.size).max(1.0);
+        let size = UVec2::new(
+            (height * bounds.width() / bounds.height()).min(view.width() as f32) as u32,
+            height as u32,
+        )
+        .max(UVec2::ONE);
+        let margin = UVec2::splat(minimap.margin);
+        let left = matches!(minimap.corner, Corner::TopLeft | Corner::BottomLeft);
+        let top = matches!(minimap.corner, Corner::TopLeft | Corner::TopRight);
+        let x = if left {
+            view.min.x + margin.x
+        } else {
+            view.max.x.saturating_sub(size.x + margin.x)
+        };
+        let y = if top {
+            view.min.y + margin.y
+        } else {
+            view.max.y.saturating_sub(size.y + margin.y)
+        };
+        camera.is_active = true;
+        camera.viewport = Some(Viewport {
+            physical_position: UVec2::new(x, y),
+            physical_size: size,
+            ..default()
+        });
+        projection.scaling_mode = ScalingMode::Fixed {
+            width: bounds.width(),
+            height: bounds.height(),
+        };
+        let center = bounds.center();
+        transform.translation.x = center.x;
+        transform.translation.y = center.y;
+    }
+}
+
+fn draw_minimap(
+    mut gizmos: Gizmos<MinimapGizmos>,
+    minimap: Res<Minimap>,
+    spawn: Res<SpawnArea>,
+    safe: Res<SafeArea>,
+    play: Res<PlayArea>,
+    cameras: Query<&Camera, With<MinimapCamera>>,
+    settings: Res<Settings>,
+    player: Query<(&Transform, &Player)>,
+    rocks: Query<(&Transform, &Rock)>,
+    ships: Query<&Transform, With<Ship>>,
+    spawners: Query<(&Transform, &Spawner)>,
+) {
+    if !minimap.visible {
+        return;
+    }
+    let (Some(bounds), Some(px)) = (
+        world_bounds(&spawn, &play),
+        cameras
+            .get_single()
+            .ok()
+            .and_then(|c| c.physical_viewport_size()),
+    ) else {
+        return;
+    };
+    // Markers keep a fixed on-screen size whatever the world size is.
+    let marker = MARKER_PX * bounds.height() / px.y.max(1) as f32;
+    spawn.regions.draw(&mut gizmos, Color::RED);
+    safe.regions.draw(&mut gizmos, Color::BLUE);
+    play.regions.draw(&mut gizmos, Color::WHITE);
+    for (t, spawner) in spawners.iter() {
+        let color = match spawner {
+            Spawner::Rock { .. } => Color::CYAN,
+            Spawner::Ship { .. } => Color::ORANGE_RED,
+            Spawner::PowerUp { .. } => Color::GOLD,
+        };
+        gizmos.rect_2d(t.translation.xy(), 0., Vec2::splat(marker * 3.), color);
+    }
+    for (t, rock) in rocks.iter() {
+        let radius = marker.max(rock.size);
+        gizmos.circle_2d(t.translation.xy(), radius, Color::rgb(0.4, 0.8, 0.1));
+    }
+    for t in ships.iter() {
+        gizmos.circle_2d(t.translation.xy(), marker * 1.5, Color::PINK);
+    }
+    let colors = settings.palette.colors();
+    for (t, p) in player.iter() {
+        // Player one's palette colour is the rocks' colour, so it keeps standing out in yellow.
+        let color = match p.slot {
+            0 => Color::YELLOW,
+            slot => players::color(slot, &colors),
+        };
+        let forward = (t.rotation * Vec3::Y).xy() * marker * 4.;
+        gizmos.circle_2d(t.translation.xy(), marker * 2., color);
+        gizmos.line_2d(t.translation.xy(), t.translation.xy() + forward, color);
+    }
+}
@@ -0,0 +1,927 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    fmt,
+    hash::{Hash, Hasher},
+    io,
+    net::{SocketAddr, UdpSocket},
+    time::Duration,
+};
+
+use bevy::{ecs::query::QueryFilter, prelude::*};
+use rand::Rng;
+
+use crate::{
+    menu::GameState,
+    players::{self, LivesPool, PlayerInput, PlayerSetup},
+    powerups::Pickup,
+    progress::{Difficulty, GameMode, NewGame, Score},
+    sampling::GameRng,
+    settings::Settings,
+    view::{PlayField, ScaleMode},
+    weapon::Projectile,
+    Health, Player, Rock, Ship,
+};
+
+pub const NET_PLAYERS: usize = 2;
+const DEFAULT_DELAY: u32 = 3;
+const MAX_DELAY: u32 = 30;
+// Same rate as the fixed schedule in the game.
+const TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// Real time allowed to pile up while waiting, so a stall isn't followed by a burst.
+const MAX_BUDGET: Duration = Duration::from_nanos(4_000_000_000 / 60);
+const HELLO_INTERVAL: Duration = Duration::from_millis(250);
+// Silence after which the other player is considered gone.
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+// Ticks between state hashes.
+const HASH_INTERVAL: u64 = 30;
+const MAX_DATAGRAM: usize = 1400;
+
+// Quantised so both peers feed the simulation exactly the same values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NetInput {
+    pub turn: i8,
+    pub thrust: bool,
+    pub fire: bool,
+}
+impl NetInput {
+    pub fn quantise(input: &PlayerInput) -> Self {
+        Self {
+            turn: (input.turn.clamp(-1.0, 1.0) * 127.0).round() as i8,
+            thrust: input.thrust > 0.5,
+            fire: input.fire,
+        }
+    }
+    pub fn input(&self) -> PlayerInput {
+        PlayerInput {
+            turn: self.turn as f32 / 127.0,
+            thrust: if self.thrust { 1.0 } else { 0.0 },
+            fire: self.fire,
+        }
+    }
+    fn encode(&self) -> String {
+        format!("{},{},{}", self.turn, self.thrust as u8, self.fire as u8)
+    }
+    fn decode(value: &str) -> Option<Self> {
+        let flag = |part: Option<&str>| match part? {
+            "0" => Some(false),
+            "1" => Some(true),
+            _ => None,
+        };
+        let mut parts = value.split(',');
+        let input = Self {
+            turn: parts.next()?.parse().ok()?,
+            thrust: flag(parts.next())?,
+            fire: flag(parts.next())?,
+        };
+        parts.next().is_none().then_some(input)
+    }
+}
+
+// One per datagram, as a line of text like the game's other formats.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    // `heard` is set once the sender has the other player's hello.
+    Hello {
+        slot: usize,
+        seed: u64,
+        delay: u32,
+        heard: bool,
+    },
+    // Inputs for `first` and the ticks after it.
+    Inputs {
+        slot: usize,
+        first: u64,
+        inputs: Vec<NetInput>,
+    },
+    Hash {
+        tick: u64,
+        hash: u64,
+    },
+    Bye,
+}
+impl Message {
+    pub fn parse(line: &str) -> Result<Self, NetError> {
+        let malformed = || NetError::Malformed(line.to_string());
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let num = |i: usize| {
+            words
+                .get(i)
+                .and_then(|w| w.parse::<u64>().ok())
+                .ok_or_else(malformed)
+        };
+        match (words.first().copied(), words.len()) {
+            (Some("hello"), 5) => Ok(Message::Hello {
+                slot: num(1)? as usize,
+                seed: num(2)?,
+                delay: num(3)?.min(MAX_DELAY as u64) as u32,
+                heard: num(4)? != 0,
+            }),
+            (Some("inputs"), n) if n >= 3 => Ok(Message::Inputs {
+                slot: num(1)? as usize,
+                first: num(2)?,
+                inputs: words[3..]
+                    .iter()
+                    .map(|w| NetInput::decode(w))
+                    .collect::<Option<_>>()
+                    .ok_or_else(malformed)?,
+            }),
+            (Some("hash"), 3) => Ok(Message::Hash {
+                tick: num(1)?,
+                hash: num(2)?,
+            }),
+            (Some("bye"), 1) => Ok(Message::Bye),
+            _ => Err(malformed()),
+        }
+    }
+
+    pub fn to_line(&self) -> String {
+        match self {
+            Message::Hello {
+                slot,
+                seed,
+                delay,
+                heard,
+            } => format!("hello {slot} {seed} {delay} {}", *heard as u8),
+            Message::Inputs {
+                slot,
+                first,
+                inputs,
+            } => {
+                let mut line = format!("inputs {slot} {first}");
+                for input in inputs {
+                    line.push(' ');
+                    line.push_str(&input.encode());
+                }
+                line
+            }
+            Message::Hash { tick, hash } => format!("hash {tick} {hash}"),
+            Message::Bye => "bye".into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum NetError {
+    Usage(String),
+    Io(io::Error),
+    Malformed(String),
+    SameSlot(usize),
+    Closed,
+    Timeout(u64),
+    Desync { tick: u64, local: u64, remote: u64 },
+}
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetError::Usage(msg) => write!(f, "{msg}"),
+            NetError::Io(e) => write!(f, "network error: {e}"),
+            NetError::Malformed(line) => write!(f, "malformed message: {line}"),
+            NetError::SameSlot(slot) => {
+                write!(f, "both sides are player {}, pick different ones", slot + 1)
+            }
+            NetError::Closed => write!(f, "the other player left"),
+            NetError::Timeout(tick) => write!(f, "lost the other player at tick {tick}"),
+            NetError::Desync {
+                tick,
+                local,
+                remote,
+            } => write!(
+                f,
+                "desync at tick {tick}: state hash {local:016x} here, {remote:016x} there"
+            ),
+        }
+    }
+}
+impl std::error::Error for NetError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetConfig {
+    pub bind: SocketAddr,
+    pub peer: SocketAddr,
+    // 0 or 1, given on the command line as player 1 or 2.
+    pub slot: usize,
+    // Ticks between sampling an input and simulating it.
+    pub delay: u32,
+}
+impl NetConfig {
+    // `<bind> <peer> <player> [delay]`, e.g. `0.0.0.0:7000 192.168.1.20:7000 1`.
+    pub fn parse(args: &[String]) -> Result<Self, NetError> {
+        let [bind, peer, player, rest @ ..] = args else {
+            return Err(NetError::Usage("missing arguments".into()));
+        };
+        let address = |value: &String| {
+            value
+                .parse()
+                .map_err(|_| NetError::Usage(format!("invalid address {value}")))
+        };
+        let player = player
+            .parse::<usize>()
+            .ok()
+            .filter(|p| (1..=NET_PLAYERS).contains(p))
+            .ok_or_else(|| NetError::Usage(format!("player must be 1 or 2, not {player}")))?;
+        let delay = match rest {
+            [] => DEFAULT_DELAY,
+            [delay] => delay
+                .parse()
+                .ok()
+                .filter(|d| *d <= MAX_DELAY)
+                .ok_or_else(|| NetError::Usage(format!("invalid delay {delay}")))?,
+            [_, extra, ..] => return Err(NetError::Usage(format!("unknown argument {extra}"))),
+        };
+        Ok(Self {
+            bind: address(bind)?,
+            peer: address(peer)?,
+            slot: player - 1,
+            delay,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetState {
+    // Sending hellos until the other player answers.
+    Connecting,
+    // Agreed on a seed and delay, waiting for the game to start.
+    Connected,
+    Running,
+    // Either side left, or the connection failed.
+    Finished,
+    Desynced(u64),
+}
+
+// Lockstep: a tick only runs once both players' inputs for it are known, and
+// local inputs are scheduled `delay` ticks ahead to hide the round trip.
+#[derive(Resource)]
+pub struct NetSession {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    pub slot: usize,
+    pub delay: u32,
+    // Player one's seed is the one both sides use.
+    seed: u64,
+    pub state: NetState,
+    // Next tick to simulate.
+    pub tick: u64,
+    inputs: [BTreeMap<u64, NetInput>; NET_PLAYERS],
+    local_hashes: BTreeMap<u64, u64>,
+    remote_hashes: BTreeMap<u64, u64>,
+    // Newest tick whose hashes have been compared.
+    compared: Option<u64>,
+    pub verified: u32,
+    // Simulation time, kept apart from the virtual clock.
+    clock: Time,
+    budget: Duration,
+    since_hello: Duration,
+    silence: Duration,
+    // Tests run as fast as inputs arrive and stop at `limit`.
+    throttle: bool,
+    limit: Option<u64>,
+}
+
+impl NetSession {
+    pub fn bind(config: &NetConfig) -> Result<Self, NetError> {
+        let socket = UdpSocket::bind(config.bind).map_err(NetError::Io)?;
+        Self::new(socket, config.peer, config.slot, config.delay)
+    }
+
+    pub fn new(
+        socket: UdpSocket,
+        peer: SocketAddr,
+        slot: usize,
+        delay: u32,
+    ) -> Result<Self, NetError> {
+        socket.set_nonblocking(true).map_err(NetError::Io)?;
+        Ok(Self {
+            socket,
+            peer,
+            slot,
+            delay: delay.min(MAX_DELAY),
+            seed: rand::thread_rng().gen(),
+            state: NetState::Connecting,
+            tick: 0,
+            inputs: Default::default(),
+            local_hashes: BTreeMap::new(),
+            remote_hashes: BTreeMap::new(),
+            compared: None,
+            verified: 0,
+            clock: Time::default(),
+            // First hello goes out straight away.
+            since_hello: HELLO_INTERVAL,
+            budget: Duration::ZERO,
+            silence: Duration::ZERO,
+            throttle: true,
+            limit: None,
+        })
+    }
+
+    pub fn start(&mut self) {
+        self.state = NetState::Running;
+        self.tick = 0;
+        self.clock = Time::default();
+        self.budget = Duration::ZERO;
+        // Nobody can have sampled anything for the first ticks. Inputs the
+        // other player sent after starting first are kept.
+        for inputs in self.inputs.iter_mut() {
+            for tick in 0..self.delay as u64 {
+                inputs.entry(tick).or_default();
+            }
+        }
+    }
+
+    fn hello(&self, heard: bool) -> Message {
+        Message::Hello {
+            slot: self.slot,
+            seed: self.seed,
+            delay: self.delay,
+            heard,
+        }
+    }
+
+    fn send(&self, message: &Message) -> Result<(), NetError> {
+        match self.socket.send_to(message.to_line().as_bytes(), self.peer) {
+            Ok(_) => Ok(()),
+            // The other side may not be listening yet.
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock
+                        | io::ErrorKind::ConnectionRefused
+                        | io::ErrorKind::ConnectionReset
+                ) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(NetError::Io(e)),
+        }
+    }
+
+    // Handles everything waiting on the socket, true if anything arrived.
+    fn poll(&mut self) -> Result<bool, NetError> {
+        let mut buf = [0; MAX_DATAGRAM];
+        let mut heard = false;
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(heard),
+                // Some platforms report an earlier send to a closed port here.
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(NetError::Io(e)),
+            };
+            if from != self.peer {
+                continue;
+            }
+            match Message::parse(String::from_utf8_lossy(&buf[..len]).trim()) {
+                Ok(message) => {
+                    heard = true;
+                    self.handle(message)?;
+                }
+                Err(e) => warn!("netplay: {e}"),
+            }
+        }
+    }
+
+    fn handle(&mut self, message: Message) -> Result<(), NetError> {
+        match message {
+            Message::Hello {
+                slot,
+                seed,
+                delay,
+                heard,
+            } => {
+                if slot == self.slot || slot >= NET_PLAYERS {
+                    return Err(NetError::SameSlot(slot));
+                }
+                if self.state == NetState::Connecting {
+                    if slot == 0 {
+                        self.seed = seed;
+                    }
+                    self.delay = self.delay.max(delay);
+                    self.state = NetState::Connected;
+                    info!(
+                        "netplay: connected to player {} at {}, {} ticks of input delay",
+                        slot + 1,
+                        self.peer,
+                        self.delay
+                    );
+                }
+                if !heard {
+                    self.send(&self.hello(true))?;
+                }
+            }
+            Message::Inputs {
+                slot,
+                first,
+                inputs,
+            } => {
+                if slot == self.slot || slot >= NET_PLAYERS {
+                    return Ok(());
+                }
+                // Each message repeats recent ticks, so lost ones are filled in later.
+                for (tick, input) in (first..).zip(inputs) {
+                    if tick >= self.tick {
+                        self.inputs[slot].entry(tick).or_insert(input);
+                    }
+                }
+            }
+            Message::Hash { tick, hash } => {
+                if self.compared.map_or(true, |c| tick > c) {
+                    self.remote_hashes.insert(tick, hash);
+                }
+            }
+            Message::Bye => return Err(NetError::Closed),
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, input: NetInput) {
+        let tick = self.tick + self.delay as u64;
+        self.inputs[self.slot].entry(tick).or_insert(input);
+    }
+
+    fn ready(&self) -> bool {
+        self.limit.map_or(true, |l| self.tick < l)
+            && self.inputs.iter().all(|i| i.contains_key(&self.tick))
+    }
+
+    // Long enough to cover every tick the other player can still be waiting for.
+    fn window(&self) -> u64 {
+        2 * self.delay as u64 + 2
+    }
+
+    fn send_inputs(&self) -> Result<(), NetError> {
+        let local = &self.inputs[self.slot];
+        let Some(&newest) = local.keys().next_back() else {
+            return Ok(());
+        };
+        let mut recent = local.range((newest + 1).saturating_sub(self.window())..);
+        let Some((&first, &input)) = recent.next() else {
+            return Ok(());
+        };
+        let inputs = std::iter::once(input)
+            .chain(recent.map(|(_, i)| *i))
+            .collect();
+        self.send(&Message::Inputs {
+            slot: self.slot,
+            first,
+            inputs,
+        })?;
+        // Only the newest hash is repeated, older ones the other side missed are skipped.
+        match self.local_hashes.iter().next_back() {
+            Some((&tick, &hash)) => self.send(&Message::Hash { tick, hash }),
+            None => Ok(()),
+        }
+    }
+
+    fn prune(&mut self) {
+        let keep = self.tick.saturating_sub(self.window());
+        for inputs in self.inputs.iter_mut() {
+            *inputs = inputs.split_off(&keep);
+        }
+    }
+
+    fn check_hashes(&mut self) -> Result<(), NetError> {
+        let both: Vec<(u64, u64, u64)> = self
+            .local_hashes
+            .iter()
+            .filter_map(|(&tick, &local)| Some((tick, local, *self.remote_hashes.get(&tick)?)))
+            .collect();
+        for &(tick, local, remote) in both.iter() {
+            if local != remote {
+                return Err(NetError::Desync {
+                    tick,
+                    local,
+                    remote,
+                });
+            }
+            self.verified += 1;
+        }
+        if let Some(&(last, ..)) = both.last() {
+            self.compared = Some(last);
+            self.local_hashes.retain(|t, _| *t > last);
+            self.remote_hashes.retain(|t, _| *t > last);
+        }
+        Ok(())
+    }
+}
+
+// Added by `game --net ...` along with its `NetSession`.
+pub struct NetPlugin;
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, hold_fixed_loop)
+            .add_systems(RunFixedMainLoop, run_net_ticks)
+            .add_systems(Update, start_game)
+            .add_systems(PostUpdate, lock_field);
+    }
+}
+
+// Session for `game --net <bind> <peer> <player> [delay]`, exits on bad arguments.
+pub fn session_from_args(args: &[String]) -> NetSession {
+    match NetConfig::parse(args).and_then(|config| NetSession::bind(&config)) {
+        Ok(session) => session,
+        Err(e) => {
+            eprintln!("netplay: {e}");
+            eprintln!("usage: game --net <bind address> <peer address> <player 1|2> [input delay]");
+            std::process::exit(2);
+        }
+    }
+}
+
+// The fixed schedule is stepped by `run_net_ticks` instead.
+fn hold_fixed_loop(mut fixed: ResMut<Time<Fixed>>) {
+    fixed.set_timestep(Duration::MAX);
+}
+
+// Both sides start the same game as soon as they agree on a seed. Rules that
+// change the simulation aren't negotiated, so both use the defaults.
+fn start_game(
+    mut session: ResMut<NetSession>,
+    mut setup: ResMut<PlayerSetup>,
+    mut mode: ResMut<GameMode>,
+    mut difficulty: ResMut<Difficulty>,
+    mut new_game: EventWriter<NewGame>,
+    mut next: ResMut<NextState<GameState>>,
+) {
+    if session.state != NetState::Connected {
+        return;
+    }
+    *setup = PlayerSetup {
+        count: NET_PLAYERS,
+        lives: LivesPool::Shared,
+    };
+    *mode = GameMode::Survival;
+    *difficulty = Difficulty::Normal;
+    new_game.send(NewGame);
+    next.set(GameState::Playing);
+    session.start();
+}
+
+// The areas follow the visible extent, which only letterboxing keeps the same
+// for every window size.
+fn lock_field(mut field: ResMut<PlayField>) {
+    if field.mode != ScaleMode::Letterbox {
+        field.mode = ScaleMode::Letterbox;
+    }
+}
+
+pub fn run_net_ticks(world: &mut World) {
+    world.resource_scope(|world, mut session: Mut<NetSession>| {
+        let Err(e) = step(world, &mut session) else {
+            return;
+        };
+        error!("netplay: {e}");
+        session.state = match e {
+            NetError::Desync { tick, .. } => NetState::Desynced(tick),
+            _ => NetState::Finished,
+        };
+        let playing = world
+            .get_resource::<State<GameState>>()
+            .is_some_and(|s| matches!(s.get(), GameState::Playing | GameState::Paused));
+        if playing {
+            world
+                .resource_mut::<NextState<GameState>>()
+                .set(GameState::MainMenu);
+        }
+    });
+}
+
+fn step(world: &mut World, session: &mut NetSession) -> Result<(), NetError> {
+    if matches!(session.state, NetState::Finished | NetState::Desynced(_)) {
+        return Ok(());
+    }
+    let delta = world.resource::<Time<Real>>().delta();
+    if session.poll()? {
+        session.silence = Duration::ZERO;
+    } else if session.state != NetState::Connecting {
+        session.silence += delta;
+        if session.silence > PEER_TIMEOUT {
+            return Err(NetError::Timeout(session.tick));
+        }
+    }
+    match session.state {
+        NetState::Connecting => {
+            session.since_hello += delta;
+            if session.since_hello >= HELLO_INTERVAL {
+                session.since_hello = Duration::ZERO;
+                session.send(&session.hello(false))?;
+            }
+            return Ok(());
+        }
+        NetState::Running => {}
+        _ => return Ok(()),
+    }
+    match world.get_resource::<State<GameState>>().map(|s| *s.get()) {
+        // Keeps the other side from timing out while it waits for us.
+        Some(GameState::Paused) => return session.send_inputs(),
+        Some(GameState::MainMenu | GameState::GameOver) => {
+            session.send(&Message::Bye)?;
+            session.state = NetState::Finished;
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    if session.throttle {
+        session.budget = (session.budget + delta).min(MAX_BUDGET);
+    }
+    let input = local_input(world);
+    session.record(input);
+    // One tick per frame at most, the collision shapes are only synced in Update.
+    if session.ready() && (!session.throttle || session.budget >= TICK) {
+        session.budget = session.budget.saturating_sub(TICK);
+        run_tick(world, session);
+        session.tick += 1;
+        session.record(input);
+        session.prune();
+    }
+    session.send_inputs()?;
+    session.check_hashes()
+}
+
+fn local_input(world: &mut World) -> NetInput {
+    device_input(world)
+        .map(|input| NetInput::quantise(&input))
+        .unwrap_or_default()
+}
+
+// Whoever sits at this machine plays with player one's controls.
+fn device_input(world: &World) -> Option<PlayerInput> {
+    Some(players::sample(
+        &world.get_resource::<Settings>()?.controls[0],
+        world.get_resource()?,
+        world.get_resource()?,
+        world.get_resource()?,
+        world.get_resource()?,
+    ))
+}
+
+fn run_tick(world: &mut World, session: &mut NetSession) {
+    let tick = session.tick;
+    if tick == 0 {
+        world.insert_resource(GameRng::seeded(session.seed));
+    }
+    let mut players = world.query::<(&Player, &mut PlayerInput)>();
+    for (player, mut input) in players.iter_mut(world) {
+        let net = session
+            .inputs
+            .get(player.slot)
+            .and_then(|i| i.get(&tick))
+            .copied()
+            .unwrap_or_default();
+        *input = net.input();
+    }
+    session.clock.advance_by(TICK);
+    *world.resource_mut::<Time>() = session.clock;
+    world.run_schedule(FixedMain);
+    let virtual_time = world.resource::<Time<Virtual>>().as_generic();
+    *world.resource_mut::<Time>() = virtual_time;
+
+    if tick % HASH_INTERVAL == 0 {
+        let hash = state_hash(world);
+        session.local_hashes.insert(tick, hash);
+    }
+}
+
+fn transform_hash(kind: u8, transform: &Transform, extra: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    kind.hash(&mut hasher);
+    let values = transform.translation.to_array();
+    for v in values.iter().chain(transform.rotation.to_array().iter()) {
+        v.to_bits().hash(&mut hasher);
+    }
+    extra.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn sum_hashes<F: QueryFilter>(world: &mut World, kind: u8) -> u64 {
+    let mut query = world.query_filtered::<&Transform, F>();
+    query
+        .iter(world)
+        .fold(0, |sum, t| sum.wrapping_add(transform_hash(kind, t, ())))
+}
+
+// Summed per entity so it doesn't depend on query order.
+pub fn state_hash(world: &mut World) -> u64 {
+    let mut players = world.query::<(&Player, &Transform, &Health)>();
+    let mut sum = players.iter(world).fold(0u64, |sum, (p, t, h)| {
+        sum.wrapping_add(transform_hash(0, t, (p.slot, h.0)))
+    });
+    sum = sum
+        .wrapping_add(sum_hashes::<With<Rock>>(world, 1))
+        .wrapping_add(sum_hashes::<With<Ship>>(world, 2))
+        .wrapping_add(sum_hashes::<With<Projectile>>(world, 3))
+        .wrapping_add(sum_hashes::<With<Pickup>>(world, 4));
+    let score = world.get_resource::<Score>().map_or(0, |s| s.points);
+    let mut hasher = DefaultHasher::new();
+    (sum, score).hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use bevy::{
+        audio::AudioPlugin,
+        gilrs::GilrsPlugin,
+        log::LogPlugin,
+        render::{settings::WgpuSettings, RenderPlugin},
+        window::ExitCondition,
+        winit::WinitPlugin,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::settings::KeyBindings;
+
+    const TICKS: u64 = 600;
+    // Wall clock allowed for the whole run, a stuck session fails instead of hanging.
+    const DEADLINE: Duration = Duration::from_secs(60);
+
+    // The game's own plugins, with no window, GPU or audio device behind them.
+    fn headless_app(session: NetSession) -> App {
+        let mut app = App::new();
+        app.add_plugins(
+            DefaultPlugins
+                .set(RenderPlugin {
+                    render_creation: WgpuSettings {
+                        backends: None,
+                        ..default()
+                    }
+                    .into(),
+                    ..default()
+                })
+                .set(WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
+                    ..default()
+                })
+                .disable::<WinitPlugin>()
+                .disable::<AudioPlugin>()
+                .disable::<GilrsPlugin>()
+                .disable::<LogPlugin>(),
+        )
+        .add_plugins((crate::GameplayPlugin, NetPlugin))
+        // Normally from the menu, view and settings plugins, which want a window.
+        .init_state::<GameState>()
+        .init_resource::<PlayField>()
+        .init_resource::<Settings>()
+        .insert_resource(session);
+        app.finish();
+        app.cleanup();
+        app
+    }
+
+    // Random but repeatable key presses on player one's default keys, held
+    // for a while like a real player would so the ships get somewhere.
+    struct Script {
+        rng: StdRng,
+        frames_left: u32,
+    }
+    impl Script {
+        fn new(seed: u64) -> Self {
+            Self {
+                rng: StdRng::seed_from_u64(seed),
+                frames_left: 0,
+            }
+        }
+        fn press(&mut self, keyboard: &mut ButtonInput<KeyCode>) {
+            if self.frames_left > 0 {
+                self.frames_left -= 1;
+                return;
+            }
+            self.frames_left = self.rng.gen_range(5..40);
+            let keys = KeyBindings::default();
+            keyboard.release_all();
+            match self.rng.gen_range(0..3) {
+                0 => keyboard.press(keys.left),
+                1 => keyboard.press(keys.right),
+                _ => {}
+            }
+            if self.rng.gen_bool(0.6) {
+                keyboard.press(keys.forward);
+            }
+            if self.rng.gen_bool(0.5) {
+                keyboard.press(keys.fire);
+            }
+        }
+    }
+
+    #[test]
+    fn two_games_stay_in_lockstep() {
+        let loopback = SocketAddr::from(([127, 0, 0, 1], 0));
+        let sockets = [
+            UdpSocket::bind(loopback).unwrap(),
+            UdpSocket::bind(loopback).unwrap(),
+        ];
+        let addresses = [
+            sockets[0].local_addr().unwrap(),
+            sockets[1].local_addr().unwrap(),
+        ];
+        let mut peers: Vec<(App, Script)> = sockets
+            .into_iter()
+            .enumerate()
+            .map(|(slot, socket)| {
+                let mut session =
+                    NetSession::new(socket, addresses[1 - slot], slot, DEFAULT_DELAY).unwrap();
+                session.throttle = false;
+                session.limit = Some(TICKS);
+                (headless_app(session), Script::new(slot as u64))
+            })
+            .collect();
+
+        let started = Instant::now();
+        while peers
+            .iter()
+            .any(|(app, _)| app.world.resource::<NetSession>().tick < TICKS)
+        {
+            assert!(started.elapsed() < DEADLINE, "sessions stopped advancing");
+            for (app, script) in peers.iter_mut() {
+                script.press(&mut app.world.resource_mut::<ButtonInput<KeyCode>>());
+                app.update();
+                let state = app.world.resource::<NetSession>().state;
+                assert!(
+                    !matches!(state, NetState::Finished | NetState::Desynced(_)),
+                    "session ended early: {state:?}"
+                );
+            }
+        }
+        // Both stopped after the same tick, so the worlds have to match exactly.
+        let [(first, _), (second, _)] = &mut peers[..] else {
+            unreachable!();
+        };
+        assert_eq!(state_hash(&mut first.world), state_hash(&mut second.world));
+        assert!(first.world.resource::<NetSession>().verified > 0);
+        // A game that never started would match trivially.
+        assert!(first.world.resource::<Score>().points > 0);
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let messages = [
+            Message::Hello {
+                slot: 1,
+                seed: u64::MAX,
+                delay: 3,
+                heard: true,
+            },
+            Message::Inputs {
+                slot: 0,
+                first: 42,
+                inputs: vec![
+                    NetInput {
+                        turn: -127,
+                        thrust: true,
+                        fire: false,
+                    },
+                    NetInput::default(),
+                ],
+            },
+            Message::Hash { tick: 30, hash: 7 },
+            Message::Bye,
+        ];
+        for message in messages {
+            assert_eq!(Message::parse(&message.to_line()).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        for line in [
+            "",
+            "hello 1 2 3",
+            "inputs 0",
+            "inputs 0 1 5,2,0",
+            "hash x 1",
+            "bye now",
+        ] {
+            assert!(matches!(Message::parse(line), Err(NetError::Malformed(_))));
+        }
+    }
+
+    #[test]
+    fn parses_command_lines() {
+        let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+        let config = NetConfig::parse(&args("0.0.0.0:7000 127.0.0.1:7001 2")).unwrap();
+        assert_eq!(config.slot, 1);
+        assert_eq!(config.delay, DEFAULT_DELAY);
+        assert_eq!(
+            NetConfig::parse(&args("0.0.0.0:7000 127.0.0.1:7001 1 5"))
+                .unwrap()
+                .delay,
+            5
+        );
+        for line in [
+            "0.0.0.0:7000 127.0.0.1:7001",
+            "0.0.0.0:7000 127.0.0.1:7001 3",
+            "nowhere 127.0.0.1:7001 1",
+            "0.0.0.0:7000 127.0.0.1:7001 1 99",
+            "0.0.0.0:7000 127.0.0.1:7001 1 2 3",
+        ] {
+            assert!(matches!(
+                NetConfig::parse(&args(line)),
+                Err(NetError::Usage(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,399 @@
+use std::f32::consts::PI;
+
+use bevy::{
+    prelude::*,
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
+use rand::{thread_rng, Rng};
+
+use crate::{
+    camera::Trauma,
+    players::PlayerInput,
+    pool::GameAssets,
+    progress::PlayerHit,
+    settings::{PaletteColors, Settings},
+    weapon::{Enemy, EnemyDestroyed},
+};
+
+// Keeps big waves of explosions from flooding the world with entities.
+const MAX_PARTICLES: usize = 1500;
+const ROCK_SIZE: f32 = 25.0;
+// Colours a particle passes through as it fades, each a material shared by
+// every particle of its preset.
+pub const FADE_STEPS: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    RockExplosion,
+    ShipExplosion,
+    Thrust,
+    PlayerHit,
+}
+
+#[derive(Clone, Debug)]
+pub struct ParticleConfig {
+    pub lifetime: f32,
+    pub speed: (f32, f32),
+    // Radians either side of `direction`, PI sprays all around.
+    pub spread: f32,
+    pub direction: Vec2,
+    // Start and end of life, faded in between.
+    pub color: (Color, Color),
+    pub size: (f32, f32),
+    // Fraction of the velocity lost per second.
+    pub drag: f32,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [
+        Preset::RockExplosion,
+        Preset::ShipExplosion,
+        Preset::Thrust,
+        Preset::PlayerHit,
+    ];
+
+    pub fn config(&self, colors: &PaletteColors) -> ParticleConfig {
+        match self {
+            Preset::RockExplosion => ParticleConfig {
+                lifetime: 0.8,
+                speed: (40.0, 160.0),
+                spread: PI,
+                direction: Vec2::Y,
+                color: (colors.rock, colors.rock.with_a(0.0)),
+                size: (6.0, 1.0),
+                drag: 2.0,
+            },
+            Preset::ShipExplosion => ParticleConfig {
+                lifetime: 0.6,
+                speed: (80.0, 240.0),
+                spread: PI,
+                direction: Vec2::Y,
+                color: (Color::ORANGE, colors.ship.with_a(0.0)),
+                size: (5.0, 2.0),
+                drag: 3.0,
+            },
+            // Points backwards out of the engine.
+            Preset::Thrust => ParticleConfig {
+                lifetime: 0.35,
+                speed: (60.0, 120.0),
+                spread: 0.3,
+                direction: Vec2::NEG_Y,
+                color: (Color::YELLOW, Color::RED.with_a(0.0)),
+                size: (5.0, 1.0),
+                drag: 1.0,
+            },
+            Preset::PlayerHit => ParticleConfig {
+                lifetime: 0.4,
+                speed: (100.0, 200.0),
+                spread: PI,
+                direction: Vec2::Y,
+                color: (Color::WHITE, colors.player.with_a(0.0)),
+                size: (4.0, 1.0),
+                drag: 4.0,
+            },
+        }
+    }
+    // Camera shake per burst before scaling. Player hits add theirs from the
+    // damage taken instead.
+    fn trauma(&self) -> f32 {
+        match self {
+            Preset::RockExplosion => 0.1,
+            Preset::ShipExplosion => 0.2,
+            Preset::Thrust | Preset::PlayerHit => 0.0,
+        }
+    }
+    // Particles in one burst before scaling.
+    fn count(&self) -> f32 {
+        match self {
+            Preset::RockExplosion => 24.0,
+            Preset::ShipExplosion => 32.0,
+            Preset::Thrust => 1.0,
+            Preset::PlayerHit => 16.0,
+        }
+    }
+}
+
+// One-off spray of particles, `scale` multiplies the count and speed.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Burst {
+    pub preset: Preset,
+    pub position: Vec2,
+    pub scale: f32,
+}
+impl Burst {
+    pub fn new(preset: Preset, position: Vec2) -> Self {
+        Self {
+            preset,
+            position,
+            scale: 1.0,
+        }
+    }
+    pub fn enemy(enemy: Enemy, position: Vec2) -> Self {
+        match enemy {
+            Enemy::Rock { size } => Self {
+                scale: size / ROCK_SIZE,
+                ..Self::new(Preset::RockExplosion, position)
+            },
+            Enemy::Ship => Self::new(Preset::ShipExplosion, position),
+        }
+    }
+}
+
+// Emits continuously while active, following its entity's rotation.
+#[derive(Component)]
+pub struct Emitter {
+    pub preset: Preset,
+    // Particles per second.
+    pub rate: f32,
+    pub active: bool,
+    carry: f32,
+}
+impl Emitter {
+    pub fn new(preset: Preset, rate: f32) -> Self {
+        Self {
+            preset,
+            rate,
+            active: false,
+            carry: 0.0,
+        }
+    }
+}
+
+// Engine emitter on a player, active while moving forward.
+#[derive(Component)]
+pub struct Thrust;
+
+#[derive(Component)]
+pub struct Particle {
+    preset: Preset,
+    velocity: Vec2,
+    life: Timer,
+    step: usize,
+    size: (f32, f32),
+    drag: f32,
+}
+
+#[derive(Resource)]
+struct ParticleQuad(Mesh2dHandle);
+impl FromWorld for ParticleQuad {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        Self(Mesh2dHandle(meshes.add(Rectangle::new(1., 1.))))
+    }
+}
+
+pub struct ParticlePlugin;
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Burst>()
+            .init_resource::<ParticleQuad>()
+            .add_systems(
+                Update,
+                (
+                    explosions,
+                    thrust,
+                    emit_bursts.after(explosions),
+                    emit_continuous.after(thrust),
+                    update_particles,
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                color_particles.run_if(resource_changed::<Settings>),
+            );
+    }
+}
+
+pub fn thrust_emitter() -> impl Bundle {
+    (
+        SpatialBundle::from_transform(Transform::from_xyz(0., -28., -0.5)),
+        Emitter::new(Preset::Thrust, 60.0),
+        Thrust,
+    )
+}
+
+// Turns gameplay events into bursts so gameplay code doesn't need to know about particles.
+fn explosions(
+    mut destroyed: EventReader<EnemyDestroyed>,
+    mut hits: EventReader<PlayerHit>,
+    players: Query<&GlobalTransform>,
+    mut bursts: EventWriter<Burst>,
+) {
+    for d in destroyed.read() {
+        bursts.send(Burst::enemy(d.enemy, d.position));
+    }
+    for hit in hits.read() {
+        if let Ok(t) = players.get(hit.player) {
+            bursts.send(Burst::new(Preset::PlayerHit, t.translation().xy()));
+        }
+    }
+}
+
+fn thrust(
+    players: Query<&PlayerInput>,
+    mut emitters: Query<(&mut Emitter, &Parent), With<Thrust>>,
+) {
+    for (mut emitter, parent) in emitters.iter_mut() {
+        emitter.active = players.get(parent.get()).is_ok_and(|i| i.thrust > 0.0);
+    }
+}
+
+fn emit_bursts(
+    mut cmds: Commands,
+    assets: Res<GameAssets>,
+    quad: Res<ParticleQuad>,
+    settings: Res<Settings>,
+    mut bursts: EventReader<Burst>,
+    mut trauma: EventWriter<Trauma>,
+    particles: Query<(), With<Particle>>,
+) {
+    let colors = settings.palette.colors();
+    let mut room = MAX_PARTICLES.saturating_sub(particles.iter().count());
+    let mut rng = thread_rng();
+    for burst in bursts.read() {
+        // Shakes even when the particle cap leaves no room for the burst.
+        let shake = burst.preset.trauma() * burst.scale;
+        if shake > 0.0 {
+            trauma.send(Trauma(shake));
+        }
+        let mut config = burst.preset.config(&colors);
+        config.speed.0 *= burst.scale;
+        config.speed.1 *= burst.scale;
+        let count = ((burst.preset.count() * burst.scale).ceil() as usize).min(room);
+        room -= count;
+        for _ in 0..count {
+            spawn_particle(
+                &mut cmds,
+                &quad,
+                &assets,
+                burst.preset,
+                &config,
+                burst.position,
+                config.direction,
+                &mut rng,
+            );
+        }
+    }
+}
+
+fn emit_continuous(
+    mut cmds: Commands,
+    assets: Res<GameAssets>,
+    quad: Res<ParticleQuad>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+    mut emitters: Query<(&mut Emitter, &GlobalTransform)>,
+    particles: Query<(), With<Particle>>,
+) {
+    let colors = settings.palette.colors();
+    let mut room = MAX_PARTICLES.saturating_sub(particles.iter().count());
+    let mut rng = thread_rng();
+    for (mut emitter, transform) in emitters.iter_mut() {
+        if !emitter.active {
+            emitter.carry = 0.0;
+            continue;
+        }
+        emitter.carry += emitter.rate * time.delta_seconds();
+        let count = (emitter.carry.floor() as usize).min(room);
+        emitter.carry = emitter.carry.fract();
+        room -= count;
+        let config = emitter.preset.config(&colors);
+        let (_, rotation, position) = transform.to_scale_rotation_translation();
+        let direction = (rotation * config.direction.extend(0.)).xy();
+        for _ in 0..count {
+            spawn_particle(
+                &mut cmds,
+                &quad,
+                &assets,
+                emitter.preset,
+                &config,
+                position.xy(),
+                direction,
+                &mut rng,
+            );
+        }
+    }
+}
+
+fn spawn_particle(
+    cmds: &mut Commands,
+    quad: &ParticleQuad,
+    assets: &GameAssets,
+    preset: Preset,
+    config: &ParticleConfig,
+    position: Vec2,
+    direction: Vec2,
+    rng: &mut impl Rng,
+) {
+    let angle = rng.gen_range(-config.spread..=config.spread);
+    let speed = rng.gen_range(config.speed.0..=config.speed.1);
+    let velocity = Vec2::from_angle(angle).rotate(direction.normalize_or_zero()) * speed;
+    cmds.spawn((
+        MaterialMesh2dBundle {
+            mesh: quad.0.clone(),
+            material: assets.particle(preset, 0),
+            transform: Transform::from_xyz(position.x, position.y, 0.5)
+                .with_scale(Vec3::splat(config.size.0)),
+            ..default()
+        },
+        Particle {
+            preset,
+            velocity,
+            life: Timer::from_seconds(config.lifetime, TimerMode::Once),
+            step: 0,
+            size: config.size,
+            drag: config.drag,
+        },
+    ));
+}
+
+fn update_particles(
+    mut cmds: Commands,
+    time: Res<Time>,
+    assets: Res<GameAssets>,
+    mut query: Query<(
+        Entity,
+        &mut Particle,
+        &mut Transform,
+        &mut Handle<ColorMaterial>,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    for (e, mut particle, mut transform, mut material) in query.iter_mut() {
+        if particle.life.tick(time.delta()).finished() {
+            cmds.entity(e).despawn();
+            continue;
+        }
+        let t = particle.life.fraction();
+        let drag = (1.0 - particle.drag * dt).max(0.0);
+        particle.velocity *= drag;
+        transform.translation += (particle.velocity * dt).extend(0.);
+        let size = particle.size.0 + (particle.size.1 - particle.size.0) * t;
+        transform.scale = Vec3::splat(size);
+        let step = ((t * FADE_STEPS as f32) as usize).min(FADE_STEPS - 1);
+        if step != particle.step {
+            particle.step = step;
+            *material = assets.particle(particle.preset, step);
+        }
+    }
+}
+
+// Sets the shared fade materials from the palette, once at start and again
+// whenever the settings change.
+fn color_particles(
+    settings: Res<Settings>,
+    assets: Res<GameAssets>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let colors = settings.palette.colors();
+    for preset in Preset::ALL {
+        let (from, to) = preset.config(&colors).color;
+        for step in 0..FADE_STEPS {
+            let t = step as f32 / (FADE_STEPS - 1) as f32;
+            let color = Vec4::from(from.as_rgba_f32()).lerp(Vec4::from(to.as_rgba_f32()), t);
+            if let Some(material) = materials.get_mut(assets.particle(preset, step).id()) {
+                material.color = Color::rgba_from_array(color);
+            }
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use bevy::{input::InputSystem, prelude::*};
+
+use crate::{
+    settings::{KeyBindings, PaletteColors, Settings},
+    Player,
+};
+
+pub const MAX_PLAYERS: usize = 4;
+// Horizontal gap between players at the start and on respawn.
+const START_SPACING: f32 = 80.0;
+const STICK_DEADZONE: f32 = 0.25;
+// Player one keeps the palette colour, the rest use these.
+const PLAYER_COLORS: [Color; MAX_PLAYERS - 1] = [
+    Color::rgb(0.3, 0.6, 1.0),
+    Color::rgb(1.0, 0.6, 0.1),
+    Color::rgb(0.8, 0.3, 0.9),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Device {
+    Keyboard,
+    // The nth connected gamepad, in connection order.
+    Gamepad(usize),
+}
+impl Device {
+    pub fn name(&self) -> String {
+        match self {
+            Device::Keyboard => "keyboard".into(),
+            Device::Gamepad(n) => format!("gamepad {n}"),
+        }
+    }
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.split_whitespace().collect::<Vec<_>>()[..] {
+            ["keyboard"] => Some(Device::Keyboard),
+            ["gamepad", n] => n.parse().ok().map(Device::Gamepad),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlayerControls {
+    pub device: Device,
+    pub keys: KeyBindings,
+}
+impl PlayerControls {
+    // Two players share the keyboard, three and four need gamepads.
+    pub fn default_for(slot: usize) -> Self {
+        match slot {
+            0 => Self {
+                device: Device::Keyboard,
+                keys: KeyBindings::default(),
+            },
+            1 => Self {
+                device: Device::Keyboard,
+                keys: KeyBindings {
+                    forward: KeyCode::ArrowUp,
+                    left: KeyCode::ArrowLeft,
+                    right: KeyCode::ArrowRight,
+                    fire: KeyCode::ShiftRight,
+                },
+            },
+            n => Self {
+                device: Device::Gamepad(n - 2),
+                keys: KeyBindings::default(),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LivesPool {
+    // Everyone draws from `progress::Lives`.
+    #[default]
+    Shared,
+    // Each player starts with their own `progress::OwnLives`.
+    Split,
+}
+impl LivesPool {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LivesPool::Shared => "shared",
+            LivesPool::Split => "split",
+        }
+    }
+    pub fn next(&self) -> Self {
+        match self {
+            LivesPool::Shared => LivesPool::Split,
+            LivesPool::Split => LivesPool::Shared,
+        }
+    }
+}
+
+// Chosen in the main menu, applied by the next new game.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerSetup {
+    pub count: usize,
+    pub lives: LivesPool,
+}
+impl Default for PlayerSetup {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            lives: LivesPool::default(),
+        }
+    }
+}
+impl PlayerSetup {
+    pub fn next_count(&self) -> usize {
+        self.count % MAX_PLAYERS + 1
+    }
+}
+
+// What a player's device asked for this frame, read by the fixed update systems.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct PlayerInput {
+    // Positive turns left.
+    pub turn: f32,
+    pub thrust: f32,
+    pub fire: bool,
+}
+
+pub fn color(slot: usize, colors: &PaletteColors) -> Color {
+    match slot {
+        0 => colors.player,
+        n => PLAYER_COLORS[(n - 1) % PLAYER_COLORS.len()],
+    }
+}
+
+// Spread along the x axis around the origin.
+pub fn start_position(slot: usize, count: usize) -> Vec2 {
+    let offset = slot as f32 - (count.max(1) - 1) as f32 / 2.0;
+    Vec2::new(offset * START_SPACING, 0.0)
+}
+
+pub fn nearest(players: &[Vec2], from: Vec2) -> Option<Vec2> {
+    players.iter().copied().min_by(|a, b| {
+        a.distance_squared(from)
+            .total_cmp(&b.distance_squared(from))
+    })
+}
+
+pub struct PlayersPlugin;
+impl Plugin for PlayersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayerSetup>()
+            // Fixed update runs between PreUpdate and Update, so input has to be ready before it.
+            .add_systems(PreUpdate, read_input.after(InputSystem));
+    }
+}
+
+pub fn read_input(
+    settings: Res<Settings>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepad_input: Res<ButtonInput<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+    mut players: Query<(&Player, &mut PlayerInput)>,
+) {
+    for (player, mut input) in players.iter_mut() {
+        let next = sample(
+            &settings.controls[player.slot % MAX_PLAYERS],
+            &keyboard_input,
+            &gamepad_input,
+            &axes,
+            &gamepads,
+        );
+        if *input != next {
+            *input = next;
+        }
+    }
+}
+
+// What one set of controls is asking for right now.
+pub fn sample(
+    controls: &PlayerControls,
+    keyboard_input: &ButtonInput<KeyCode>,
+    gamepad_input: &ButtonInput<GamepadButton>,
+    axes: &Axis<GamepadAxis>,
+    gamepads: &Gamepads,
+) -> PlayerInput {
+    match controls.device {
+        Device::Keyboard => {
+            let keys = controls.keys;
+            let held = |key| {
+                if keyboard_input.pressed(key) {
+                    1.0
+                } else {
+                    0.0
+                }
+            };
+            PlayerInput {
+                turn: held(keys.left) - held(keys.right),
+                thrust: held(keys.forward),
+                fire: keyboard_input.pressed(keys.fire),
+            }
+        }
+        Device::Gamepad(n) => {
+            let Some(gamepad) = gamepads.iter().nth(n) else {
+                return PlayerInput::default();
+            };
+            let held = |button| gamepad_input.pressed(GamepadButton::new(gamepad, button));
+            let axis = |axis| {
+                axes.get(GamepadAxis::new(gamepad, axis))
+                    .filter(|v| v.abs() > STICK_DEADZONE)
+                    .unwrap_or(0.0)
+            };
+            let dpad = |button| if held(button) { 1.0 } else { 0.0 };
+            let turn = dpad(GamepadButtonType::DPadLeft)
+                - dpad(GamepadButtonType::DPadRight)
+                - axis(GamepadAxisType::LeftStickX);
+            let thrust = held(GamepadButtonType::South)
+                || held(GamepadButtonType::RightTrigger2)
+                || axis(GamepadAxisType::LeftStickY) > 0.0;
+            PlayerInput {
+                turn: turn.clamp(-1.0, 1.0),
+                thrust: if thrust { 1.0 } else { 0.0 },
+                fire: held(GamepadButtonType::West) || held(GamepadButtonType::RightTrigger),
+            }
+        }
+    }
+}
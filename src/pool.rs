@@ -0,0 +1,199 @@
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    prelude::*,
+    sprite::Mesh2dHandle,
+};
+use bevy_sepax2d::prelude::*;
+
+use crate::{
+    particles::{Preset, FADE_STEPS},
+    powerups::{PowerUpKind, PICKUP_RADIUS},
+    weapon::Projectile,
+    Attack, MoveTo, Rock, Ship, Spawner,
+};
+
+// Free entities kept per kind, anything released past this is despawned.
+const POOL_LIMIT: usize = 256;
+pub const PROJECTILE_RADIUS: f32 = 4.0;
+pub const ROCK_SPAWNER_RADIUS: f32 = 25.0;
+pub const SHIP_SPAWNER_RADIUS: f32 = 10.0;
+
+pub const MESH_COUNT: DiagnosticPath = DiagnosticPath::const_new("assets/meshes");
+pub const MATERIAL_COUNT: DiagnosticPath = DiagnosticPath::const_new("assets/materials");
+pub const POOLED_COUNT: DiagnosticPath = DiagnosticPath::const_new("pool/free");
+
+// Handles shared by every rock, ship, projectile, pickup, particle and
+// spawner instead of one asset per entity.
+#[derive(Resource)]
+pub struct GameAssets {
+    pub ship_mesh: Mesh2dHandle,
+    pub ship_material: Handle<ColorMaterial>,
+    pub rock_material: Handle<ColorMaterial>,
+    pub projectile_mesh: Mesh2dHandle,
+    pub projectile_material: Handle<ColorMaterial>,
+    pub pickup_mesh: Mesh2dHandle,
+    // In `PowerUpKind::ALL` order.
+    pub pickup_materials: [Handle<ColorMaterial>; 4],
+    // Fade steps per preset, in `Preset::ALL` order. Particles swap between
+    // them as they age, the colours are set from the palette.
+    pub particle_materials: [[Handle<ColorMaterial>; FADE_STEPS]; 4],
+    pub rock_spawner_mesh: Mesh2dHandle,
+    pub rock_spawner_material: Handle<ColorMaterial>,
+    pub ship_spawner_mesh: Mesh2dHandle,
+    pub ship_spawner_material: Handle<ColorMaterial>,
+    pub power_up_spawner_material: Handle<ColorMaterial>,
+}
+impl GameAssets {
+    pub fn pickup(&self, kind: PowerUpKind) -> (Mesh2dHandle, Handle<ColorMaterial>) {
+        let i = PowerUpKind::ALL
+            .iter()
+            .position(|k| *k == kind)
+            .unwrap_or(0);
+        (self.pickup_mesh.clone(), self.pickup_materials[i].clone())
+    }
+    // `step` runs from 0 at birth to `FADE_STEPS - 1` at the end of life.
+    pub fn particle(&self, preset: Preset, step: usize) -> Handle<ColorMaterial> {
+        let i = Preset::ALL.iter().position(|p| *p == preset).unwrap_or(0);
+        self.particle_materials[i][step.min(FADE_STEPS - 1)].clone()
+    }
+    // Power-up spawners are as small as ship spawners.
+    pub fn spawner(&self, spawner: &Spawner) -> (Mesh2dHandle, Handle<ColorMaterial>) {
+        match spawner {
+            Spawner::Rock { .. } => (
+                self.rock_spawner_mesh.clone(),
+                self.rock_spawner_material.clone(),
+            ),
+            Spawner::Ship { .. } => (
+                self.ship_spawner_mesh.clone(),
+                self.ship_spawner_material.clone(),
+            ),
+            Spawner::PowerUp { .. } => (
+                self.ship_spawner_mesh.clone(),
+                self.power_up_spawner_material.clone(),
+            ),
+        }
+    }
+}
+impl FromWorld for GameAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let ship_mesh = Mesh2dHandle(meshes.add(Triangle2d::new(
+            Vec2::Y * 20.0,
+            Vec2::new(-20.0, -20.0),
+            Vec2::new(20.0, -20.0),
+        )));
+        let projectile_mesh = Mesh2dHandle(meshes.add(Circle {
+            radius: PROJECTILE_RADIUS,
+        }));
+        let pickup_mesh = Mesh2dHandle(meshes.add(Circle {
+            radius: PICKUP_RADIUS,
+        }));
+        let rock_spawner_mesh = Mesh2dHandle(meshes.add(Circle {
+            radius: ROCK_SPAWNER_RADIUS,
+        }));
+        let ship_spawner_mesh = Mesh2dHandle(meshes.add(Circle {
+            radius: SHIP_SPAWNER_RADIUS,
+        }));
+        let mut materials = world.resource_mut::<Assets<ColorMaterial>>();
+        Self {
+            ship_mesh,
+            ship_material: materials.add(Color::PINK),
+            rock_material: materials.add(Color::rgb(0.4, 0.8, 0.1)),
+            projectile_mesh,
+            projectile_material: materials.add(Color::WHITE),
+            pickup_mesh,
+            pickup_materials: PowerUpKind::ALL.map(|kind| materials.add(kind.color())),
+            particle_materials: Preset::ALL
+                .map(|_| std::array::from_fn(|_| materials.add(Color::WHITE))),
+            rock_spawner_mesh,
+            rock_spawner_material: materials.add(Color::BLUE),
+            ship_spawner_mesh,
+            ship_spawner_material: materials.add(Color::RED),
+            power_up_spawner_material: materials.add(Color::GOLD),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolKind {
+    Rock,
+    Ship,
+    Projectile,
+}
+
+// Marks a hidden entity waiting in the pool.
+#[derive(Component)]
+pub struct Pooled;
+
+#[derive(Resource, Default)]
+pub struct EntityPool {
+    rocks: Vec<Entity>,
+    ships: Vec<Entity>,
+    projectiles: Vec<Entity>,
+}
+
+impl EntityPool {
+    fn list(&mut self, kind: PoolKind) -> &mut Vec<Entity> {
+        match kind {
+            PoolKind::Rock => &mut self.rocks,
+            PoolKind::Ship => &mut self.ships,
+            PoolKind::Projectile => &mut self.projectiles,
+        }
+    }
+    pub fn free(&self) -> usize {
+        self.rocks.len() + self.ships.len() + self.projectiles.len()
+    }
+
+    // A recycled entity when one is free, otherwise a new empty one. Callers
+    // insert the full bundle, which also makes it visible again.
+    pub fn take(&mut self, cmds: &mut Commands, kind: PoolKind) -> Entity {
+        while let Some(e) = self.list(kind).pop() {
+            // Entities can be despawned behind the pool's back, e.g. by a new game.
+            if let Some(mut entity) = cmds.get_entity(e) {
+                entity.remove::<Pooled>();
+                return e;
+            }
+        }
+        cmds.spawn_empty().id()
+    }
+
+    // Strips the gameplay components so no system sees the entity until it's taken again.
+    pub fn release(&mut self, cmds: &mut Commands, kind: PoolKind, e: Entity) {
+        let list = self.list(kind);
+        // Two systems can hit the same enemy in one frame.
+        if list.contains(&e) {
+            return;
+        }
+        if list.len() >= POOL_LIMIT {
+            cmds.entity(e).despawn_recursive();
+            return;
+        }
+        list.push(e);
+        cmds.entity(e)
+            .remove::<(Rock, Ship, Attack, MoveTo, Projectile, Sepax, Movable)>()
+            .insert((Pooled, Visibility::Hidden));
+    }
+}
+
+pub struct PoolPlugin;
+impl Plugin for PoolPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameAssets>()
+            .init_resource::<EntityPool>()
+            .register_diagnostic(Diagnostic::new(MESH_COUNT))
+            .register_diagnostic(Diagnostic::new(MATERIAL_COUNT))
+            .register_diagnostic(Diagnostic::new(POOLED_COUNT))
+            .add_systems(Last, count_assets);
+    }
+}
+
+fn count_assets(
+    mut diagnostics: Diagnostics,
+    meshes: Res<Assets<Mesh>>,
+    materials: Res<Assets<ColorMaterial>>,
+    pool: Res<EntityPool>,
+) {
+    diagnostics.add_measurement(&MESH_COUNT, || meshes.len() as f64);
+    diagnostics.add_measurement(&MATERIAL_COUNT, || materials.len() as f64);
+    diagnostics.add_measurement(&POOLED_COUNT, || pool.free() as f64);
+}
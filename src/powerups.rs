@@ -0,0 +1,323 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use bevy_sepax2d::prelude::{
+    sepax2d::{sat_overlap, Circle as SpxCircle},
+    *,
+};
+use rand::Rng;
+
+use crate::{
+    areas::PlayArea, hud::HudTimer, pool::GameAssets, sampling::GameRng, weapon::Weapon, Health,
+    Player, Spawner, PLAYER_HEALTH,
+};
+
+pub const PICKUP_RADIUS: f32 = 12.0;
+const PICKUP_LIFETIME: f32 = 10.0;
+// Pickups land within this distance of their spawner.
+const PICKUP_SCATTER: f32 = 80.0;
+// Pickups lying around at once, across all spawners.
+const MAX_PICKUPS: usize = 3;
+const SPEED_BOOST: f32 = 1.5;
+const RAPID_FIRE: f32 = 3.0;
+const HEAL: i32 = 25;
+
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PowerUpKind {
+    #[default]
+    Shield,
+    Speed,
+    RapidFire,
+    Health,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 4] = [
+        PowerUpKind::Shield,
+        PowerUpKind::Speed,
+        PowerUpKind::RapidFire,
+        PowerUpKind::Health,
+    ];
+
+    // Name used in arena files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PowerUpKind::Shield => "shield",
+            PowerUpKind::Speed => "speed",
+            PowerUpKind::RapidFire => "rapid",
+            PowerUpKind::Health => "health",
+        }
+    }
+    pub fn label(&self) -> &'static str {
+        match self {
+            PowerUpKind::Shield => "Shield",
+            PowerUpKind::Speed => "Speed",
+            PowerUpKind::RapidFire => "Rapid fire",
+            PowerUpKind::Health => "Health",
+        }
+    }
+    // Seconds the effect lasts, `None` for instant ones.
+    pub fn duration(&self) -> Option<f32> {
+        match self {
+            PowerUpKind::Shield => Some(8.0),
+            PowerUpKind::Speed => Some(10.0),
+            PowerUpKind::RapidFire => Some(8.0),
+            PowerUpKind::Health => None,
+        }
+    }
+    pub fn color(&self) -> Color {
+        match self {
+            PowerUpKind::Shield => Color::AQUAMARINE,
+            PowerUpKind::Speed => Color::GOLD,
+            PowerUpKind::RapidFire => Color::ORANGE,
+            PowerUpKind::Health => Color::LIME_GREEN,
+        }
+    }
+}
+
+#[derive(Reflect, Clone, Debug, PartialEq)]
+pub struct TableEntry {
+    pub kind: PowerUpKind,
+    // Relative chance of this power-up being dropped.
+    pub weight: f32,
+}
+
+pub fn default_table() -> Vec<TableEntry> {
+    PowerUpKind::ALL
+        .iter()
+        .map(|kind| TableEntry {
+            kind: *kind,
+            weight: if *kind == PowerUpKind::Health {
+                2.0
+            } else {
+                1.0
+            },
+        })
+        .collect()
+}
+
+pub fn choose(table: &[TableEntry], rng: &mut impl Rng) -> Option<PowerUpKind> {
+    let total: f32 = table.iter().map(|e| e.weight.max(0.)).sum();
+    if total <= 0. {
+        return None;
+    }
+    let mut pick = rng.gen_range(0.0..total);
+    table
+        .iter()
+        .find(|e| {
+            pick -= e.weight.max(0.);
+            pick < 0.
+        })
+        .or(table.last())
+        .map(|e| e.kind)
+}
+
+#[derive(Component)]
+pub struct Pickup {
+    pub kind: PowerUpKind,
+    pub life: Timer,
+}
+
+// Blocks all damage while present on a player.
+#[derive(Component)]
+pub struct Shielded;
+
+// One per player and timed power-up, its `HudTimer` counts down the rest.
+#[derive(Component)]
+pub struct PowerUpEffect {
+    pub kind: PowerUpKind,
+    pub player: Entity,
+}
+
+pub struct PowerUpPlugin;
+impl Plugin for PowerUpPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, draw_shields).add_systems(
+            FixedUpdate,
+            (
+                spawn_pickups,
+                expire_pickups,
+                collect_pickups.after(crate::player_movement),
+                expire_effects.before(collect_pickups),
+            ),
+        );
+    }
+}
+
+fn spawn_pickups(
+    mut cmds: Commands,
+    assets: Res<GameAssets>,
+    play: Res<PlayArea>,
+    mut rng: ResMut<GameRng>,
+    time: Res<Time>,
+    mut spawners: Query<(&mut Spawner, &Transform)>,
+    pickups: Query<(), With<Pickup>>,
+) {
+    let mut count = pickups.iter().count();
+    for (mut s, transform) in spawners.iter_mut() {
+        let Spawner::PowerUp { timer, table } = s.as_mut() else {
+            continue;
+        };
+        timer.tick(time.delta());
+        if !timer.just_finished() || count >= MAX_PICKUPS {
+            continue;
+        }
+        let rng = &mut rng.0;
+        let Some(kind) = choose(table, rng) else {
+            continue;
+        };
+        // Near the spawner, but never out of the players' reach.
+        let offset = Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU))
+            * PICKUP_SCATTER
+            * rng.gen::<f32>().sqrt();
+        let point = transform.translation.xy() + offset;
+        let point = play.regions.closest(point).unwrap_or(point);
+        create_pickup(&mut cmds, &assets, kind, point);
+        count += 1;
+    }
+}
+
+pub fn create_pickup(
+    cmds: &mut Commands,
+    assets: &GameAssets,
+    kind: PowerUpKind,
+    position: Vec2,
+) -> Entity {
+    let (mesh, material) = assets.pickup(kind);
+    cmds.spawn((
+        MaterialMesh2dBundle {
+            mesh,
+            material,
+            transform: Transform::from_xyz(position.x, position.y, 1.),
+            ..default()
+        },
+        Sepax {
+            convex: Convex::Circle(SpxCircle::new((0., 0.), PICKUP_RADIUS)),
+        },
+        Pickup {
+            kind,
+            life: Timer::from_seconds(PICKUP_LIFETIME, TimerMode::Once),
+        },
+    ))
+    .id()
+}
+
+fn expire_pickups(mut cmds: Commands, time: Res<Time>, mut pickups: Query<(Entity, &mut Pickup)>) {
+    for (e, mut pickup) in pickups.iter_mut() {
+        if pickup.life.tick(time.delta()).finished() {
+            cmds.entity(e).despawn();
+        }
+    }
+}
+
+fn collect_pickups(
+    mut cmds: Commands,
+    pickups: Query<(Entity, &Pickup, &Sepax)>,
+    mut players: Query<
+        (
+            Entity,
+            &Sepax,
+            &mut Player,
+            &mut Health,
+            Option<&mut Weapon>,
+        ),
+        Without<Pickup>,
+    >,
+    mut effects: Query<(&PowerUpEffect, &mut HudTimer)>,
+) {
+    for (pickup_entity, pickup, pickup_shape) in pickups.iter() {
+        let Some((player_entity, _, mut player, mut health, mut weapon)) = players
+            .iter_mut()
+            .find(|(_, shape, ..)| sat_overlap(shape.shape(), pickup_shape.shape()))
+        else {
+            continue;
+        };
+        cmds.entity(pickup_entity).despawn();
+        let kind = pickup.kind;
+        let Some(duration) = kind.duration() else {
+            // Health is the only instant power-up.
+            health.0 = (health.0 + HEAL).min(PLAYER_HEALTH);
+            continue;
+        };
+        // Picking up an active power-up again only restarts its timer.
+        let active = effects
+            .iter_mut()
+            .find(|(effect, _)| effect.player == player_entity && effect.kind == kind);
+        if let Some((_, mut hud)) = active {
+            hud.timer.reset();
+            continue;
+        }
+        if kind == PowerUpKind::Shield {
+            cmds.entity(player_entity).insert(Shielded);
+        }
+        boost(kind, &mut player, weapon.as_deref_mut(), false);
+        cmds.spawn((
+            PowerUpEffect {
+                kind,
+                player: player_entity,
+            },
+            HudTimer {
+                label: kind.label().into(),
+                timer: Timer::from_seconds(duration, TimerMode::Once),
+            },
+        ));
+    }
+}
+
+// Undoes whatever `collect_pickups` changed once the timer runs out.
+fn expire_effects(
+    mut cmds: Commands,
+    time: Res<Time>,
+    mut effects: Query<(Entity, &PowerUpEffect, &mut HudTimer)>,
+    mut players: Query<(&mut Player, Option<&mut Weapon>)>,
+) {
+    for (e, effect, mut hud) in effects.iter_mut() {
+        if !hud.timer.tick(time.delta()).finished() {
+            continue;
+        }
+        cmds.entity(e).despawn();
+        let Ok((mut player, mut weapon)) = players.get_mut(effect.player) else {
+            continue;
+        };
+        if effect.kind == PowerUpKind::Shield {
+            cmds.entity(effect.player).remove::<Shielded>();
+        }
+        boost(effect.kind, &mut player, weapon.as_deref_mut(), true);
+    }
+}
+
+// Speed and rapid fire change the player's own stats while active, `undo`
+// puts them back.
+pub fn boost(kind: PowerUpKind, player: &mut Player, weapon: Option<&mut Weapon>, undo: bool) {
+    match kind {
+        PowerUpKind::Speed if undo => {
+            player.movement_speed /= SPEED_BOOST;
+            player.rotation_speed /= SPEED_BOOST;
+        }
+        PowerUpKind::Speed => {
+            player.movement_speed *= SPEED_BOOST;
+            player.rotation_speed *= SPEED_BOOST;
+        }
+        PowerUpKind::RapidFire => {
+            let Some(weapon) = weapon else {
+                return;
+            };
+            let cooldown = weapon.cooldown.duration().as_secs_f32();
+            let cooldown = if undo {
+                cooldown * RAPID_FIRE
+            } else {
+                cooldown / RAPID_FIRE
+            };
+            weapon
+                .cooldown
+                .set_duration(Duration::from_secs_f32(cooldown));
+        }
+        PowerUpKind::Shield | PowerUpKind::Health => {}
+    }
+}
+
+fn draw_shields(mut gizmos: Gizmos, players: Query<&Transform, With<Shielded>>) {
+    for t in players.iter() {
+        gizmos.circle_2d(t.translation.xy(), 35.0, PowerUpKind::Shield.color());
+    }
+}
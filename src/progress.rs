@@ -0,0 +1,362 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    arena::{self, Arena, SpawnerKind},
+    players::{self, LivesPool, PlayerSetup},
+    pool::GameAssets,
+    powerups::{Pickup, PowerUpEffect},
+    sampling::SpawnSampler,
+    weapon::{Enemy, EnemyDestroyed, Projectile},
+    Health, Player, Rock, Ship, Spawner, PLAYER_HEALTH,
+};
+
+pub const START_LIVES: u32 = 3;
+pub const WAVE_SECONDS: f32 = 30.0;
+// Each wave shortens every spawner timer by this factor.
+const WAVE_SPEEDUP: f32 = 0.9;
+const MIN_SPAWNER_TIMER: f32 = 0.5;
+const POINTS_PER_SECOND: f32 = 10.0;
+// Seconds without being hit for each step of the multiplier.
+const STREAK_STEP: f32 = 10.0;
+const MAX_MULTIPLIER: u32 = 8;
+const SHIP_POINTS: f32 = 50.0;
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PlayerHit {
+    pub player: Entity,
+    pub damage: i32,
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct GameOver;
+
+// Clears the world and starts over from the arena's spawners.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct NewGame;
+
+#[derive(Resource, Reflect, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[reflect(Resource)]
+pub enum GameMode {
+    #[default]
+    Survival,
+    // Lives are never lost, for practising and testing.
+    Endless,
+}
+impl GameMode {
+    pub const ALL: [GameMode; 2] = [GameMode::Survival, GameMode::Endless];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GameMode::Survival => "Survival",
+            GameMode::Endless => "Endless",
+        }
+    }
+    pub fn next(&self) -> Self {
+        match self {
+            GameMode::Survival => GameMode::Endless,
+            GameMode::Endless => GameMode::Survival,
+        }
+    }
+}
+
+#[derive(Resource, Reflect, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[reflect(Resource)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+    pub fn next(&self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+    // Scales the time between rock and ship spawns at the start of a game.
+    fn spawn_interval(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 1.5,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 0.7,
+        }
+    }
+}
+
+#[derive(Resource, Reflect, Debug, Clone)]
+#[reflect(Resource)]
+pub struct Score {
+    pub points: u64,
+    pub multiplier: u32,
+    // Time since the player was last hit.
+    pub streak: f32,
+    // Fractional points not yet added to `points`.
+    carry: f32,
+}
+impl Default for Score {
+    fn default() -> Self {
+        Self {
+            points: 0,
+            multiplier: 1,
+            streak: 0.0,
+            carry: 0.0,
+        }
+    }
+}
+impl Score {
+    pub fn add(&mut self, points: f32) {
+        self.carry += points * self.multiplier as f32;
+        let whole = self.carry.floor();
+        self.points += whole as u64;
+        self.carry -= whole;
+    }
+}
+
+// The shared pool, unused when each player has `OwnLives`.
+#[derive(Resource, Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+#[reflect(Resource)]
+pub struct Lives(pub u32);
+impl Default for Lives {
+    fn default() -> Self {
+        Self(START_LIVES)
+    }
+}
+
+// A player's own lives when the pool is split.
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct OwnLives(pub u32);
+impl Default for OwnLives {
+    fn default() -> Self {
+        Self(START_LIVES)
+    }
+}
+
+#[derive(Resource, Reflect, Debug, Clone)]
+#[reflect(Resource)]
+pub struct Wave {
+    pub number: u32,
+    pub timer: Timer,
+}
+impl Default for Wave {
+    fn default() -> Self {
+        Self {
+            number: 1,
+            timer: Timer::from_seconds(WAVE_SECONDS, TimerMode::Repeating),
+        }
+    }
+}
+
+pub struct ProgressPlugin;
+impl Plugin for ProgressPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Score>()
+            .init_resource::<Lives>()
+            .init_resource::<Wave>()
+            .init_resource::<GameMode>()
+            .init_resource::<Difficulty>()
+            .add_event::<PlayerHit>()
+            .add_event::<GameOver>()
+            .add_event::<NewGame>()
+            .add_systems(PreUpdate, new_game.run_if(on_event::<NewGame>()))
+            .add_systems(
+                FixedUpdate,
+                (
+                    score_survival.after(crate::player_collision),
+                    score_kills,
+                    next_wave,
+                    lose_life.after(crate::player_collision),
+                ),
+            );
+    }
+}
+
+fn score_survival(time: Res<Time>, mut hits: EventReader<PlayerHit>, mut score: ResMut<Score>) {
+    if hits.read().count() > 0 {
+        score.streak = 0.0;
+    } else {
+        score.streak += time.delta_seconds();
+    }
+    score.multiplier = (1 + (score.streak / STREAK_STEP) as u32).min(MAX_MULTIPLIER);
+    score.add(POINTS_PER_SECOND * time.delta_seconds());
+}
+
+// Rocks are worth their radius, bigger ones take longer to dodge.
+fn score_kills(mut destroyed: EventReader<EnemyDestroyed>, mut score: ResMut<Score>) {
+    for d in destroyed.read() {
+        score.add(match d.enemy {
+            Enemy::Rock { size } => size,
+            Enemy::Ship => SHIP_POINTS,
+        });
+    }
+}
+
+fn next_wave(time: Res<Time>, mut wave: ResMut<Wave>, mut spawners: Query<&mut Spawner>) {
+    // Ticking alone should not count as a change, the HUD only cares about the number.
+    let timer = &mut wave.bypass_change_detection().timer;
+    timer.tick(time.delta());
+    if !timer.just_finished() {
+        return;
+    }
+    wave.number += 1;
+    info!("wave {}", wave.number);
+    for mut spawner in spawners.iter_mut() {
+        speed_up(&mut spawner, 1);
+    }
+}
+
+// Shortens rock and ship spawn timers by `waves` waves' worth, negative
+// counts slow them down again.
+pub fn speed_up(spawner: &mut Spawner, waves: i32) {
+    let timer = match spawner {
+        Spawner::Rock { timer, .. } | Spawner::Ship { timer } => timer,
+        // Power-ups keep dropping at their own pace.
+        Spawner::PowerUp { .. } => return,
+    };
+    let secs = timer.duration().as_secs_f32() * WAVE_SPEEDUP.powi(waves);
+    // Enough waves back the factor overflows, the timer is then left as it is.
+    if let Ok(duration) = Duration::try_from_secs_f32(secs.max(MIN_SPAWNER_TIMER)) {
+        timer.set_duration(duration);
+    }
+}
+
+// Respawns a player at their start position with full health while lives
+// remain, otherwise they're out. The game ends when nobody is left.
+fn lose_life(
+    mut cmds: Commands,
+    mode: Res<GameMode>,
+    setup: Res<PlayerSetup>,
+    mut lives: ResMut<Lives>,
+    mut players: Query<(
+        Entity,
+        &Player,
+        &mut Health,
+        &mut Transform,
+        Option<&mut OwnLives>,
+    )>,
+    mut over: EventWriter<GameOver>,
+) {
+    let mut remaining = players.iter().count();
+    for (e, player, mut health, mut transform, own) in players.iter_mut() {
+        if health.0 > 0 {
+            continue;
+        }
+        let pool = match own {
+            Some(own) => &mut own.into_inner().0,
+            None => &mut lives.0,
+        };
+        if *mode != GameMode::Endless {
+            *pool = pool.saturating_sub(1);
+        }
+        if *pool == 0 {
+            info!("player {} is out", player.slot + 1);
+            cmds.entity(e).despawn_recursive();
+            remaining -= 1;
+            continue;
+        }
+        health.0 = PLAYER_HEALTH;
+        let start = players::start_position(player.slot, setup.count);
+        *transform = Transform::from_xyz(start.x, start.y, 0.);
+    }
+    if remaining == 0 && !players.is_empty() {
+        info!("game over");
+        over.send(GameOver);
+    }
+}
+
+fn new_game(
+    mut cmds: Commands,
+    mut events: EventReader<NewGame>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    assets: Res<GameAssets>,
+    arena: Option<Res<Arena>>,
+    mut sampler: ResMut<SpawnSampler>,
+    setup: Res<PlayerSetup>,
+    difficulty: Res<Difficulty>,
+    entities: Query<
+        Entity,
+        Or<(
+            With<Player>,
+            With<Rock>,
+            With<Ship>,
+            With<Spawner>,
+            With<Pickup>,
+            With<Projectile>,
+            With<PowerUpEffect>,
+        )>,
+    >,
+) {
+    events.clear();
+    for e in entities.iter() {
+        cmds.entity(e).despawn_recursive();
+    }
+    cmds.insert_resource(Score::default());
+    cmds.insert_resource(Lives::default());
+    cmds.insert_resource(Wave::default());
+    sampler.clear_history();
+    for s in arena.iter().flat_map(|a| a.spawners.iter()) {
+        let mut def = s.clone();
+        if def.kind != SpawnerKind::PowerUp {
+            def.timer *= difficulty.spawn_interval();
+        }
+        arena::spawn_spawner(&mut cmds, &mut meshes, &assets, &def);
+    }
+    for slot in 0..setup.count {
+        let position = players::start_position(slot, setup.count);
+        let player = crate::create_player(&mut cmds, &mut meshes, &mut materials, slot, position);
+        if setup.lives == LivesPool::Split {
+            cmds.entity(player).insert(OwnLives::default());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ship(secs: f32) -> Spawner {
+        Spawner::Ship {
+            timer: Timer::from_seconds(secs, TimerMode::Repeating),
+        }
+    }
+    fn secs(spawner: &Spawner) -> f32 {
+        match spawner {
+            Spawner::Ship { timer } => timer.duration().as_secs_f32(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn speeds_up_and_back_down() {
+        let mut spawner = ship(2.0);
+        speed_up(&mut spawner, 2);
+        assert!((secs(&spawner) - 2.0 * 0.81).abs() < 1e-4);
+        speed_up(&mut spawner, -2);
+        assert!((secs(&spawner) - 2.0).abs() < 1e-4);
+        speed_up(&mut spawner, 100);
+        assert_eq!(secs(&spawner), MIN_SPAWNER_TIMER);
+    }
+
+    #[test]
+    fn survives_huge_wave_counts() {
+        let mut spawner = ship(2.0);
+        speed_up(&mut spawner, i32::MAX);
+        assert_eq!(secs(&spawner), MIN_SPAWNER_TIMER);
+        speed_up(&mut spawner, i32::MIN);
+        assert_eq!(secs(&spawner), MIN_SPAWNER_TIMER);
+    }
+}
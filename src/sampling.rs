@@ -0,0 +1,313 @@
+use std::{collections::VecDeque, fmt};
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::areas::{Regions, SafeArea, SpawnArea};
+
+// Cells per axis of the fallback grid laid over each spawn region.
+const GRID: u32 = 32;
+// Points per axis tried when deciding whether a cell has room to spawn in,
+// so a gap narrower than a cell is still found.
+const PROBES: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleError {
+    // No spawn region, or every region has a weight of zero.
+    NoSpawnRegion,
+    // The safe area covers all of the spawn area.
+    FullyExcluded,
+    // Every candidate was too close to a player or a recent spawn.
+    TooClose,
+}
+impl fmt::Display for SampleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SampleError::NoSpawnRegion => write!(f, "the spawn area has no regions to spawn in"),
+            SampleError::FullyExcluded => {
+                write!(f, "the safe area covers the whole spawn area")
+            }
+            SampleError::TooClose => write!(
+                f,
+                "no spawn point is far enough from the players and recent spawns"
+            ),
+        }
+    }
+}
+impl std::error::Error for SampleError {}
+
+#[derive(Debug, Clone, Copy)]
+struct Cell {
+    rect: Rect,
+    // Index of the spawn region the cell was laid over.
+    region: usize,
+}
+
+#[derive(Resource)]
+pub struct SpawnSampler {
+    pub min_player_distance: f32,
+    pub min_spawn_distance: f32,
+    pub history: usize,
+    pub attempts: usize,
+    recent: VecDeque<Vec2>,
+    // Cells with room outside the safe area, and the running total of their
+    // weights to pick one by.
+    cells: Vec<Cell>,
+    cumulative: Vec<f32>,
+}
+impl Default for SpawnSampler {
+    fn default() -> Self {
+        Self {
+            min_player_distance: 200.0,
+            min_spawn_distance: 60.0,
+            history: 8,
+            attempts: 32,
+            recent: VecDeque::new(),
+            cells: Vec::new(),
+            cumulative: Vec::new(),
+        }
+    }
+}
+
+impl SpawnSampler {
+    // Each region gets a grid over its own bounds, so thin regions aren't lost
+    // between the cells of a bigger one. A cell is weighted by the region's
+    // weight over its area, the same density `Regions::sample` picks points with.
+    pub fn rebuild(&mut self, spawn: &Regions, exclude: &Regions) {
+        self.cells.clear();
+        self.cumulative.clear();
+        let mut total = 0.0;
+        for (i, region) in spawn.0.iter().enumerate() {
+            let area = region.shape.area();
+            if region.weight <= 0. || area <= 0. {
+                continue;
+            }
+            let bounds = region.shape.bounds();
+            let size = bounds.size() / GRID as f32;
+            let weight = region.weight / area * size.x * size.y;
+            for y in 0..GRID {
+                for x in 0..GRID {
+                    let min = bounds.min + size * Vec2::new(x as f32, y as f32);
+                    let rect = Rect::from_corners(min, min + size);
+                    let open = (0..PROBES * PROBES).any(|k| {
+                        let t = Vec2::new((k % PROBES) as f32, (k / PROBES) as f32);
+                        let p = rect.min + rect.size() * t / (PROBES - 1) as f32;
+                        region.shape.contains(p) && !exclude.contains(p)
+                    });
+                    if open {
+                        total += weight;
+                        self.cells.push(Cell { rect, region: i });
+                        self.cumulative.push(total);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn clear_history(&mut self) {
+        self.recent.clear();
+    }
+    // Latest accepted points, oldest first.
+    pub fn recent(&self) -> impl Iterator<Item = &Vec2> {
+        self.recent.iter()
+    }
+
+    fn accepts(&self, p: Vec2, exclude: &Regions, players: &[Vec2]) -> bool {
+        !exclude.contains(p)
+            && players
+                .iter()
+                .all(|pl| pl.distance(p) >= self.min_player_distance)
+            && self
+                .recent
+                .iter()
+                .all(|r| r.distance(p) >= self.min_spawn_distance)
+    }
+
+    // Picks a cell by weight and a point in it, `None` when the point falls
+    // outside the cell's region.
+    fn sample_cell(&self, rng: &mut impl Rng, spawn: &Regions) -> Option<Vec2> {
+        let total = *self.cumulative.last()?;
+        let pick = rng.gen_range(0.0..total);
+        let i = self
+            .cumulative
+            .partition_point(|c| *c <= pick)
+            .min(self.cells.len() - 1);
+        let Cell { rect, region } = self.cells[i];
+        let p = Vec2::new(
+            rng.gen_range(rect.min.x..=rect.max.x),
+            rng.gen_range(rect.min.y..=rect.max.y),
+        );
+        spawn.0[region].shape.contains(p).then_some(p)
+    }
+
+    // Uniform over the spawn regions, weighted per region, minus the excluded
+    // area and whatever is too close to players and recent spawns. Candidates
+    // are drawn from the regions first, then from the grid, which wastes fewer
+    // of them when most of the area is excluded; both give the same
+    // distribution. The grid only misses a gap that none of a cell's probe
+    // points land in, and the number of candidates is always bounded.
+    pub fn sample(
+        &mut self,
+        rng: &mut impl Rng,
+        spawn: &Regions,
+        exclude: &Regions,
+        players: &[Vec2],
+    ) -> Result<Vec2, SampleError> {
+        if spawn.0.iter().all(|r| r.weight <= 0.) {
+            return Err(SampleError::NoSpawnRegion);
+        }
+        let from_regions = (0..self.attempts)
+            .filter_map(|_| spawn.sample(rng))
+            .find(|p| self.accepts(*p, exclude, players));
+        let point = match from_regions {
+            Some(p) => p,
+            None if self.cells.is_empty() => return Err(SampleError::FullyExcluded),
+            None => (0..self.attempts)
+                .filter_map(|_| self.sample_cell(rng, spawn))
+                .find(|p| self.accepts(*p, exclude, players))
+                .ok_or(SampleError::TooClose)?,
+        };
+        self.recent.push_back(point);
+        while self.recent.len() > self.history {
+            self.recent.pop_front();
+        }
+        Ok(point)
+    }
+}
+
+// Every random choice the simulation makes comes from here, so two games
+// with the same seed and inputs play out the same.
+#[derive(Resource)]
+pub struct GameRng(pub StdRng);
+impl Default for GameRng {
+    fn default() -> Self {
+        Self(StdRng::from_entropy())
+    }
+}
+impl GameRng {
+    pub fn seeded(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
+    }
+}
+
+pub struct SamplingPlugin;
+impl Plugin for SamplingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpawnSampler>()
+            .init_resource::<GameRng>()
+            .add_systems(PreUpdate, rebuild_sampler);
+    }
+}
+
+fn rebuild_sampler(mut sampler: ResMut<SpawnSampler>, spawn: Res<SpawnArea>, safe: Res<SafeArea>) {
+    if spawn.is_changed() || safe.is_changed() {
+        sampler.rebuild(&spawn.regions, &safe.regions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::areas::{AreaShape, Region};
+
+    fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> Region {
+        Region::new(AreaShape::Rect(Rect::new(x0, y0, x1, y1)))
+    }
+
+    // No distance limits, so only the areas decide.
+    fn sampler(spawn: &Regions, exclude: &Regions) -> SpawnSampler {
+        let mut sampler = SpawnSampler {
+            min_player_distance: 0.,
+            min_spawn_distance: 0.,
+            history: 0,
+            ..default()
+        };
+        sampler.rebuild(spawn, exclude);
+        sampler
+    }
+
+    #[test]
+    fn missing_regions_use_no_randomness() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let before = rng.clone().gen::<u64>();
+        let zero = Regions(vec![Region {
+            weight: 0.,
+            ..rect(0., 0., 10., 10.)
+        }]);
+        for spawn in [Regions::default(), zero] {
+            let mut sampler = sampler(&spawn, &Regions::default());
+            assert_eq!(
+                sampler.sample(&mut rng, &spawn, &Regions::default(), &[]),
+                Err(SampleError::NoSpawnRegion)
+            );
+        }
+        assert_eq!(rng.gen::<u64>(), before);
+    }
+
+    #[test]
+    fn reports_a_fully_excluded_area() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let spawn = Regions(vec![rect(0., 0., 10., 10.)]);
+        let exclude = Regions(vec![rect(-1., -1., 11., 11.)]);
+        let mut sampler = sampler(&spawn, &exclude);
+        assert_eq!(
+            sampler.sample(&mut rng, &spawn, &exclude, &[]),
+            Err(SampleError::FullyExcluded)
+        );
+    }
+
+    #[test]
+    fn reports_players_too_close() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let spawn = Regions(vec![rect(0., 0., 10., 10.)]);
+        let mut sampler = sampler(&spawn, &Regions::default());
+        sampler.min_player_distance = 100.;
+        assert_eq!(
+            sampler.sample(&mut rng, &spawn, &Regions::default(), &[Vec2::splat(5.)]),
+            Err(SampleError::TooClose)
+        );
+    }
+
+    #[test]
+    fn finds_a_thin_region_next_to_an_excluded_one() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let spawn = Regions(vec![
+            rect(0., 0., 1000., 1000.),
+            rect(0., 1000., 1000., 1001.),
+        ]);
+        let exclude = Regions(vec![rect(-1., -1., 1001., 1000.)]);
+        let mut sampler = sampler(&spawn, &exclude);
+        sampler.attempts = 4;
+        for _ in 0..200 {
+            let p = sampler.sample(&mut rng, &spawn, &exclude, &[]).unwrap();
+            assert!(p.y >= 1000. && !exclude.contains(p), "{p}");
+        }
+    }
+
+    // With most of both regions excluded the grid supplies most points, and
+    // they still follow the weights and spread evenly over what's left.
+    #[test]
+    fn follows_weights_and_stays_uniform() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let spawn = Regions(vec![
+            rect(0., 0., 100., 100.),
+            Region {
+                weight: 3.,
+                ..rect(200., 0., 300., 100.)
+            },
+        ]);
+        let exclude = Regions(vec![rect(-1., -1., 301., 90.)]);
+        let mut sampler = sampler(&spawn, &exclude);
+        sampler.attempts = 8;
+        let points: Vec<Vec2> = (0..4000)
+            .map(|_| sampler.sample(&mut rng, &spawn, &exclude, &[]).unwrap())
+            .collect();
+        assert!(points.iter().all(|p| p.y >= 90.));
+        let share = |f: &dyn Fn(&Vec2) -> bool| {
+            points.iter().filter(|p| f(p)).count() as f32 / points.len() as f32
+        };
+        assert!((share(&|p: &Vec2| p.x >= 200.) - 0.75).abs() < 0.03);
+        assert!((share(&|p: &Vec2| p.y >= 95.) - 0.5).abs() < 0.03);
+        assert!((share(&|p: &Vec2| p.x % 100. < 50.) - 0.5).abs() < 0.03);
+    }
+}
@@ -0,0 +1,500 @@
+use std::{
+    fmt::{self, Write},
+    fs,
+    path::PathBuf,
+};
+
+use bevy::{
+    audio::Volume,
+    prelude::*,
+    scene::ron,
+    window::{PresentMode, PrimaryWindow, WindowMode},
+};
+
+use crate::{
+    hud::HudSettings,
+    minimap::Minimap,
+    players::{self, Device, PlayerControls, MAX_PLAYERS},
+    view::{PlayField, ScaleMode},
+    Player, Rock, Ship, Spawner,
+};
+
+const FILE_NAME: &str = "settings.cfg";
+pub const VOLUME_STEP: f32 = 0.1;
+
+// `$XDG_CONFIG_HOME/game`, falling back to `~/.config/game` or `%APPDATA%\game`.
+pub fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .map(|dir| dir.join("game"))
+}
+
+// `$XDG_DATA_HOME/game`, falling back to `~/.local/share/game` or `%APPDATA%\game`.
+pub fn data_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .map(|dir| dir.join("game"))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Palette {
+    #[default]
+    Classic,
+    HighContrast,
+    // Okabe-Ito colours, distinguishable with the common colour blindnesses.
+    ColorSafe,
+}
+
+pub struct PaletteColors {
+    pub player: Color,
+    pub rock: Color,
+    pub ship: Color,
+    pub rock_spawner: Color,
+    pub ship_spawner: Color,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 3] = [Palette::Classic, Palette::HighContrast, Palette::ColorSafe];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Palette::Classic => "classic",
+            Palette::HighContrast => "contrast",
+            Palette::ColorSafe => "colorsafe",
+        }
+    }
+    pub fn next(&self) -> Self {
+        let i = Self::ALL.iter().position(|p| p == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+    pub fn colors(&self) -> PaletteColors {
+        match self {
+            Palette::Classic => PaletteColors {
+                player: Color::rgb(0.4, 0.8, 0.1),
+                rock: Color::rgb(0.4, 0.8, 0.1),
+                ship: Color::PINK,
+                rock_spawner: Color::BLUE,
+                ship_spawner: Color::RED,
+            },
+            Palette::HighContrast => PaletteColors {
+                player: Color::WHITE,
+                rock: Color::YELLOW,
+                ship: Color::RED,
+                rock_spawner: Color::CYAN,
+                ship_spawner: Color::FUCHSIA,
+            },
+            Palette::ColorSafe => PaletteColors {
+                player: Color::rgb(0.0, 0.45, 0.7),
+                rock: Color::rgb(0.9, 0.6, 0.0),
+                ship: Color::rgb(0.8, 0.47, 0.65),
+                rock_spawner: Color::rgb(0.34, 0.71, 0.91),
+                ship_spawner: Color::rgb(0.84, 0.37, 0.0),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binding {
+    Forward,
+    Left,
+    Right,
+    Fire,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyBindings {
+    pub forward: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub fire: KeyCode,
+}
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            forward: KeyCode::KeyW,
+            left: KeyCode::KeyA,
+            right: KeyCode::KeyD,
+            fire: KeyCode::Space,
+        }
+    }
+}
+impl KeyBindings {
+    pub const ALL: [Binding; 4] = [
+        Binding::Forward,
+        Binding::Left,
+        Binding::Right,
+        Binding::Fire,
+    ];
+
+    pub fn get(&self, binding: Binding) -> KeyCode {
+        match binding {
+            Binding::Forward => self.forward,
+            Binding::Left => self.left,
+            Binding::Right => self.right,
+            Binding::Fire => self.fire,
+        }
+    }
+    pub fn set(&mut self, binding: Binding, key: KeyCode) {
+        match binding {
+            Binding::Forward => self.forward = key,
+            Binding::Left => self.left = key,
+            Binding::Right => self.right = key,
+            Binding::Fire => self.fire = key,
+        }
+    }
+}
+impl Binding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Binding::Forward => "forward",
+            Binding::Left => "left",
+            Binding::Right => "right",
+            Binding::Fire => "fire",
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub master_volume: f32,
+    pub effects_volume: f32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    pub palette: Palette,
+    pub scale_mode: ScaleMode,
+    pub minimap: bool,
+    pub show_fps: bool,
+    // One entry per player slot, whether or not that many are playing.
+    pub controls: [PlayerControls; MAX_PLAYERS],
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            master_volume: 0.8,
+            effects_volume: 1.0,
+            fullscreen: false,
+            vsync: true,
+            palette: Palette::default(),
+            scale_mode: ScaleMode::default(),
+            minimap: true,
+            show_fps: cfg!(feature = "debug"),
+            controls: std::array::from_fn(PlayerControls::default_for),
+        }
+    }
+}
+
+impl Settings {
+    pub fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join(FILE_NAME))
+    }
+
+    // Missing files are normal on first start, broken ones are reported and ignored.
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        match fs::read_to_string(&path) {
+            Ok(source) => Self::parse(&source).unwrap_or_else(|e| {
+                error!("ignoring {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    // One `key value` pair per line, unknown keys are skipped so older
+    // builds can read files written by newer ones.
+    pub fn parse(source: &str) -> Result<Self, SettingsError> {
+        let mut settings = Self::default();
+        for (n, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(char::is_whitespace) else {
+                continue;
+            };
+            let value = value.trim();
+            let bad = || SettingsError(n + 1, key.to_string());
+            // NaN would get through the clamps below.
+            let float = || {
+                value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|v| v.is_finite())
+                    .ok_or_else(bad)
+            };
+            let flag = || match value {
+                "on" | "true" => Ok(true),
+                "off" | "false" => Ok(false),
+                _ => Err(bad()),
+            };
+            let key_code = || ron::from_str::<KeyCode>(value).map_err(|_| bad());
+            match key {
+                "master_volume" => settings.master_volume = float()?.clamp(0.0, 1.0),
+                "effects_volume" => settings.effects_volume = float()?.clamp(0.0, 1.0),
+                "fullscreen" => settings.fullscreen = flag()?,
+                "vsync" => settings.vsync = flag()?,
+                "minimap" => settings.minimap = flag()?,
+                "fps" => settings.show_fps = flag()?,
+                "palette" => {
+                    settings.palette = *Palette::ALL
+                        .iter()
+                        .find(|p| p.name() == value)
+                        .ok_or_else(bad)?
+                }
+                "display" => {
+                    settings.scale_mode = match value {
+                        "letterbox" => ScaleMode::Letterbox,
+                        "expand" => ScaleMode::Expand,
+                        _ => return Err(bad()),
+                    }
+                }
+                _ => {
+                    // Player one's keys have no prefix, the others are `p2_bind_fire` etc.
+                    let (slot, key) = match key.split_once('_') {
+                        Some((p, rest)) if p.starts_with('p') => match p[1..].parse::<usize>() {
+                            Ok(n) if (2..=MAX_PLAYERS).contains(&n) => (n - 1, rest),
+                            _ => continue,
+                        },
+                        _ => (0, key),
+                    };
+                    let controls = &mut settings.controls[slot];
+                    if key == "device" {
+                        controls.device = Device::parse(value).ok_or_else(bad)?;
+                    } else if let Some(binding) = KeyBindings::ALL
+                        .into_iter()
+                        .find(|b| key.strip_prefix("bind_") == Some(b.name()))
+                    {
+                        controls.keys.set(binding, key_code()?);
+                    }
+                }
+            }
+        }
+        Ok(settings)
+    }
+
+    pub fn to_source(&self) -> String {
+        let on_off = |b: bool| if b { "on" } else { "off" };
+        let mut out = String::new();
+        let _ = writeln!(out, "master_volume {}", self.master_volume);
+        let _ = writeln!(out, "effects_volume {}", self.effects_volume);
+        let _ = writeln!(out, "fullscreen {}", on_off(self.fullscreen));
+        let _ = writeln!(out, "vsync {}", on_off(self.vsync));
+        let _ = writeln!(out, "palette {}", self.palette.name());
+        let _ = writeln!(out, "display {}", self.scale_mode.name());
+        let _ = writeln!(out, "minimap {}", on_off(self.minimap));
+        let _ = writeln!(out, "fps {}", on_off(self.show_fps));
+        for (slot, controls) in self.controls.iter().enumerate() {
+            let prefix = match slot {
+                0 => String::new(),
+                n => format!("p{}_", n + 1),
+            };
+            let _ = writeln!(out, "{prefix}device {}", controls.device.name());
+            for binding in KeyBindings::ALL {
+                let key = ron::to_string(&controls.keys.get(binding)).unwrap_or_default();
+                let _ = writeln!(out, "{prefix}bind_{} {}", binding.name(), key);
+            }
+        }
+        out
+    }
+
+    pub fn save(&self) -> Result<PathBuf, std::io::Error> {
+        let path = Self::path().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "no config directory")
+        })?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, self.to_source())?;
+        Ok(path)
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        if self.fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        }
+    }
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        }
+    }
+    pub fn effects(&self) -> f32 {
+        self.master_volume * self.effects_volume
+    }
+}
+
+#[derive(Debug)]
+pub struct SettingsError(usize, String);
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: bad value for {}", self.0, self.1)
+    }
+}
+impl std::error::Error for SettingsError {}
+
+pub struct SettingsPlugin;
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<Settings>() {
+            app.insert_resource(Settings::load());
+        }
+        app.add_systems(
+            PostUpdate,
+            (apply_settings, save_settings).run_if(resource_changed::<Settings>),
+        )
+        .add_systems(PostUpdate, apply_palette);
+    }
+}
+
+fn apply_settings(
+    settings: Res<Settings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut volume: ResMut<GlobalVolume>,
+    sinks: Query<&AudioSink>,
+    mut field: ResMut<PlayField>,
+    mut minimap: ResMut<Minimap>,
+    mut hud: ResMut<HudSettings>,
+) {
+    for mut window in windows.iter_mut() {
+        if window.mode != settings.window_mode() {
+            window.mode = settings.window_mode();
+        }
+        if window.present_mode != settings.present_mode() {
+            window.present_mode = settings.present_mode();
+        }
+    }
+    // GlobalVolume only reaches sounds started later, running ones are updated directly.
+    volume.volume = Volume::new(settings.master_volume);
+    for sink in sinks.iter() {
+        sink.set_volume(settings.effects());
+    }
+    if field.mode != settings.scale_mode {
+        field.mode = settings.scale_mode;
+    }
+    minimap.visible = settings.minimap;
+    hud.show_fps = settings.show_fps;
+}
+
+fn save_settings(settings: Res<Settings>) {
+    // Loaded this frame, nothing new to write.
+    if settings.is_added() {
+        return;
+    }
+    match settings.save() {
+        Ok(path) => info!("saved settings to {}", path.display()),
+        Err(e) => error!("failed to save settings: {}", e),
+    }
+}
+
+// Recolours new entities, and every entity when the palette changes.
+fn apply_palette(
+    settings: Res<Settings>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    query: Query<(
+        Ref<Handle<ColorMaterial>>,
+        Option<&Player>,
+        Has<Rock>,
+        Has<Ship>,
+        Option<&Spawner>,
+    )>,
+) {
+    let colors = settings.palette.colors();
+    for (handle, player, rock, ship, spawner) in query.iter() {
+        if !handle.is_added() && !settings.is_changed() {
+            continue;
+        }
+        let color = match (player, rock, ship, spawner) {
+            (Some(player), ..) => players::color(player.slot, &colors),
+            (_, true, ..) => colors.rock,
+            (_, _, true, _) => colors.ship,
+            (.., Some(Spawner::Rock { .. })) => colors.rock_spawner,
+            (.., Some(Spawner::Ship { .. })) => colors.ship_spawner,
+            _ => continue,
+        };
+        if let Some(material) = materials.get_mut(handle.id()) {
+            material.color = color;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_round_trip() {
+        let settings = Settings::default();
+        assert_eq!(Settings::parse(&settings.to_source()).unwrap(), settings);
+    }
+
+    #[test]
+    fn changes_round_trip() {
+        let mut settings = Settings {
+            master_volume: 0.3,
+            fullscreen: true,
+            palette: Palette::ColorSafe,
+            scale_mode: ScaleMode::Expand,
+            show_fps: true,
+            ..default()
+        };
+        settings.controls[0].keys.set(Binding::Fire, KeyCode::Enter);
+        settings.controls[3].device = Device::Gamepad(2);
+        settings.controls[3]
+            .keys
+            .set(Binding::Left, KeyCode::ArrowLeft);
+        assert_eq!(Settings::parse(&settings.to_source()).unwrap(), settings);
+    }
+
+    #[test]
+    fn reads_player_prefixes() {
+        let source = "bind_fire Enter\np2_bind_fire KeyJ\np3_device gamepad 1\n";
+        let settings = Settings::parse(source).unwrap();
+        assert_eq!(settings.controls[0].keys.fire, KeyCode::Enter);
+        assert_eq!(settings.controls[1].keys.fire, KeyCode::KeyJ);
+        assert_eq!(settings.controls[2].device, Device::Gamepad(1));
+    }
+
+    #[test]
+    fn skips_unknown_keys_and_comments() {
+        let source = "# written by hand\nshiny on\np9_bind_fire KeyK\nvsync off # tearing\n";
+        let settings = Settings::parse(source).unwrap();
+        assert_eq!(
+            settings,
+            Settings {
+                vsync: false,
+                ..default()
+            }
+        );
+    }
+
+    #[test]
+    fn clamps_volumes() {
+        let settings = Settings::parse("master_volume 3\neffects_volume -1").unwrap();
+        assert_eq!(settings.master_volume, 1.0);
+        assert_eq!(settings.effects_volume, 0.0);
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        for (source, line) in [
+            ("vsync maybe", 1),
+            ("\nmaster_volume NaN", 2),
+            ("effects_volume inf", 1),
+            ("palette neon", 1),
+            ("display stretch", 1),
+            ("p2_device joystick", 1),
+            ("bind_fire NotAKey", 1),
+        ] {
+            let e = Settings::parse(source).unwrap_err();
+            assert_eq!(e.0, line, "{source}");
+        }
+    }
+}
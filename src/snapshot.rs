@@ -0,0 +1,374 @@
+use std::{fmt, fs, path::PathBuf};
+
+use bevy::{
+    asset::io::file::FileAssetReader,
+    ecs::entity::EntityHashMap,
+    input::common_conditions::input_just_pressed,
+    prelude::*,
+    scene::{ron, serde::SceneDeserializer, SceneSpawnError},
+    sprite::Mesh2dHandle,
+};
+use bevy_sepax2d::prelude::Sepax;
+use serde::de::DeserializeSeed;
+
+use crate::{
+    areas::{PlayArea, SafeArea, SpawnArea},
+    players::PlayerInput,
+    pool::GameAssets,
+    powerups::{self, Pickup, PowerUpEffect, PowerUpKind, TableEntry},
+    progress::{Lives, OwnLives, Score, Wave},
+    weapon::{Projectile, Weapon},
+    Attack, Health, MoveTo, Player, Rock, Ship, Spawner,
+};
+
+const QUICKSAVE: &str = "quicksave";
+
+pub fn snapshot_path(name: &str) -> PathBuf {
+    FileAssetReader::get_base_path()
+        .join("saves")
+        .join(format!("{name}.scn.ron"))
+}
+
+pub struct SnapshotPlugin;
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Player>()
+            .register_type::<Health>()
+            .register_type::<Attack>()
+            .register_type::<Spawner>()
+            .register_type::<MoveTo>()
+            .register_type::<Rock>()
+            .register_type::<Ship>()
+            .register_type::<Weapon>()
+            .register_type::<PowerUpKind>()
+            .register_type::<TableEntry>()
+            .register_type::<SpawnArea>()
+            .register_type::<SafeArea>()
+            .register_type::<PlayArea>()
+            .register_type::<Score>()
+            .register_type::<Lives>()
+            .register_type::<OwnLives>()
+            .register_type::<Wave>()
+            .add_systems(
+                Update,
+                (
+                    save_snapshot.run_if(input_just_pressed(KeyCode::F5)),
+                    load_snapshot.run_if(input_just_pressed(KeyCode::F9)),
+                ),
+            )
+            .add_systems(PostUpdate, rebuild_snapshot_entities);
+    }
+}
+
+fn saved_entities(world: &mut World) -> Vec<Entity> {
+    world
+        .query_filtered::<Entity, Or<(With<Player>, With<Rock>, With<Ship>, With<Spawner>)>>()
+        .iter(world)
+        .collect()
+}
+
+// The gameplay components of every saved entity and the area, score, lives
+// and wave resources, as RON.
+pub fn serialize(world: &mut World) -> Result<String, ron::Error> {
+    let entities = saved_entities(world);
+    // Power-up effects aren't saved, so neither are the boosts they would undo.
+    let effects: Vec<(PowerUpKind, Entity)> = world
+        .query::<&PowerUpEffect>()
+        .iter(world)
+        .map(|e| (e.kind, e.player))
+        .collect();
+    set_boosts(world, &effects, true);
+    let scene = DynamicSceneBuilder::from_world(world)
+        .deny_all()
+        .allow::<Transform>()
+        .allow::<Player>()
+        .allow::<Health>()
+        .allow::<Attack>()
+        .allow::<Spawner>()
+        .allow::<MoveTo>()
+        .allow::<Rock>()
+        .allow::<Ship>()
+        .allow::<Weapon>()
+        .allow::<OwnLives>()
+        .deny_all_resources()
+        .allow_resource::<SpawnArea>()
+        .allow_resource::<SafeArea>()
+        .allow_resource::<PlayArea>()
+        .allow_resource::<Score>()
+        .allow_resource::<Lives>()
+        .allow_resource::<Wave>()
+        .extract_entities(entities.into_iter())
+        .extract_resources()
+        .build();
+    set_boosts(world, &effects, false);
+    let registry = world.resource::<AppTypeRegistry>();
+    scene.serialize_ron(registry)
+}
+
+fn set_boosts(world: &mut World, effects: &[(PowerUpKind, Entity)], undo: bool) {
+    let mut players = world.query::<(&mut Player, Option<&mut Weapon>)>();
+    for (kind, player) in effects {
+        if let Ok((mut player, mut weapon)) = players.get_mut(world, *player) {
+            powerups::boost(*kind, &mut player, weapon.as_deref_mut(), undo);
+        }
+    }
+}
+
+// Pickups, shots and power-up effects only last a few seconds and aren't saved.
+fn transient_entities(world: &mut World) -> Vec<Entity> {
+    world
+        .query_filtered::<Entity, Or<(With<Pickup>, With<Projectile>, With<PowerUpEffect>)>>()
+        .iter(world)
+        .collect()
+}
+
+// Replaces every saved entity with the ones in `source`. Only gameplay
+// components are stored, `rebuild_snapshot_entities` adds meshes and colliders back.
+pub fn restore(world: &mut World, source: &str) -> Result<(), SnapshotError> {
+    let registry = world.resource::<AppTypeRegistry>().clone();
+    let scene = {
+        let registry = registry.read();
+        let deserializer = SceneDeserializer {
+            type_registry: &registry,
+        };
+        ron::Deserializer::from_str(source)
+            .map_err(ron::Error::from)
+            .and_then(|mut de| deserializer.deserialize(&mut de))
+            .map_err(SnapshotError::Parse)?
+    };
+    let mut replaced = saved_entities(world);
+    replaced.extend(transient_entities(world));
+    for e in replaced {
+        world.entity_mut(e).despawn_recursive();
+    }
+    scene
+        .write_to_world(world, &mut EntityHashMap::default())
+        .map_err(SnapshotError::Spawn)
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Parse(ron::Error),
+    Spawn(SceneSpawnError),
+}
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Parse(e) => write!(f, "could not parse: {e}"),
+            SnapshotError::Spawn(e) => write!(f, "could not spawn: {e}"),
+        }
+    }
+}
+impl std::error::Error for SnapshotError {}
+
+fn save_snapshot(world: &mut World) {
+    let ron = match serialize(world) {
+        Ok(ron) => ron,
+        Err(e) => {
+            error!("failed to serialize snapshot: {}", e);
+            return;
+        }
+    };
+    let path = snapshot_path(QUICKSAVE);
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, ron));
+    match written {
+        Ok(()) => info!("saved snapshot to {}", path.display()),
+        Err(e) => error!("failed to write {}: {}", path.display(), e),
+    }
+}
+
+fn load_snapshot(world: &mut World) {
+    let path = snapshot_path(QUICKSAVE);
+    let source = match fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(e) => {
+            error!("failed to read {}: {}", path.display(), e);
+            return;
+        }
+    };
+    match restore(world, &source) {
+        Ok(()) => info!("loaded snapshot from {}", path.display()),
+        Err(e) => error!("failed to load {}: {}", path.display(), e),
+    }
+}
+
+fn rebuild_snapshot_entities(
+    mut cmds: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    assets: Res<GameAssets>,
+    mut query: Query<
+        (
+            Entity,
+            &Transform,
+            Option<&Rock>,
+            Option<&mut Spawner>,
+            Has<Player>,
+            Has<Ship>,
+        ),
+        (
+            // Sprites replace the mesh, the collider is always there once rebuilt.
+            Without<Sepax>,
+            Or<(With<Player>, With<Rock>, With<Ship>, With<Spawner>)>,
+        ),
+    >,
+) {
+    // Spawners first, so restored rocks can share the meshes their spawner
+    // builds instead of each getting one of their own.
+    let mut rock_meshes: Vec<(f32, Mesh2dHandle)> = Vec::new();
+    for (e, transform, _, spawner, ..) in query.iter_mut() {
+        let Some(mut spawner) = spawner else {
+            continue;
+        };
+        if let Spawner::Rock {
+            sizes,
+            meshes: built,
+            ..
+        } = spawner.as_mut()
+        {
+            *built = crate::rock_meshes(&mut meshes, sizes);
+            rock_meshes.extend(sizes.iter().copied().zip(built.iter().cloned()));
+        }
+        cmds.entity(e)
+            .insert(crate::spawner_body(&spawner, &assets, *transform));
+    }
+    for (e, transform, rock, _, player, ship) in query.iter() {
+        let transform = *transform;
+        let mut entity = cmds.entity(e);
+        if player {
+            entity
+                .insert((
+                    crate::player_body(&mut meshes, &mut materials, transform),
+                    PlayerInput::default(),
+                ))
+                .with_children(|p| crate::player_parts(p, &mut meshes, &mut materials));
+        } else if ship {
+            entity.insert(crate::ship_body(&assets, transform));
+        } else if let Some(rock) = rock {
+            // A size no spawner makes any more gets one mesh for all its rocks.
+            let mesh = match rock_meshes.iter().find(|(size, _)| *size == rock.size) {
+                Some((_, mesh)) => mesh.clone(),
+                None => {
+                    let mesh = crate::rock_meshes(&mut meshes, &[rock.size]).remove(0);
+                    rock_meshes.push((rock.size, mesh.clone()));
+                    mesh
+                }
+            };
+            entity.insert(crate::rock_body(mesh, &assets, transform, rock.size));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world() -> World {
+        let mut app = App::new();
+        app.register_type::<Transform>()
+            .add_plugins(SnapshotPlugin)
+            .init_resource::<Score>()
+            .init_resource::<Lives>()
+            .init_resource::<Wave>();
+        std::mem::take(&mut app.world)
+    }
+
+    #[test]
+    fn restores_saved_entities_and_resources() {
+        let mut world = world();
+        world.spawn((
+            Transform::from_xyz(10.0, 20.0, 0.0),
+            Player {
+                movement_speed: 300.0,
+                rotation_speed: 5.0,
+                slot: 1,
+            },
+            Health(3),
+        ));
+        world.spawn((Transform::default(), Rock { size: 20.0 }));
+        let unsaved = world.spawn(Transform::default()).id();
+        world.resource_mut::<Score>().points = 1200;
+        world.resource_mut::<Lives>().0 = 2;
+        let source = serialize(&mut world).unwrap();
+
+        world.spawn((Transform::default(), Rock { size: 40.0 }));
+        world.resource_mut::<Score>().points = 0;
+        world.resource_mut::<Lives>().0 = 0;
+        restore(&mut world, &source).unwrap();
+
+        let players: Vec<_> = world
+            .query::<(&Transform, &Player, &Health)>()
+            .iter(&world)
+            .map(|(t, p, h)| (t.translation, p.slot, p.movement_speed, h.0))
+            .collect();
+        assert_eq!(players, vec![(Vec3::new(10.0, 20.0, 0.0), 1, 300.0, 3)]);
+        let rocks: Vec<f32> = world
+            .query::<&Rock>()
+            .iter(&world)
+            .map(|r| r.size)
+            .collect();
+        assert_eq!(rocks, vec![20.0]);
+        assert!(world.get_entity(unsaved).is_some());
+        assert_eq!(world.resource::<Score>().points, 1200);
+        assert_eq!(*world.resource::<Lives>(), Lives(2));
+    }
+
+    #[test]
+    fn saves_stats_without_power_up_boosts() {
+        let mut world = world();
+        let mut player = Player {
+            movement_speed: 300.0,
+            rotation_speed: 5.0,
+            slot: 0,
+        };
+        let mut weapon = Weapon::default();
+        powerups::boost(PowerUpKind::Speed, &mut player, None, false);
+        powerups::boost(
+            PowerUpKind::RapidFire,
+            &mut player,
+            Some(&mut weapon),
+            false,
+        );
+        let cooldown = |w: &Weapon| w.cooldown.duration().as_secs_f32();
+        let boosted = (player.movement_speed, cooldown(&weapon));
+        let e = world
+            .spawn((Transform::default(), player, weapon, Health(3)))
+            .id();
+        for kind in [PowerUpKind::Speed, PowerUpKind::RapidFire] {
+            world.spawn(PowerUpEffect { kind, player: e });
+        }
+        let source = serialize(&mut world).unwrap();
+        // Still boosted until the effects run out.
+        let live = (
+            world.get::<Player>(e).unwrap().movement_speed,
+            cooldown(world.get::<Weapon>(e).unwrap()),
+        );
+        assert_eq!(live.0, boosted.0);
+        assert!((live.1 - boosted.1).abs() < 1e-6);
+
+        world.spawn(Pickup {
+            kind: PowerUpKind::Shield,
+            life: Timer::from_seconds(1.0, TimerMode::Once),
+        });
+        restore(&mut world, &source).unwrap();
+        let (player, weapon) = world.query::<(&Player, &Weapon)>().single(&world);
+        assert_eq!(player.movement_speed, 300.0);
+        assert!((cooldown(weapon) - cooldown(&Weapon::default())).abs() < 1e-6);
+        assert_eq!(world.query::<&PowerUpEffect>().iter(&world).count(), 0);
+        assert_eq!(world.query::<&Pickup>().iter(&world).count(), 0);
+    }
+
+    #[test]
+    fn keeps_the_world_when_the_source_is_broken() {
+        let mut world = world();
+        world.spawn((Transform::default(), Rock { size: 20.0 }));
+        assert!(matches!(
+            restore(&mut world, "(entities: {"),
+            Err(SnapshotError::Parse(_))
+        ));
+        assert_eq!(world.query::<&Rock>().iter(&world).count(), 1);
+    }
+}
@@ -0,0 +1,306 @@
+use bevy::{
+    asset::{io::file::FileAssetReader, LoadState},
+    prelude::*,
+    sprite::Mesh2dHandle,
+};
+
+use crate::Rock;
+
+// What an entity looks like, so a sheet can replace its primitive mesh.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visual {
+    Player,
+    Ship,
+    Rock,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoopMode {
+    #[default]
+    Loop,
+    // Stops on the last frame.
+    Once,
+    // Runs forwards then backwards.
+    PingPong,
+}
+
+struct SheetDef {
+    visual: Visual,
+    // Relative to the assets folder.
+    path: &'static str,
+    tile: Vec2,
+    columns: usize,
+    rows: usize,
+    fps: f32,
+    mode: LoopMode,
+    // Drawn size in world units, rocks use their radius instead.
+    size: Vec2,
+}
+
+// Art is optional, anything missing keeps drawing the primitive meshes.
+const SHEETS: [SheetDef; 3] = [
+    SheetDef {
+        visual: Visual::Player,
+        path: "sprites/player.png",
+        tile: Vec2::new(32.0, 48.0),
+        columns: 4,
+        rows: 1,
+        fps: 10.0,
+        mode: LoopMode::Loop,
+        size: Vec2::new(30.0, 50.0),
+    },
+    SheetDef {
+        visual: Visual::Ship,
+        path: "sprites/ship.png",
+        tile: Vec2::new(40.0, 40.0),
+        columns: 4,
+        rows: 1,
+        fps: 8.0,
+        mode: LoopMode::PingPong,
+        size: Vec2::new(40.0, 40.0),
+    },
+    SheetDef {
+        visual: Visual::Rock,
+        path: "sprites/rock.png",
+        tile: Vec2::new(64.0, 64.0),
+        columns: 8,
+        rows: 1,
+        fps: 6.0,
+        mode: LoopMode::Loop,
+        size: Vec2::new(50.0, 50.0),
+    },
+];
+
+#[derive(Component, Clone, Debug)]
+pub struct Animation {
+    pub first: usize,
+    pub last: usize,
+    pub mode: LoopMode,
+    pub timer: Timer,
+    frame: usize,
+    reverse: bool,
+}
+impl Animation {
+    pub fn new(first: usize, last: usize, fps: f32, mode: LoopMode) -> Self {
+        Self {
+            first,
+            last,
+            mode,
+            timer: Timer::from_seconds(1.0 / fps.max(0.001), TimerMode::Repeating),
+            frame: first,
+            reverse: false,
+        }
+    }
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+    pub fn finished(&self) -> bool {
+        self.mode == LoopMode::Once && self.frame == self.last
+    }
+    fn advance(&mut self) {
+        if self.first >= self.last {
+            return;
+        }
+        match self.mode {
+            LoopMode::Loop => {
+                self.frame = if self.frame >= self.last {
+                    self.first
+                } else {
+                    self.frame + 1
+                }
+            }
+            LoopMode::Once => self.frame = (self.frame + 1).min(self.last),
+            LoopMode::PingPong => {
+                if self.frame >= self.last {
+                    self.reverse = true;
+                } else if self.frame <= self.first {
+                    self.reverse = false;
+                }
+                self.frame = if self.reverse {
+                    self.frame - 1
+                } else {
+                    self.frame + 1
+                };
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SheetState {
+    Loading,
+    Ready,
+    Missing,
+}
+
+struct Sheet {
+    def: &'static SheetDef,
+    image: Handle<Image>,
+    layout: Handle<TextureAtlasLayout>,
+    state: SheetState,
+}
+
+#[derive(Resource, Default)]
+pub struct SpriteSheets {
+    sheets: Vec<Sheet>,
+}
+impl SpriteSheets {
+    fn ready(&self, visual: Visual) -> Option<&Sheet> {
+        self.sheets
+            .iter()
+            .find(|s| s.def.visual == visual && s.state == SheetState::Ready)
+    }
+}
+
+pub struct SpriteSheetPlugin;
+impl Plugin for SpriteSheetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpriteSheets>()
+            .add_systems(Startup, load_sheets)
+            .add_systems(Update, (track_sheets, animate))
+            .add_systems(PostUpdate, swap_visuals);
+    }
+}
+
+fn load_sheets(
+    asset_server: Res<AssetServer>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut sheets: ResMut<SpriteSheets>,
+) {
+    let base = FileAssetReader::get_base_path().join("assets");
+    for def in SHEETS.iter() {
+        // Checked up front so missing art doesn't show up as load errors.
+        if !base.join(def.path).exists() {
+            info!(
+                "no art at {}, drawing {:?} with meshes",
+                def.path, def.visual
+            );
+            continue;
+        }
+        sheets.sheets.push(Sheet {
+            def,
+            image: asset_server.load(def.path),
+            layout: layouts.add(TextureAtlasLayout::from_grid(
+                def.tile,
+                def.columns,
+                def.rows,
+                None,
+                None,
+            )),
+            state: SheetState::Loading,
+        });
+    }
+}
+
+fn track_sheets(asset_server: Res<AssetServer>, mut sheets: ResMut<SpriteSheets>) {
+    let mut changed = false;
+    for sheet in sheets.bypass_change_detection().sheets.iter_mut() {
+        if sheet.state != SheetState::Loading {
+            continue;
+        }
+        sheet.state = match asset_server.get_load_state(&sheet.image) {
+            Some(LoadState::Loaded) => SheetState::Ready,
+            Some(LoadState::Failed) => {
+                warn!("failed to load {}, keeping meshes", sheet.def.path);
+                SheetState::Missing
+            }
+            _ => continue,
+        };
+        changed = true;
+    }
+    if changed {
+        sheets.set_changed();
+    }
+}
+
+// Replaces the mesh with a sprite once the sheet is ready. Runs again whenever
+// the mesh comes back, e.g. for an entity recycled by the pool.
+fn swap_visuals(
+    mut cmds: Commands,
+    sheets: Res<SpriteSheets>,
+    query: Query<(Entity, &Visual, Ref<Mesh2dHandle>, Option<&Rock>)>,
+) {
+    for (e, visual, mesh, rock) in query.iter() {
+        if !mesh.is_added() && !sheets.is_changed() {
+            continue;
+        }
+        let Some(sheet) = sheets.ready(*visual) else {
+            continue;
+        };
+        let def = sheet.def;
+        let size = rock.map_or(def.size, |r| Vec2::splat(r.size * 2.0));
+        let frames = def.columns * def.rows;
+        cmds.entity(e).remove::<Mesh2dHandle>().insert((
+            Sprite {
+                custom_size: Some(size),
+                ..default()
+            },
+            sheet.image.clone(),
+            TextureAtlas {
+                layout: sheet.layout.clone(),
+                index: 0,
+            },
+            Animation::new(0, frames.saturating_sub(1), def.fps, def.mode),
+        ));
+    }
+}
+
+fn animate(time: Res<Time>, mut query: Query<(&mut Animation, &mut TextureAtlas)>) {
+    for (mut animation, mut atlas) in query.iter_mut() {
+        let steps = animation
+            .timer
+            .tick(time.delta())
+            .times_finished_this_tick();
+        for _ in 0..steps {
+            animation.advance();
+        }
+        if atlas.index != animation.frame {
+            atlas.index = animation.frame;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(animation: &mut Animation, steps: usize) -> Vec<usize> {
+        (0..steps)
+            .map(|_| {
+                animation.advance();
+                animation.frame()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loop_wraps_around() {
+        let mut animation = Animation::new(2, 4, 10.0, LoopMode::Loop);
+        assert_eq!(animation.frame(), 2);
+        assert_eq!(frames(&mut animation, 5), [3, 4, 2, 3, 4]);
+        assert!(!animation.finished());
+    }
+
+    #[test]
+    fn once_stops_on_the_last_frame() {
+        let mut animation = Animation::new(0, 2, 10.0, LoopMode::Once);
+        assert!(!animation.finished());
+        assert_eq!(frames(&mut animation, 4), [1, 2, 2, 2]);
+        assert!(animation.finished());
+    }
+
+    #[test]
+    fn ping_pong_reverses_at_both_ends() {
+        let mut animation = Animation::new(1, 3, 10.0, LoopMode::PingPong);
+        assert_eq!(frames(&mut animation, 6), [2, 3, 2, 1, 2, 3]);
+        assert!(!animation.finished());
+    }
+
+    #[test]
+    fn single_frame_stays_put() {
+        for mode in [LoopMode::Loop, LoopMode::Once, LoopMode::PingPong] {
+            let mut animation = Animation::new(5, 5, 10.0, mode);
+            assert_eq!(frames(&mut animation, 3), [5, 5, 5]);
+        }
+    }
+}
@@ -0,0 +1,456 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Write},
+    fs, io,
+    path::PathBuf,
+};
+
+use bevy::{app::AppExit, asset::io::file::FileAssetReader, prelude::*};
+
+use crate::{
+    menu::GameState,
+    progress::{NewGame, PlayerHit, Wave},
+    settings::data_dir,
+    weapon::{Enemy, EnemyDestroyed},
+    Player,
+};
+
+const FILE_NAME: &str = "stats.txt";
+// Stats an achievement can be defined on, besides `rocks_<radius>`.
+const STAT_NAMES: [&str; 6] = [
+    "rocks",
+    "ships",
+    "damage_taken",
+    "longest_survival",
+    "best_wave",
+    "games",
+];
+
+// Lifetime totals across every game played.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    // Rocks destroyed, keyed by radius rounded to whole units.
+    pub rocks: BTreeMap<u32, u64>,
+    pub ships: u64,
+    pub damage_taken: u64,
+    // Seconds, in a single game.
+    pub longest_survival: f32,
+    pub best_wave: u32,
+    pub games: u64,
+    // Achievement ids.
+    pub unlocked: BTreeSet<String>,
+}
+
+impl Stats {
+    pub fn path() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join(FILE_NAME))
+    }
+
+    // A broken file is moved aside rather than overwritten by the next save.
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        let Ok(source) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        Self::parse(&source).unwrap_or_else(|e| {
+            let aside = path.with_extension("broken");
+            error!(
+                "ignoring {}: {}, kept as {}",
+                path.display(),
+                e,
+                aside.display()
+            );
+            let _ = fs::rename(&path, aside);
+            Self::default()
+        })
+    }
+
+    // `key value` lines like the settings file, `rocks <radius> <count>` once per size.
+    pub fn parse(source: &str) -> Result<Self, StatsError> {
+        let mut stats = Self::default();
+        for (n, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let bad = || StatsError::Line(n + 1, line.to_string());
+            let count = |i: usize| {
+                words
+                    .get(i)
+                    .and_then(|w| w.parse::<u64>().ok())
+                    .ok_or_else(bad)
+            };
+            match words.first().copied() {
+                None => {}
+                Some("rocks") => {
+                    let size = u32::try_from(count(1)?).map_err(|_| bad())?;
+                    stats.rocks.insert(size, count(2)?);
+                }
+                Some("ships") => stats.ships = count(1)?,
+                Some("damage_taken") => stats.damage_taken = count(1)?,
+                Some("longest_survival") => {
+                    stats.longest_survival = words
+                        .get(1)
+                        .and_then(|w| w.parse().ok())
+                        .filter(|s: &f32| s.is_finite() && *s >= 0.0)
+                        .ok_or_else(bad)?
+                }
+                Some("best_wave") => {
+                    stats.best_wave = u32::try_from(count(1)?).map_err(|_| bad())?
+                }
+                Some("games") => stats.games = count(1)?,
+                Some("unlocked") => {
+                    stats
+                        .unlocked
+                        .insert(words.get(1).ok_or_else(bad)?.to_string());
+                }
+                // Written by a newer build.
+                Some(_) => {}
+            }
+        }
+        Ok(stats)
+    }
+
+    pub fn to_source(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "games {}", self.games);
+        let _ = writeln!(out, "ships {}", self.ships);
+        let _ = writeln!(out, "damage_taken {}", self.damage_taken);
+        let _ = writeln!(out, "longest_survival {:.1}", self.longest_survival);
+        let _ = writeln!(out, "best_wave {}", self.best_wave);
+        for (size, count) in self.rocks.iter() {
+            let _ = writeln!(out, "rocks {size} {count}");
+        }
+        for id in self.unlocked.iter() {
+            let _ = writeln!(out, "unlocked {id}");
+        }
+        out
+    }
+
+    pub fn save(&self) -> Result<PathBuf, io::Error> {
+        let path = Self::path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, self.to_source())?;
+        Ok(path)
+    }
+
+    pub fn known(stat: &str) -> bool {
+        STAT_NAMES.contains(&stat)
+            || stat
+                .strip_prefix("rocks_")
+                .is_some_and(|size| size.parse::<u32>().is_ok())
+    }
+
+    pub fn value(&self, stat: &str) -> f64 {
+        match stat {
+            "rocks" => self.rocks.values().sum::<u64>() as f64,
+            "ships" => self.ships as f64,
+            "damage_taken" => self.damage_taken as f64,
+            "longest_survival" => self.longest_survival as f64,
+            "best_wave" => self.best_wave as f64,
+            "games" => self.games as f64,
+            _ => stat
+                .strip_prefix("rocks_")
+                .and_then(|size| size.parse().ok())
+                .and_then(|size| self.rocks.get(&size))
+                .map_or(0.0, |count| *count as f64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Achievement {
+    pub id: String,
+    pub stat: String,
+    pub threshold: f64,
+    pub title: String,
+}
+
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Achievements(pub Vec<Achievement>);
+
+impl Achievements {
+    pub fn path() -> PathBuf {
+        FileAssetReader::get_base_path()
+            .join("assets")
+            .join("achievements.txt")
+    }
+
+    pub fn load() -> Result<Self, StatsError> {
+        let path = Self::path();
+        let source = fs::read_to_string(&path).map_err(|e| StatsError::Io(path, e))?;
+        Self::parse(&source)
+    }
+
+    // `<id> <stat> <at least> <title...>` per line.
+    pub fn parse(source: &str) -> Result<Self, StatsError> {
+        let mut achievements = Vec::new();
+        for (n, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let bad = |msg: &str| StatsError::Line(n + 1, msg.to_string());
+            let mut words = line.split_whitespace();
+            let (Some(id), Some(stat), Some(threshold)) =
+                (words.next(), words.next(), words.next())
+            else {
+                return Err(bad("expected an id, stat, threshold and title"));
+            };
+            let title = words.collect::<Vec<_>>().join(" ");
+            if title.is_empty() {
+                return Err(bad("missing title"));
+            }
+            if !Stats::known(stat) {
+                return Err(bad(&format!("unknown stat {stat}")));
+            }
+            if achievements.iter().any(|a: &Achievement| a.id == id) {
+                return Err(bad(&format!("{id} is defined twice")));
+            }
+            achievements.push(Achievement {
+                id: id.into(),
+                stat: stat.into(),
+                threshold: threshold
+                    .parse()
+                    .map_err(|_| bad(&format!("invalid threshold {threshold}")))?,
+                title,
+            });
+        }
+        Ok(Self(achievements))
+    }
+}
+
+#[derive(Debug)]
+pub enum StatsError {
+    Io(PathBuf, io::Error),
+    Line(usize, String),
+}
+impl fmt::Display for StatsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatsError::Io(path, e) => write!(f, "could not read {}: {e}", path.display()),
+            StatsError::Line(line, msg) => write!(f, "line {line}: {msg}"),
+        }
+    }
+}
+impl std::error::Error for StatsError {}
+
+#[derive(Event, Debug, Clone)]
+pub struct Unlocked {
+    pub id: String,
+    pub title: String,
+}
+
+// Seconds survived in the current game.
+#[derive(Resource, Debug, Default)]
+struct RunTime(f32);
+
+pub struct StatsPlugin;
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        let achievements = Achievements::load().unwrap_or_else(|e| {
+            error!("no achievements: {e}");
+            Achievements::default()
+        });
+        app.insert_resource(Stats::load())
+            .insert_resource(achievements)
+            .init_resource::<RunTime>()
+            .add_event::<Unlocked>()
+            .add_systems(PreUpdate, start_run.run_if(on_event::<NewGame>()))
+            .add_systems(
+                FixedUpdate,
+                (count_kills, count_damage, track_survival, track_wave),
+            )
+            .add_systems(Update, check_achievements)
+            .add_systems(OnEnter(GameState::GameOver), save_stats)
+            .add_systems(OnEnter(GameState::MainMenu), save_stats)
+            // Quitting from the menu or closing the window mid-game.
+            .add_systems(Last, save_stats.run_if(on_event::<AppExit>()));
+    }
+}
+
+fn save(stats: &Stats) {
+    match stats.save() {
+        Ok(path) => info!("saved stats to {}", path.display()),
+        Err(e) => error!("failed to save stats: {}", e),
+    }
+}
+
+fn save_stats(stats: Res<Stats>) {
+    // Nothing played yet, no need for a file.
+    if stats.games == 0 {
+        return;
+    }
+    save(&stats);
+}
+
+fn start_run(mut events: EventReader<NewGame>, mut run: ResMut<RunTime>, mut stats: ResMut<Stats>) {
+    events.clear();
+    run.0 = 0.0;
+    stats.games += 1;
+}
+
+fn count_kills(mut destroyed: EventReader<EnemyDestroyed>, mut stats: ResMut<Stats>) {
+    for d in destroyed.read() {
+        match d.enemy {
+            Enemy::Rock { size } => *stats.rocks.entry(size.round() as u32).or_default() += 1,
+            Enemy::Ship => stats.ships += 1,
+        }
+    }
+}
+
+fn count_damage(mut hits: EventReader<PlayerHit>, mut stats: ResMut<Stats>) {
+    for hit in hits.read() {
+        stats.damage_taken += hit.damage.max(0) as u64;
+    }
+}
+
+fn track_survival(
+    time: Res<Time>,
+    players: Query<(), With<Player>>,
+    mut run: ResMut<RunTime>,
+    mut stats: ResMut<Stats>,
+) {
+    if players.is_empty() {
+        return;
+    }
+    run.0 += time.delta_seconds();
+    if run.0 > stats.longest_survival {
+        stats.longest_survival = run.0;
+    }
+}
+
+fn track_wave(wave: Res<Wave>, mut stats: ResMut<Stats>) {
+    if wave.number > stats.best_wave {
+        stats.best_wave = wave.number;
+    }
+}
+
+// Unlocks are saved straight away so a crash can't take them back.
+fn check_achievements(
+    mut stats: ResMut<Stats>,
+    achievements: Res<Achievements>,
+    mut unlocked: EventWriter<Unlocked>,
+) {
+    if !stats.is_changed() {
+        return;
+    }
+    let reached: Vec<&Achievement> = achievements
+        .0
+        .iter()
+        .filter(|a| !stats.unlocked.contains(&a.id) && stats.value(&a.stat) >= a.threshold)
+        .collect();
+    if reached.is_empty() {
+        return;
+    }
+    for a in reached {
+        info!("achievement unlocked: {}", a.title);
+        stats.unlocked.insert(a.id.clone());
+        unlocked.send(Unlocked {
+            id: a.id.clone(),
+            title: a.title.clone(),
+        });
+    }
+    save(&stats);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn played() -> Stats {
+        Stats {
+            rocks: BTreeMap::from([(10, 4), (40, 1)]),
+            ships: 3,
+            damage_taken: 250,
+            longest_survival: 93.5,
+            best_wave: 4,
+            games: 2,
+            unlocked: BTreeSet::from(["first_blood".to_string()]),
+        }
+    }
+
+    #[test]
+    fn source_round_trips() {
+        let stats = played();
+        assert_eq!(Stats::parse(&stats.to_source()).unwrap(), stats);
+        assert_eq!(Stats::parse("").unwrap(), Stats::default());
+    }
+
+    #[test]
+    fn skips_comments_and_unknown_stats() {
+        let stats = Stats::parse("# totals\ngames 3 # so far\nfuture_stat 9\n\nships 1\n").unwrap();
+        assert_eq!(stats.games, 3);
+        assert_eq!(stats.ships, 1);
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        for (source, line) in [
+            ("games -1", 1),
+            ("games 1\nships", 2),
+            ("rocks 10", 1),
+            ("rocks 99999999999 1", 1),
+            ("best_wave 99999999999", 1),
+            ("longest_survival soon", 1),
+            ("longest_survival NaN", 1),
+            ("longest_survival inf", 1),
+            ("games 1\nlongest_survival -1", 2),
+            ("games 1\n\nunlocked", 3),
+        ] {
+            assert!(
+                matches!(Stats::parse(source), Err(StatsError::Line(n, _)) if n == line),
+                "{source:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn looks_up_values() {
+        let stats = played();
+        assert_eq!(stats.value("rocks"), 5.0);
+        assert_eq!(stats.value("rocks_10"), 4.0);
+        assert_eq!(stats.value("rocks_25"), 0.0);
+        assert_eq!(stats.value("longest_survival"), 93.5);
+        assert_eq!(stats.value("games"), 2.0);
+        assert!(Stats::known("rocks_25"));
+        assert!(Stats::known("best_wave"));
+        assert!(!Stats::known("rocks_big"));
+        assert!(!Stats::known("jumps"));
+    }
+
+    #[test]
+    fn parses_achievements() {
+        let source = "# id stat threshold title\nfirst_blood rocks 1 First blood\n\nbig_game rocks_40 10 Big game hunter\n";
+        let achievements = Achievements::parse(source).unwrap();
+        assert_eq!(
+            achievements.0[1],
+            Achievement {
+                id: "big_game".into(),
+                stat: "rocks_40".into(),
+                threshold: 10.0,
+                title: "Big game hunter".into(),
+            }
+        );
+        assert_eq!(achievements.0.len(), 2);
+    }
+
+    #[test]
+    fn rejects_bad_achievements() {
+        for (source, line) in [
+            ("first rocks", 1),
+            ("first rocks 1", 1),
+            ("first jumps 1 Jumper", 1),
+            ("first rocks many Lots", 1),
+            ("first rocks 1 One\nfirst ships 1 Again", 2),
+        ] {
+            assert!(
+                matches!(Achievements::parse(source), Err(StatsError::Line(n, _)) if n == line),
+                "{source:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use bevy::{
+    prelude::*,
+    render::{camera::Viewport, view::RenderLayers},
+    window::{PrimaryWindow, WindowResized},
+};
+
+use crate::{
+    areas::{PlayArea, SafeArea, SpawnArea},
+    arena::Arena,
+    FIELD_SIZE,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ScaleMode {
+    // Always shows exactly the play field, with black bars on the longer axis.
+    #[default]
+    Letterbox,
+    // Fills the window and shows more of the world on the longer axis.
+    Expand,
+}
+
+impl ScaleMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScaleMode::Letterbox => "letterbox",
+            ScaleMode::Expand => "expand",
+        }
+    }
+}
+
+// The world is laid out in logical units of `size`, independent of the window.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct PlayField {
+    pub size: Vec2,
+    pub mode: ScaleMode,
+}
+impl Default for PlayField {
+    fn default() -> Self {
+        Self {
+            size: FIELD_SIZE,
+            mode: ScaleMode::default(),
+        }
+    }
+}
+
+impl PlayField {
+    // Window pixels per world unit.
+    pub fn scale(&self, window: Vec2) -> f32 {
+        (window / self.size).min_element()
+    }
+    // World units shown by the main camera for a window of this logical size.
+    pub fn visible(&self, window: Vec2) -> Vec2 {
+        match self.mode {
+            ScaleMode::Letterbox => self.size,
+            ScaleMode::Expand => window / self.scale(window),
+        }
+    }
+    // Centered viewport keeping the field aspect ratio, only used when letterboxing.
+    pub fn viewport(&self, physical: UVec2) -> Option<Viewport> {
+        if self.mode != ScaleMode::Letterbox {
+            return None;
+        }
+        let size = (self.size * self.scale(physical.as_vec2()))
+            .as_uvec2()
+            .clamp(UVec2::ONE, physical);
+        Some(Viewport {
+            physical_position: (physical - size) / 2,
+            physical_size: size,
+            ..default()
+        })
+    }
+}
+
+// Clears the whole window so the bars around a letterboxed viewport stay black.
+#[derive(Component)]
+struct LetterboxCamera;
+
+pub struct ViewPlugin;
+impl Plugin for ViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayField>()
+            .add_systems(Startup, spawn_letterbox_camera)
+            .add_systems(
+                Update,
+                (fit_ui, fit_areas)
+                    .run_if(on_event::<WindowResized>().or_else(resource_changed::<PlayField>)),
+            );
+    }
+}
+
+fn spawn_letterbox_camera(mut cmds: Commands) {
+    cmds.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                order: -1,
+                clear_color: ClearColorConfig::Custom(Color::BLACK),
+                ..default()
+            },
+            ..default()
+        },
+        RenderLayers::none(),
+        LetterboxCamera,
+    ));
+}
+
+fn window_size(windows: &Query<&Window, With<PrimaryWindow>>) -> Option<(Vec2, UVec2)> {
+    let window = windows.get_single().ok()?;
+    let physical = UVec2::new(window.physical_width(), window.physical_height());
+    // Minimised windows report a zero size.
+    if physical.x == 0 || physical.y == 0 {
+        return None;
+    }
+    Some((Vec2::new(window.width(), window.height()), physical))
+}
+
+// Physical rect of the window the game is drawn in, split between the player
+// views by `camera`, and the world size it shows before zooming.
+pub fn field_view(
+    field: &PlayField,
+    windows: &Query<&Window, With<PrimaryWindow>>,
+) -> Option<(URect, Vec2)> {
+    let (logical, physical) = window_size(windows)?;
+    let rect = field
+        .viewport(physical)
+        .map_or(URect::from_corners(UVec2::ZERO, physical), |v| {
+            URect::from_corners(v.physical_position, v.physical_position + v.physical_size)
+        });
+    Some((rect, field.visible(logical)))
+}
+
+fn fit_ui(
+    field: Res<PlayField>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut ui_scale: ResMut<UiScale>,
+) {
+    let Some((logical, _)) = window_size(&windows) else {
+        return;
+    };
+    // The HUD is laid out for the logical field size and scaled with it.
+    ui_scale.0 = field.scale(logical);
+}
+
+// Areas the arena file leaves out follow the visible extent, the ones it
+// defines are in world units and stay put.
+fn fit_areas(
+    field: Res<PlayField>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    arena: Option<Res<Arena>>,
+    mut spawn: ResMut<SpawnArea>,
+    mut safe: ResMut<SafeArea>,
+    mut play: ResMut<PlayArea>,
+) {
+    let Some((logical, _)) = window_size(&windows) else {
+        return;
+    };
+    let visible = field.visible(logical);
+    let arena = arena.as_deref();
+    if arena.map_or(true, |a| a.spawn.0.is_empty()) {
+        spawn.regions = SpawnArea::for_extent(visible).regions;
+    }
+    if arena.map_or(true, |a| a.safe.0.is_empty()) {
+        safe.regions = SafeArea::for_extent(visible).regions;
+    }
+    if arena.map_or(true, |a| a.play.0.is_empty()) {
+        play.regions = PlayArea::for_extent(visible).regions;
+    }
+}
+
+// Cursor position in world space. The cursor is window relative while the
+// camera expects it relative to its (possibly letterboxed) viewport.
+pub fn cursor_world(window: &Window, camera: &Camera, transform: &GlobalTransform) -> Option<Vec2> {
+    let cursor = window.cursor_position()?;
+    let offset = camera.logical_viewport_rect().map_or(Vec2::ZERO, |r| r.min);
+    camera.viewport_to_world_2d(transform, cursor - offset)
+}
@@ -0,0 +1,145 @@
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use bevy_sepax2d::prelude::{
+    sepax2d::{sat_overlap, Circle as SpxCircle},
+    *,
+};
+
+use crate::{
+    players::PlayerInput,
+    pool::{EntityPool, GameAssets, PoolKind, PROJECTILE_RADIUS},
+    Player, Rock, Ship,
+};
+
+const PROJECTILE_LIFETIME: f32 = 1.5;
+// Distance in front of the player the projectile appears at.
+const MUZZLE: f32 = 30.0;
+
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct Weapon {
+    pub cooldown: Timer,
+    pub projectile_speed: f32,
+}
+impl Default for Weapon {
+    fn default() -> Self {
+        Self {
+            cooldown: Timer::from_seconds(0.3, TimerMode::Once),
+            projectile_speed: 500.0,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Projectile {
+    pub velocity: Vec2,
+    pub life: Timer,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Enemy {
+    Rock { size: f32 },
+    Ship,
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct EnemyDestroyed {
+    pub enemy: Enemy,
+    pub position: Vec2,
+}
+
+pub struct WeaponPlugin;
+impl Plugin for WeaponPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<EnemyDestroyed>().add_systems(
+            FixedUpdate,
+            (
+                fire.after(crate::player_movement),
+                move_projectiles,
+                projectile_hits.after(move_projectiles),
+            ),
+        );
+    }
+}
+
+pub fn fire(
+    mut cmds: Commands,
+    mut pool: ResMut<EntityPool>,
+    assets: Res<GameAssets>,
+    time: Res<Time>,
+    mut players: Query<(&Transform, &PlayerInput, &mut Weapon), With<Player>>,
+) {
+    for (transform, input, mut weapon) in players.iter_mut() {
+        weapon.cooldown.tick(time.delta());
+        if !input.fire || !weapon.cooldown.finished() {
+            continue;
+        }
+        weapon.cooldown.reset();
+        let forward = (transform.rotation * Vec3::Y).xy();
+        let position = transform.translation.xy() + forward * MUZZLE;
+        let e = pool.take(&mut cmds, PoolKind::Projectile);
+        cmds.entity(e).insert((
+            MaterialMesh2dBundle {
+                mesh: assets.projectile_mesh.clone(),
+                material: assets.projectile_material.clone(),
+                transform: Transform::from_xyz(position.x, position.y, 2.),
+                ..default()
+            },
+            Sepax {
+                convex: Convex::Circle(SpxCircle::new((0., 0.), PROJECTILE_RADIUS)),
+            },
+            Movable { axes: Vec::new() },
+            Projectile {
+                velocity: forward * weapon.projectile_speed,
+                life: Timer::from_seconds(PROJECTILE_LIFETIME, TimerMode::Once),
+            },
+        ));
+    }
+}
+
+pub fn move_projectiles(
+    mut cmds: Commands,
+    mut pool: ResMut<EntityPool>,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Projectile, &mut Transform)>,
+) {
+    for (e, mut projectile, mut transform) in query.iter_mut() {
+        projectile.life.tick(time.delta());
+        if projectile.life.finished() {
+            pool.release(&mut cmds, PoolKind::Projectile, e);
+            continue;
+        }
+        let delta = projectile.velocity * time.delta_seconds();
+        transform.translation += delta.extend(0.);
+    }
+}
+
+pub fn projectile_hits(
+    mut cmds: Commands,
+    projectiles: Query<(Entity, &Sepax), With<Projectile>>,
+    enemies: Query<(Entity, &Sepax, &Transform, Option<&Rock>), Or<(With<Rock>, With<Ship>)>>,
+    mut destroyed: EventWriter<EnemyDestroyed>,
+    mut pool: ResMut<EntityPool>,
+) {
+    // Each enemy can only be destroyed once even when several shots reach it together.
+    let mut hit = Vec::new();
+    for (p, shot) in projectiles.iter() {
+        let target = enemies
+            .iter()
+            .find(|(e, s, ..)| !hit.contains(e) && sat_overlap(s.shape(), shot.shape()));
+        let Some((e, _, transform, rock)) = target else {
+            continue;
+        };
+        hit.push(e);
+        let enemy = rock.map_or(Enemy::Ship, |r| Enemy::Rock { size: r.size });
+        let kind = match enemy {
+            Enemy::Rock { .. } => PoolKind::Rock,
+            Enemy::Ship => PoolKind::Ship,
+        };
+        pool.release(&mut cmds, PoolKind::Projectile, p);
+        pool.release(&mut cmds, kind, e);
+        destroyed.send(EnemyDestroyed {
+            enemy,
+            position: transform.translation.xy(),
+        });
+    }
+}